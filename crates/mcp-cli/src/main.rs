@@ -0,0 +1,379 @@
+//! `mcp`: connect to an MCP server over stdio or SSE, to see what it offers and call it.
+
+mod output;
+mod repl;
+
+use std::{collections::HashMap, process::ExitCode, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use mcp_client::{
+    client::{ClientCapabilities, ClientInfo},
+    transport::SseTransportBuilder,
+    McpClient, McpClientTrait, McpService, SseTransport, StdioTransport, Transport,
+};
+use mcp_core::{
+    prompt::Prompt,
+    protocol::{GetPromptResult, InitializeResult},
+    Resource, Tool,
+};
+use serde_json::Value;
+use tracing_subscriber::EnvFilter;
+
+use crate::output::Printer;
+
+#[derive(Parser)]
+#[command(
+    name = "mcp",
+    version,
+    about = "Inspect MCP servers and call them from the terminal"
+)]
+struct Cli {
+    /// Run the server as a subprocess and talk to it over stdio, e.g. "uvx mcp-server-git".
+    /// The command is split into words as a shell would, so quote arguments with spaces in them.
+    #[arg(long, value_name = "COMMAND", required_unless_present = "sse")]
+    stdio: Option<String>,
+
+    /// Connect to the server's SSE endpoint
+    #[arg(long, value_name = "URL", conflicts_with = "stdio")]
+    sse: Option<String>,
+
+    /// Set an environment variable for the server process
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = parse_env)]
+    env: Vec<(String, String)>,
+
+    /// Send a header with every SSE request
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Authenticate SSE requests with a bearer token
+    #[arg(long, env = "MCP_BEARER_TOKEN", hide_env_values = true)]
+    bearer_token: Option<String>,
+
+    /// How many seconds to wait for each response
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    timeout: u64,
+
+    /// Print results as JSON, as the server sent them
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show what the server said about itself when initializing
+    Info,
+    /// List or call tools
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommand,
+    },
+    /// List or read resources
+    Resources {
+        #[command(subcommand)]
+        command: ResourcesCommand,
+    },
+    /// List or get prompts
+    Prompts {
+        #[command(subcommand)]
+        command: PromptsCommand,
+    },
+    /// Start an interactive session, which asks for tool arguments one by one
+    Repl,
+}
+
+#[derive(Subcommand)]
+enum ToolsCommand {
+    List,
+    Call {
+        name: String,
+        /// The arguments, as a JSON object
+        #[arg(long, default_value = "{}")]
+        args: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ResourcesCommand {
+    List,
+    Read { uri: String },
+}
+
+#[derive(Subcommand)]
+enum PromptsCommand {
+    List,
+    Get {
+        name: String,
+        /// The arguments, as a JSON object of strings
+        #[arg(long, default_value = "{}")]
+        args: String,
+    },
+}
+
+fn parse_env(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("Expected KEY=VALUE, got {arg}"))
+}
+
+fn parse_header(arg: &str) -> Result<(String, String), String> {
+    arg.split_once(':')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| format!("Expected NAME: VALUE, got {arg}"))
+}
+
+/// Split a command line into words the way a POSIX shell does, minus expansions: words are
+/// separated by unquoted whitespace, single quotes keep everything, and within double quotes a
+/// backslash only escapes `"`, `\`, `$` and `` ` ``.
+fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated ' in --stdio command"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => word.extend(['\\', c]),
+                            None => bail!("Unterminated \" in --stdio command"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated \" in --stdio command"),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("Trailing \\ in --stdio command"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The transport, kept so it can be closed once we're done.
+enum Connection {
+    Stdio(StdioTransport),
+    Sse(SseTransport),
+}
+
+impl Connection {
+    async fn close(&self) {
+        let _ = match self {
+            Connection::Stdio(transport) => transport.close().await,
+            Connection::Sse(transport) => transport.close().await,
+        };
+    }
+}
+
+/// An initialized client, with what the server told us about itself.
+pub struct Session {
+    client: Box<dyn McpClientTrait>,
+    info: InitializeResult,
+}
+
+impl Session {
+    pub async fn tools(&self) -> Result<Vec<Tool>> {
+        let mut tools = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.client.list_tools(cursor).await?;
+            tools.extend(page.tools);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    pub async fn resources(&self) -> Result<Vec<Resource>> {
+        let mut resources = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.client.list_resources(cursor).await?;
+            resources.extend(page.resources);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return Ok(resources);
+            }
+        }
+    }
+
+    pub async fn prompts(&self) -> Result<Vec<Prompt>> {
+        let mut prompts = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.client.list_prompts(cursor).await?;
+            prompts.extend(page.prompts);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return Ok(prompts);
+            }
+        }
+    }
+
+    pub async fn get_prompt(&self, name: &str, arguments: Value) -> Result<GetPromptResult> {
+        Ok(self.client.get_prompt(name, arguments).await?)
+    }
+}
+
+fn client_info() -> ClientInfo {
+    ClientInfo {
+        name: "mcp-cli".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+async fn connect(cli: &Cli) -> Result<(Connection, Session)> {
+    let timeout = Duration::from_secs(cli.timeout);
+    let (connection, mut client): (_, Box<dyn McpClientTrait>) = match (&cli.stdio, &cli.sse) {
+        (Some(command), _) => {
+            let mut words = split_command(command)?.into_iter();
+            let program = words
+                .next()
+                .ok_or_else(|| anyhow!("--stdio needs a command"))?;
+            let env: HashMap<_, _> = cli.env.iter().cloned().collect();
+            let transport = StdioTransport::new(program, words.collect(), env);
+            let handle = transport.start().await?;
+            let client = McpClient::new(McpService::with_timeout(handle, timeout));
+            (Connection::Stdio(transport), Box::new(client))
+        }
+        (None, Some(url)) => {
+            let mut builder = SseTransportBuilder::new(url.as_str());
+            for (name, value) in &cli.headers {
+                builder = builder.with_header(name, value);
+            }
+            if let Some(token) = &cli.bearer_token {
+                builder = builder.with_bearer_token(token);
+            }
+            let transport = builder.build()?;
+            let handle = transport.start().await?;
+            let client = McpClient::new(McpService::with_timeout(handle, timeout));
+            (Connection::Sse(transport), Box::new(client))
+        }
+        (None, None) => bail!("Either --stdio or --sse is needed"),
+    };
+
+    let info = client
+        .initialize(client_info(), ClientCapabilities::default())
+        .await
+        .context("Failed to initialize")?;
+    Ok((connection, Session { client, info }))
+}
+
+/// Parse `--args` as a JSON object.
+fn parse_args(args: &str) -> Result<Value> {
+    let value: Value = serde_json::from_str(args).context("--args isn't valid JSON")?;
+    if !value.is_object() {
+        bail!("--args must be a JSON object");
+    }
+    Ok(value)
+}
+
+/// Run a single command. Returns false if it ran, but the server reported a failure.
+async fn run(session: &Session, command: Command, printer: &Printer) -> Result<bool> {
+    match command {
+        Command::Info => printer.info(&session.info)?,
+        Command::Tools { command } => match command {
+            ToolsCommand::List => printer.tools(&session.tools().await?)?,
+            ToolsCommand::Call { name, args } => {
+                let result = session.client.call_tool(&name, parse_args(&args)?).await?;
+                printer.call_result(&result)?;
+                return Ok(result.is_error != Some(true));
+            }
+        },
+        Command::Resources { command } => match command {
+            ResourcesCommand::List => printer.resources(&session.resources().await?)?,
+            ResourcesCommand::Read { uri } => {
+                printer.resource_contents(&session.client.read_resource(&uri).await?)?
+            }
+        },
+        Command::Prompts { command } => match command {
+            PromptsCommand::List => printer.prompts(&session.prompts().await?)?,
+            PromptsCommand::Get { name, args } => {
+                printer.prompt(&session.get_prompt(&name, parse_args(&args)?).await?)?
+            }
+        },
+        Command::Repl => repl::run(session, printer).await?,
+    }
+    Ok(true)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Logs go to stderr, to keep stdout for results. The server's stderr is logged at info.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    let printer = Printer::new(cli.json);
+    let (connection, session) = match connect(&cli).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = run(&session, cli.command, &printer).await;
+    connection.close().await;
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        let words = |command| split_command(command).unwrap();
+        assert_eq!(words("uvx  mcp-server-git "), ["uvx", "mcp-server-git"]);
+        assert_eq!(
+            words(r#"npx -y server '/Users/ada/My Documents' "a \"b\" c" d\ e ''"#),
+            [
+                "npx",
+                "-y",
+                "server",
+                "/Users/ada/My Documents",
+                r#"a "b" c"#,
+                "d e",
+                ""
+            ]
+        );
+        assert_eq!(words(r#"echo "C:\tmp""#), ["echo", r"C:\tmp"]);
+        assert!(split_command("node 'server.js").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Printing results, either for people or as JSON.
+
+use anyhow::Result;
+use mcp_core::{
+    content::EmbeddedResource,
+    prompt::{Prompt, PromptMessageContent, PromptMessageRole},
+    protocol::{CallToolResult, GetPromptResult, InitializeResult, ReadResourceResult},
+    Content, Resource, ResourceContents, Tool,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+pub struct Printer {
+    json: bool,
+}
+
+impl Printer {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+
+    fn json<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        Ok(())
+    }
+
+    pub fn info(&self, info: &InitializeResult) -> Result<()> {
+        if self.json {
+            return self.json(info);
+        }
+        let server = &info.server_info;
+        println!("{} {}", server.name, server.version);
+        println!("Protocol: {}", info.protocol_version);
+        let capabilities = &info.capabilities;
+        let mut supported = Vec::new();
+        if capabilities.tools.is_some() {
+            supported.push("tools");
+        }
+        if capabilities.resources.is_some() {
+            supported.push("resources");
+        }
+        if capabilities.prompts.is_some() {
+            supported.push("prompts");
+        }
+        println!("Capabilities: {}", none_if_empty(&supported.join(", ")));
+        if let Some(instructions) = &info.instructions {
+            println!("\n{instructions}");
+        }
+        Ok(())
+    }
+
+    pub fn tools(&self, tools: &[Tool]) -> Result<()> {
+        if self.json {
+            return self.json(tools);
+        }
+        for tool in tools {
+            println!("{}", tool.name);
+            if !tool.description.is_empty() {
+                println!("    {}", tool.description);
+            }
+            for parameter in parameters(&tool.input_schema) {
+                println!("    - {}", parameter.summary());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn call_result(&self, result: &CallToolResult) -> Result<()> {
+        if self.json {
+            return self.json(result);
+        }
+        if result.is_error == Some(true) {
+            eprintln!("The tool reported an error:");
+        }
+        for content in &result.content {
+            match content {
+                Content::Text(text) => println!("{}", text.text),
+                Content::Image(image) => {
+                    println!("{}", image_summary(&image.mime_type, &image.data))
+                }
+                Content::Resource(resource) => self.embedded(resource),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn resources(&self, resources: &[Resource]) -> Result<()> {
+        if self.json {
+            return self.json(resources);
+        }
+        for resource in resources {
+            match &resource.mime_type {
+                Some(mime_type) => println!("{} ({}, {mime_type})", resource.uri, resource.name),
+                None => println!("{} ({})", resource.uri, resource.name),
+            }
+            if let Some(description) = &resource.description {
+                println!("    {description}");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn resource_contents(&self, result: &ReadResourceResult) -> Result<()> {
+        if self.json {
+            return self.json(result);
+        }
+        for contents in &result.contents {
+            print_contents(contents);
+        }
+        Ok(())
+    }
+
+    pub fn prompts(&self, prompts: &[Prompt]) -> Result<()> {
+        if self.json {
+            return self.json(prompts);
+        }
+        for prompt in prompts {
+            println!("{}", prompt.name);
+            if let Some(description) = &prompt.description {
+                println!("    {description}");
+            }
+            for argument in prompt.arguments.iter().flatten() {
+                let required = if argument.required == Some(true) {
+                    ", required"
+                } else {
+                    ""
+                };
+                match &argument.description {
+                    Some(description) => {
+                        println!("    - {} (string{required}): {description}", argument.name)
+                    }
+                    None => println!("    - {} (string{required})", argument.name),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn prompt(&self, result: &GetPromptResult) -> Result<()> {
+        if self.json {
+            return self.json(result);
+        }
+        if let Some(description) = &result.description {
+            println!("{description}\n");
+        }
+        for message in &result.messages {
+            let role = match message.role {
+                PromptMessageRole::User => "user",
+                PromptMessageRole::Assistant => "assistant",
+            };
+            print!("[{role}] ");
+            match &message.content {
+                PromptMessageContent::Text { text } => println!("{text}"),
+                PromptMessageContent::Image { image } => {
+                    println!("{}", image_summary(&image.mime_type, &image.data))
+                }
+                PromptMessageContent::Resource { resource } => self.embedded(resource),
+            }
+        }
+        Ok(())
+    }
+
+    fn embedded(&self, resource: &EmbeddedResource) {
+        print_contents(&resource.resource);
+    }
+}
+
+fn print_contents(contents: &ResourceContents) {
+    match contents {
+        ResourceContents::TextResourceContents { text, .. } => println!("{text}"),
+        ResourceContents::BlobResourceContents {
+            uri,
+            mime_type,
+            blob,
+        } => println!(
+            "[{uri}: {}, {} bytes of base64]",
+            mime_type.as_deref().unwrap_or("binary"),
+            blob.len()
+        ),
+    }
+}
+
+fn image_summary(mime_type: &str, data: &str) -> String {
+    format!("[image: {mime_type}, {} bytes of base64]", data.len())
+}
+
+fn none_if_empty(list: &str) -> &str {
+    if list.is_empty() {
+        "none"
+    } else {
+        list
+    }
+}
+
+/// A property of a tool's input schema.
+pub struct Parameter<'a> {
+    pub name: &'a str,
+    pub schema: &'a Value,
+    pub required: bool,
+}
+
+impl Parameter<'_> {
+    /// The type the schema gives, if it gives exactly one.
+    pub fn kind(&self) -> Option<&str> {
+        self.schema.get("type").and_then(Value::as_str)
+    }
+
+    /// e.g. `units (string, required): "metric" or "imperial"`
+    pub fn summary(&self) -> String {
+        let mut details = vec![self.kind().unwrap_or("any").to_string()];
+        if self.required {
+            details.push("required".to_string());
+        }
+        let mut summary = format!("{} ({})", self.name, details.join(", "));
+        if let Some(description) = self.schema.get("description").and_then(Value::as_str) {
+            summary.push_str(": ");
+            summary.push_str(description);
+        }
+        if let Some(choices) = self.schema.get("enum").and_then(Value::as_array) {
+            let choices: Vec<_> = choices.iter().map(Value::to_string).collect();
+            summary.push_str(&format!(" [one of {}]", choices.join(", ")));
+        }
+        summary
+    }
+}
+
+/// The properties of an object schema, with the required ones first.
+pub fn parameters(schema: &Value) -> Vec<Parameter<'_>> {
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let mut parameters: Vec<_> = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| Parameter {
+            name,
+            schema,
+            required: required.contains(&name.as_str()),
+        })
+        .collect();
+    parameters.sort_by_key(|parameter| !parameter.required);
+    parameters
+}
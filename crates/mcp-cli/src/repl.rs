@@ -0,0 +1,268 @@
+//! An interactive session. Calling a tool without arguments asks for each one its input schema
+//! lists, and parses the answer by the type the schema gives it.
+
+use std::io::Write;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+
+use crate::{
+    output::{parameters, Parameter, Printer},
+    parse_args, Session,
+};
+
+const HELP: &str = "\
+Commands:
+  info                    What the server said about itself
+  tools                   List the tools
+  call <tool> [ARGS]      Call a tool. Without ARGS, asks for each argument
+  resources               List the resources
+  read <uri>              Read a resource
+  prompts                 List the prompts
+  prompt <name> [ARGS]    Get a prompt. Without ARGS, asks for each argument
+  help                    Show this
+  quit                    Leave
+
+ARGS is a JSON object, e.g. {\"city\": \"Paris\"}";
+
+type Input = Lines<BufReader<Stdin>>;
+
+/// Print `prompt` and read a line. `None` at the end of input.
+async fn read_line(input: &mut Input, prompt: &str) -> Result<Option<String>> {
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+    let line = input.next_line().await?;
+    if line.is_none() {
+        println!();
+    }
+    Ok(line)
+}
+
+pub async fn run(session: &Session, printer: &Printer) -> Result<()> {
+    let server = &session.info.server_info;
+    println!(
+        "Connected to {} {}. Type help for the commands.",
+        server.name, server.version
+    );
+
+    let mut input = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = read_line(&mut input, "mcp> ").await? {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let result = match command {
+            "" => Ok(()),
+            "help" => {
+                println!("{HELP}");
+                Ok(())
+            }
+            "quit" | "exit" => break,
+            "info" => printer.info(&session.info),
+            "tools" => match session.tools().await {
+                Ok(tools) => printer.tools(&tools),
+                Err(e) => Err(e),
+            },
+            "call" => call(session, printer, &mut input, rest).await,
+            "resources" => match session.resources().await {
+                Ok(resources) => printer.resources(&resources),
+                Err(e) => Err(e),
+            },
+            "read" if !rest.is_empty() => match session.client.read_resource(rest).await {
+                Ok(contents) => printer.resource_contents(&contents),
+                Err(e) => Err(e.into()),
+            },
+            "prompts" => match session.prompts().await {
+                Ok(prompts) => printer.prompts(&prompts),
+                Err(e) => Err(e),
+            },
+            "prompt" => prompt(session, printer, &mut input, rest).await,
+            _ => Err(anyhow!(
+                "Unknown command: {line}. Type help for the commands."
+            )),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {e:#}");
+        }
+    }
+    Ok(())
+}
+
+/// Split `name [ARGS]`.
+fn name_and_args(rest: &str) -> Result<(&str, Option<Value>)> {
+    let (name, args) = rest.split_once(' ').unwrap_or((rest, ""));
+    if name.is_empty() {
+        bail!("Expected a name");
+    }
+    let args = args.trim();
+    if args.is_empty() {
+        Ok((name, None))
+    } else {
+        Ok((name, Some(parse_args(args)?)))
+    }
+}
+
+async fn call(session: &Session, printer: &Printer, input: &mut Input, rest: &str) -> Result<()> {
+    let (name, args) = name_and_args(rest)?;
+    let args = match args {
+        Some(args) => args,
+        None => {
+            let tools = session.tools().await?;
+            let tool = tools
+                .iter()
+                .find(|tool| tool.name == name)
+                .ok_or_else(|| anyhow!("The server has no tool called {name}"))?;
+            match ask_for_arguments(input, parameters(&tool.input_schema)).await? {
+                Some(args) => args,
+                None => return Ok(()),
+            }
+        }
+    };
+    printer.call_result(&session.client.call_tool(name, args).await?)
+}
+
+async fn prompt(session: &Session, printer: &Printer, input: &mut Input, rest: &str) -> Result<()> {
+    let (name, args) = name_and_args(rest)?;
+    let args = match args {
+        Some(args) => args,
+        None => {
+            let prompts = session.prompts().await?;
+            let prompt = prompts
+                .iter()
+                .find(|prompt| prompt.name == name)
+                .ok_or_else(|| anyhow!("The server has no prompt called {name}"))?;
+            // Prompt arguments are always strings
+            let schemas: Vec<_> = prompt
+                .arguments
+                .iter()
+                .flatten()
+                .map(|argument| {
+                    let mut schema = serde_json::json!({ "type": "string" });
+                    if let Some(description) = &argument.description {
+                        schema["description"] = description.clone().into();
+                    }
+                    (argument, schema)
+                })
+                .collect();
+            let parameters = schemas
+                .iter()
+                .map(|(argument, schema)| Parameter {
+                    name: &argument.name,
+                    schema,
+                    required: argument.required == Some(true),
+                })
+                .collect();
+            match ask_for_arguments(input, parameters).await? {
+                Some(args) => args,
+                None => return Ok(()),
+            }
+        }
+    };
+    printer.prompt(&session.get_prompt(name, args).await?)
+}
+
+/// Ask for each parameter in turn. An empty answer leaves an optional parameter out. `None` if
+/// the input ended before all the answers were in.
+async fn ask_for_arguments(
+    input: &mut Input,
+    parameters: Vec<Parameter<'_>>,
+) -> Result<Option<Value>> {
+    let mut args = Map::new();
+    for parameter in parameters {
+        loop {
+            let Some(answer) = read_line(input, &format!("  {}> ", parameter.summary())).await?
+            else {
+                return Ok(None);
+            };
+            if answer.is_empty() {
+                if parameter.required {
+                    eprintln!("  {} is required", parameter.name);
+                    continue;
+                }
+                break;
+            }
+            match parse_value(parameter.kind(), &answer) {
+                Ok(value) => {
+                    args.insert(parameter.name.to_string(), value);
+                    break;
+                }
+                Err(e) => eprintln!("  {e}"),
+            }
+        }
+    }
+    Ok(Some(Value::Object(args)))
+}
+
+/// Parse an answer as the JSON type `kind`. Strings are taken as typed, arrays and objects are
+/// written as JSON, and an answer for a parameter without a type is JSON if it parses as JSON
+/// and a string otherwise.
+fn parse_value(kind: Option<&str>, answer: &str) -> Result<Value, String> {
+    match kind {
+        Some("string") => Ok(Value::String(answer.to_string())),
+        Some("integer") => answer
+            .trim()
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| format!("{answer} isn't an integer")),
+        Some("number") => answer
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("{answer} isn't a number")),
+        Some("boolean") => match answer.trim().to_lowercase().as_str() {
+            "true" | "yes" | "y" => Ok(Value::Bool(true)),
+            "false" | "no" | "n" => Ok(Value::Bool(false)),
+            _ => Err(format!("{answer} isn't true or false")),
+        },
+        Some(kind @ ("array" | "object" | "null")) => serde_json::from_str::<Value>(answer)
+            .ok()
+            .filter(|value| match kind {
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => value.is_null(),
+            })
+            .ok_or_else(|| format!("{answer} isn't a JSON {kind}")),
+        _ => Ok(serde_json::from_str(answer).unwrap_or_else(|_| Value::String(answer.into()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value(Some("string"), " 42 "), Ok(json!(" 42 ")));
+        assert_eq!(parse_value(Some("integer"), "42"), Ok(json!(42)));
+        assert!(parse_value(Some("integer"), "4.2").is_err());
+        assert_eq!(parse_value(Some("number"), "4.5"), Ok(json!(4.5)));
+        assert_eq!(parse_value(Some("boolean"), "Yes"), Ok(json!(true)));
+        assert!(parse_value(Some("boolean"), "maybe").is_err());
+        assert_eq!(parse_value(Some("array"), "[1, 2]"), Ok(json!([1, 2])));
+        assert!(parse_value(Some("array"), "{}").is_err());
+        assert_eq!(parse_value(None, "{\"a\": 1}"), Ok(json!({ "a": 1 })));
+        assert_eq!(parse_value(None, "Paris"), Ok(json!("Paris")));
+    }
+
+    #[test]
+    fn test_parameters_required_first() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "a": { "type": "integer" },
+                "b": { "type": "string", "description": "The b", "enum": ["x", "y"] },
+            },
+            "required": ["b"],
+        });
+        let parameters = parameters(&schema);
+        let names: Vec<_> = parameters.iter().map(|p| p.name).collect();
+        assert_eq!(names, ["b", "a"]);
+        assert_eq!(
+            parameters[0].summary(),
+            "b (string, required): The b [one of \"x\", \"y\"]"
+        );
+    }
+}
@@ -2,17 +2,27 @@ use mcp_core::{
     protocol::{
         CallToolResult, GetPromptResult, Implementation, InitializeResult, JsonRpcNotification,
         JsonRpcRequest, JsonRpcResponse, ListPromptsResult, ListResourcesResult, ListToolsResult,
-        MessageId, ReadResourceResult, ServerCapabilities, METHOD_NOT_FOUND,
+        MessageId, ReadResourceResult, ServerCapabilities, ServerNotification, METHOD_NOT_FOUND,
     },
+    schema::{describe, validate, ValidationError},
     transport::SendableMessage,
+    Tool,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
 use tokio::sync::Mutex;
 use tower::{Service, ServiceExt}; // for Service::ready()
 
+use crate::handler::{Dispatcher, Notifications};
+
+use crate::service::HasDispatcher;
+/// What the client supports. Set `elicitation` if it can handle `elicitation/create` requests,
+/// see `ElicitationHandler`.
+pub use mcp_core::protocol::ClientCapabilities;
+
 pub type BoxError = Box<dyn std::error::Error + Sync + Send>;
 
 /// Error type for MCP client operations.
@@ -22,7 +32,11 @@ pub enum Error {
     Transport(#[from] super::transport::Error),
 
     #[error("RPC error: code={code}, message={message}")]
-    RpcError { code: i32, message: String },
+    RpcError {
+        code: i32,
+        message: String,
+        data: Option<Value>,
+    },
 
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
@@ -42,6 +56,21 @@ pub enum Error {
     #[error("Error from mcp-server: {0}")]
     ServerBoxError(BoxError),
 
+    #[error("Invalid arguments for tool {tool}: {}", describe(.errors))]
+    InvalidArguments {
+        tool: String,
+        errors: Vec<ValidationError>,
+    },
+
+    #[error("No server offers a {kind} called {name}")]
+    UnknownTarget { kind: &'static str, name: String },
+
+    #[error("A client called {0} was already added")]
+    DuplicateClient(String),
+
+    #[error("Another server already offers a {kind} called {name}")]
+    NameCollision { kind: &'static str, name: String },
+
     #[error("Call to '{server}' failed for '{method}'. {source}")]
     McpServerError {
         method: String,
@@ -58,17 +87,12 @@ impl From<BoxError> for Error {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientInfo {
     pub name: String,
     pub version: String,
 }
 
-#[derive(Serialize, Deserialize, Default)]
-pub struct ClientCapabilities {
-    // Add fields as needed. For now, empty capabilities are fine.
-}
-
 #[derive(Serialize, Deserialize)]
 pub struct InitializeParams {
     #[serde(rename = "protocolVersion")]
@@ -100,6 +124,14 @@ pub trait McpClientTrait: Send + Sync {
     async fn list_prompts(&self, next_cursor: Option<String>) -> Result<ListPromptsResult, Error>;
 
     async fn get_prompt(&self, name: &str, arguments: Value) -> Result<GetPromptResult, Error>;
+
+    /// What the server said it supports when it was initialized, or `None` before that.
+    fn server_capabilities(&self) -> Option<&ServerCapabilities>;
+
+    /// Subscribe to the notifications the server sends from now on, such as
+    /// `notifications/tools/list_changed` or log messages. Use [`Notifications::with_methods`] to
+    /// only receive some of them.
+    fn subscribe(&self) -> Notifications;
 }
 
 /// The MCP client is the interface for MCP operations.
@@ -110,24 +142,138 @@ where
     S::Future: Send,
 {
     service: Mutex<S>,
+    dispatcher: Dispatcher,
     next_id: AtomicU64,
     server_capabilities: Option<ServerCapabilities>,
     server_info: Option<Implementation>,
+    tools: ToolCache,
+    validate_arguments: bool,
+}
+
+/// The server's tools as of the last `tools/list`, kept so `call_tool` can check arguments
+/// without asking the server. Dropped when the server says the list changed.
+struct ToolCache {
+    inner: std::sync::Mutex<ToolCacheInner>,
+}
+
+struct ToolCacheInner {
+    tools: HashMap<String, Tool>,
+    /// Whether every page of the list has been seen since the cache was last dropped
+    complete: bool,
+    changes: Notifications,
+}
+
+impl ToolCache {
+    fn new(dispatcher: &Dispatcher) -> Self {
+        let changes = dispatcher
+            .subscribe()
+            .with_methods([ServerNotification::TOOL_LIST_CHANGED]);
+        Self {
+            inner: std::sync::Mutex::new(ToolCacheInner {
+                tools: HashMap::new(),
+                complete: false,
+                changes,
+            }),
+        }
+    }
+
+    /// Add a page of `tools/list` results, the first if `first`.
+    fn update(&self, first: bool, tools: &[Tool], complete: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if first {
+            inner.changes.drain();
+            inner.tools.clear();
+        }
+        inner
+            .tools
+            .extend(tools.iter().map(|tool| (tool.name.clone(), tool.clone())));
+        inner.complete = complete;
+    }
+
+    /// The cached tool called `name`, or `Err` if the cache needs filling first.
+    fn get(&self, name: &str) -> Result<Option<Tool>, ()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.changes.drain() {
+            inner.tools.clear();
+            inner.complete = false;
+        }
+        if !inner.complete {
+            return Err(());
+        }
+        Ok(inner.tools.get(name).cloned())
+    }
+
+    fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tools.clear();
+        inner.complete = false;
+    }
 }
 
 impl<S> McpClient<S>
 where
-    S: Service<SendableMessage, Response = Option<JsonRpcResponse>> + Clone + Send + Sync + 'static,
+    S: Service<SendableMessage, Response = Option<JsonRpcResponse>>
+        + HasDispatcher
+        + Clone
+        + Send
+        + Sync
+        + 'static,
     S::Error: Into<Error>,
     S::Future: Send,
 {
     pub fn new(service: S) -> Self {
+        let dispatcher = service.dispatcher().clone();
+        Self::with_dispatcher(service, dispatcher)
+    }
+}
+
+impl<S> McpClient<S>
+where
+    S: Service<SendableMessage, Response = Option<JsonRpcResponse>> + Clone + Send + Sync + 'static,
+    S::Error: Into<Error>,
+    S::Future: Send,
+{
+    /// Create a client for a service that doesn't implement `HasDispatcher`. `dispatcher` should
+    /// be the dispatcher of the transport the service sends messages over.
+    pub fn with_dispatcher(service: S, dispatcher: Dispatcher) -> Self {
         Self {
             service: Mutex::new(service),
+            tools: ToolCache::new(&dispatcher),
+            dispatcher,
             next_id: AtomicU64::new(1),
             server_capabilities: None,
             server_info: None,
+            validate_arguments: true,
+        }
+    }
+
+    /// Whether `call_tool` checks arguments against the tool's `input_schema` before sending them,
+    /// failing with `Error::InvalidArguments` instead of making the call. On by default.
+    pub fn with_argument_validation(mut self, enabled: bool) -> Self {
+        self.validate_arguments = enabled;
+        self
+    }
+
+    /// Drop the cached tool definitions, so they are listed again before the next `call_tool`.
+    /// This happens by itself when the server sends `notifications/tools/list_changed`.
+    pub fn invalidate_tools(&self) {
+        self.tools.clear();
+    }
+
+    /// The definition of the tool called `name`, listing the server's tools first if they aren't
+    /// cached.
+    async fn tool(&self, name: &str) -> Result<Option<Tool>, Error> {
+        if let Ok(tool) = self.tools.get(name) {
+            return Ok(tool);
         }
+        let mut cursor = None;
+        loop {
+            cursor = self.list_tools(cursor).await?.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(self.tools.get(name).ok().flatten())
     }
 
     /// Send a JSON-RPC request
@@ -167,11 +313,12 @@ where
 
         match response {
             JsonRpcResponse::Success { result, .. } => {
-                Ok(serde_json::from_value(result).map_err(|e| Error::Serialization(e))?)
+                Ok(serde_json::from_value(result).map_err(Error::Serialization)?)
             }
             JsonRpcResponse::Error { error, .. } => Err(Error::RpcError {
                 code: error.code,
                 message: error.message,
+                data: error.data,
             }),
         }
     }
@@ -282,6 +429,7 @@ where
             return Err(Error::RpcError {
                 code: METHOD_NOT_FOUND,
                 message: "Server does not support 'resources' capability".to_string(),
+                data: None,
             });
         }
 
@@ -301,11 +449,15 @@ where
             });
         }
 
+        let first = next_cursor.is_none();
         let payload = next_cursor
             .map(|cursor| serde_json::json!({"cursor": cursor}))
             .unwrap_or_else(|| serde_json::json!({}));
 
-        self.send_request("tools/list", payload).await
+        let result: ListToolsResult = self.send_request("tools/list", payload).await?;
+        self.tools
+            .update(first, &result.tools, result.next_cursor.is_none());
+        Ok(result)
     }
 
     async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, Error> {
@@ -317,9 +469,28 @@ where
             return Err(Error::RpcError {
                 code: METHOD_NOT_FOUND,
                 message: "Server does not support 'tools' capability".to_string(),
+                data: None,
             });
         }
 
+        if self.validate_arguments {
+            if let Some(tool) = self.tool(name).await? {
+                // Leaving out the arguments is the same as passing none
+                let empty = Value::Object(Default::default());
+                let checked = if arguments.is_null() {
+                    &empty
+                } else {
+                    &arguments
+                };
+                validate(&tool.input_schema, checked).map_err(|errors| {
+                    Error::InvalidArguments {
+                        tool: name.to_string(),
+                        errors,
+                    }
+                })?;
+            }
+        }
+
         let params = serde_json::json!({ "name": name, "arguments": arguments });
 
         // TODO ERROR: check that if there is an error, we send back is_error: true with msg
@@ -337,6 +508,7 @@ where
             return Err(Error::RpcError {
                 code: METHOD_NOT_FOUND,
                 message: "Server does not support 'prompts' capability".to_string(),
+                data: None,
             });
         }
 
@@ -357,6 +529,7 @@ where
             return Err(Error::RpcError {
                 code: METHOD_NOT_FOUND,
                 message: "Server does not support 'prompts' capability".to_string(),
+                data: None,
             });
         }
 
@@ -364,4 +537,105 @@ where
 
         self.send_request("prompts/get", params).await
     }
+
+    fn server_capabilities(&self) -> Option<&ServerCapabilities> {
+        self.server_capabilities.as_ref()
+    }
+
+    fn subscribe(&self) -> Notifications {
+        self.dispatcher.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_call_tool_validates_arguments_against_cached_tools() {
+        let methods = Arc::new(std::sync::Mutex::new(Vec::new()));
+        // A server with one tool, `add`, that records the methods it's called with
+        let recorded = methods.clone();
+        let service = tower::service_fn(move |message: SendableMessage| {
+            let methods = recorded.clone();
+            async move {
+                let SendableMessage::Request(request) = message else {
+                    return Ok(None);
+                };
+                methods.lock().unwrap().push(request.method.clone());
+                let result = match request.method.as_str() {
+                    "initialize" => json!({
+                        "protocolVersion": "2024-11-05",
+                        "capabilities": { "tools": {} },
+                        "serverInfo": { "name": "test", "version": "1.0.0" },
+                    }),
+                    "tools/list" => json!({
+                        "tools": [{
+                            "name": "add",
+                            "description": "Add to the counter",
+                            "inputSchema": {
+                                "type": "object",
+                                "properties": { "amount": { "type": "integer" } },
+                                "required": ["amount"],
+                            },
+                        }],
+                    }),
+                    _ => json!({ "content": [] }),
+                };
+                Ok::<_, Error>(Some(JsonRpcResponse::success(request.id, result)))
+            }
+        });
+        let dispatcher = Dispatcher::new();
+        let mut client = McpClient::with_dispatcher(service, dispatcher.clone());
+        client
+            .initialize(
+                ClientInfo {
+                    name: "test".to_string(),
+                    version: "1.0.0".to_string(),
+                },
+                ClientCapabilities::default(),
+            )
+            .await
+            .unwrap();
+        let sent = || methods.lock().unwrap().split_off(0);
+        sent();
+
+        // Invalid arguments are caught without calling the tool
+        let result = client.call_tool("add", json!({ "amount": "one" })).await;
+        let Err(error @ Error::InvalidArguments { .. }) = result else {
+            panic!("Expected invalid arguments, got {result:?}");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Invalid arguments for tool add: /amount: expected integer, got string"
+        );
+        assert_eq!(sent(), vec!["tools/list"]);
+
+        // The tools stay cached until the list changes
+        client
+            .call_tool("add", json!({ "amount": 1 }))
+            .await
+            .unwrap();
+        assert_eq!(sent(), vec!["tools/call"]);
+
+        dispatcher.notify(JsonRpcNotification::new(
+            ServerNotification::TOOL_LIST_CHANGED.to_string(),
+            None,
+        ));
+        client
+            .call_tool("add", json!({ "amount": 1 }))
+            .await
+            .unwrap();
+        assert_eq!(sent(), vec!["tools/list", "tools/call"]);
+
+        client.invalidate_tools();
+        assert!(client.call_tool("add", Value::Null).await.is_err());
+        assert_eq!(sent(), vec!["tools/list"]);
+
+        let client = client.with_argument_validation(false);
+        client.call_tool("add", Value::Null).await.unwrap();
+        assert_eq!(sent(), vec!["tools/call"]);
+    }
 }
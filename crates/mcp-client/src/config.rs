@@ -0,0 +1,548 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use futures::future::join_all;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::client::{self, ClientCapabilities, ClientInfo, McpClient, McpClientTrait};
+use crate::pool::McpClientPool;
+use crate::service::McpService;
+use crate::transport::{self, SseTransport, SseTransportBuilder, StdioTransport, Transport};
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid JSON config: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid TOML config: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Invalid config for server {server}: {reason}")]
+    InvalidServer { server: String, reason: String },
+
+    #[error("Config for server {server} uses ${{{variable}}}, which isn't set")]
+    MissingVariable { server: String, variable: String },
+
+    #[error("Server {server} failed to start: {source}")]
+    Start {
+        server: String,
+        source: transport::Error,
+    },
+
+    #[error("Server {server} failed to initialize: {source}")]
+    Initialize {
+        server: String,
+        source: client::Error,
+    },
+}
+
+/// How to reach one server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerConfig {
+    /// Run `command` with `args`, and talk to it over stdio. `env` is added to the environment
+    /// the process inherits.
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: BTreeMap<String, String>,
+    },
+    /// Connect to the SSE endpoint at `url`, sending `headers` with every request.
+    Sse {
+        url: String,
+        headers: BTreeMap<String, String>,
+    },
+}
+
+/// A server entry as it is written, before it is checked.
+#[derive(Deserialize)]
+struct RawServer {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    url: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    disabled: bool,
+}
+
+/// Servers are only parsed one by one, so a bad entry leaves the others usable.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawConfig {
+    #[serde(default)]
+    mcp_servers: BTreeMap<String, serde_json::Value>,
+}
+
+impl RawServer {
+    fn check(self, server: &str) -> Result<ServerConfig, ConfigError> {
+        let invalid = |reason: &str| ConfigError::InvalidServer {
+            server: server.to_string(),
+            reason: reason.to_string(),
+        };
+        let kind = match (self.kind.as_deref(), &self.command, &self.url) {
+            (Some(kind), _, _) => kind,
+            (None, Some(_), None) => "stdio",
+            (None, None, Some(_)) => "sse",
+            (None, Some(_), Some(_)) => return Err(invalid("it has both a command and a url")),
+            (None, None, None) => return Err(invalid("it needs a command or a url")),
+        };
+        match kind {
+            "stdio" => {
+                if self.url.is_some() || !self.headers.is_empty() {
+                    return Err(invalid("url and headers only apply to sse servers"));
+                }
+                Ok(ServerConfig::Stdio {
+                    command: self
+                        .command
+                        .ok_or_else(|| invalid("stdio servers need a command"))?,
+                    args: self.args,
+                    env: self.env,
+                })
+            }
+            "sse" => {
+                if self.command.is_some() || !self.args.is_empty() || !self.env.is_empty() {
+                    return Err(invalid("command, args and env only apply to stdio servers"));
+                }
+                Ok(ServerConfig::Sse {
+                    url: self.url.ok_or_else(|| invalid("sse servers need a url"))?,
+                    headers: self.headers,
+                })
+            }
+            kind => Err(invalid(&format!("unsupported type {kind}"))),
+        }
+    }
+}
+
+/// Replace each `${NAME}` in `value` with the variable `NAME`, or with `default` for
+/// `${NAME:-default}` when it isn't set.
+fn expand(
+    value: &str,
+    server: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, ConfigError> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| ConfigError::InvalidServer {
+            server: server.to_string(),
+            reason: format!("unterminated ${{ in {value}"),
+        })?;
+        let (variable, default) = match after[..end].split_once(":-") {
+            Some((variable, default)) => (variable, Some(default)),
+            None => (&after[..end], None),
+        };
+        match (lookup(variable), default) {
+            (Some(found), _) => expanded.push_str(&found),
+            (None, Some(default)) => expanded.push_str(default),
+            (None, None) => {
+                return Err(ConfigError::MissingVariable {
+                    server: server.to_string(),
+                    variable: variable.to_string(),
+                })
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+impl ServerConfig {
+    /// Expand variables in everything but the names of `env` and `headers`.
+    fn expanded(
+        &self,
+        server: &str,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let expand = |value: &String| expand(value, server, lookup);
+        let expand_values = |map: &BTreeMap<String, String>| {
+            map.iter()
+                .map(|(name, value)| Ok((name.clone(), expand(value)?)))
+                .collect::<Result<_, ConfigError>>()
+        };
+        Ok(match self {
+            ServerConfig::Stdio { command, args, env } => ServerConfig::Stdio {
+                command: expand(command)?,
+                args: args.iter().map(expand).collect::<Result<_, _>>()?,
+                env: expand_values(env)?,
+            },
+            ServerConfig::Sse { url, headers } => ServerConfig::Sse {
+                url: expand(url)?,
+                headers: expand_values(headers)?,
+            },
+        })
+    }
+}
+
+/// The `mcpServers` config many hosts share, in JSON:
+///
+/// ```json
+/// {
+///   "mcpServers": {
+///     "git": { "command": "uvx", "args": ["mcp-server-git"], "env": { "GIT_DIR": "${HOME}/src" } },
+///     "remote": { "url": "https://mcp.example.com/sse", "headers": { "Authorization": "Bearer ${TOKEN}" } }
+///   }
+/// }
+/// ```
+///
+/// or TOML, with a `[mcpServers.git]` table per server. A server is run over stdio if it has a
+/// `command`, and reached over SSE if it has a `url`; `"type": "stdio"` or `"type": "sse"` may
+/// say so explicitly. Servers with `"disabled": true` are left out. Other fields are ignored.
+/// Servers whose entries are invalid, e.g. of a type other than those, are reported in
+/// [`McpServersConfig::invalid`] rather than failing the whole config.
+///
+/// `${NAME}` and `${NAME:-default}` are replaced with environment variables when connecting, in
+/// the command, args, url and the values of `env` and `headers`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct McpServersConfig {
+    pub servers: BTreeMap<String, ServerConfig>,
+    /// Why each server that couldn't be used was left out, by name
+    pub invalid: BTreeMap<String, String>,
+}
+
+impl McpServersConfig {
+    pub fn from_json(contents: &str) -> Result<Self, ConfigError> {
+        Self::from_raw(serde_json::from_str(contents)?)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        Self::from_raw(toml::from_str(contents)?)
+    }
+
+    /// Load the config at `path`, as TOML if it ends in `.toml` and as JSON otherwise.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = tokio::fs::read_to_string(path).await?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            Self::from_toml(&contents)
+        } else {
+            Self::from_json(&contents)
+        }
+    }
+
+    fn from_raw(raw: RawConfig) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (name, server) in raw.mcp_servers {
+            let checked = serde_json::from_value::<RawServer>(server)
+                .map_err(|e| e.to_string())
+                .and_then(|server| match server.disabled {
+                    true => Ok(None),
+                    false => server.check(&name).map(Some).map_err(|e| match e {
+                        ConfigError::InvalidServer { reason, .. } => reason,
+                        e => e.to_string(),
+                    }),
+                });
+            match checked {
+                Ok(Some(server)) => {
+                    config.servers.insert(name, server);
+                }
+                Ok(None) => {}
+                Err(reason) => {
+                    tracing::warn!(server = %name, %reason, "Leaving out invalid server");
+                    config.invalid.insert(name, reason);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Start every server and initialize a client for it, all at once. Requests to a server fail
+    /// after `timeout`, including `initialize`.
+    ///
+    /// A server that can't be started or initialized, whose config uses a variable that isn't
+    /// set, or whose entry is invalid, is reported in [`ConnectedServers::errors`] rather than
+    /// failing the rest.
+    pub async fn connect(
+        &self,
+        info: ClientInfo,
+        capabilities: ClientCapabilities,
+        timeout: Duration,
+    ) -> ConnectedServers {
+        let lookup = |variable: &str| std::env::var(variable).ok();
+        let connecting = self.servers.iter().map(|(name, config)| {
+            let (info, capabilities) = (info.clone(), capabilities.clone());
+            async move {
+                let result = async {
+                    let config = config.expanded(name, &lookup)?;
+                    connect(name, config, info, capabilities, timeout).await
+                };
+                (name.clone(), result.await)
+            }
+        });
+
+        let mut connected = ConnectedServers {
+            clients: BTreeMap::new(),
+            errors: self
+                .invalid
+                .iter()
+                .map(|(name, reason)| {
+                    let error = ConfigError::InvalidServer {
+                        server: name.clone(),
+                        reason: reason.clone(),
+                    };
+                    (name.clone(), error)
+                })
+                .collect(),
+            transports: Vec::new(),
+        };
+        for (name, result) in join_all(connecting).await {
+            match result {
+                Ok((client, transport)) => {
+                    connected.clients.insert(name.clone(), client);
+                    connected.transports.push((name, transport));
+                }
+                Err(e) => {
+                    connected.errors.insert(name, e);
+                }
+            }
+        }
+        connected
+    }
+}
+
+/// A started transport, kept to close it.
+enum Started {
+    Stdio(StdioTransport),
+    Sse(SseTransport),
+}
+
+impl Started {
+    async fn close(&self) -> Result<(), transport::Error> {
+        match self {
+            Started::Stdio(transport) => transport.close().await,
+            Started::Sse(transport) => transport.close().await,
+        }
+    }
+}
+
+async fn start<T: Transport>(
+    transport: &T,
+    timeout: Duration,
+) -> Result<Box<dyn McpClientTrait>, transport::Error> {
+    let handle = transport.start().await?;
+    Ok(Box::new(McpClient::new(McpService::with_timeout(
+        handle, timeout,
+    ))))
+}
+
+async fn connect(
+    server: &str,
+    config: ServerConfig,
+    info: ClientInfo,
+    capabilities: ClientCapabilities,
+    timeout: Duration,
+) -> Result<(Box<dyn McpClientTrait>, Started), ConfigError> {
+    let started = match config {
+        ServerConfig::Stdio { command, args, env } => {
+            let transport = StdioTransport::new(command, args, env.into_iter().collect());
+            start(&transport, timeout)
+                .await
+                .map(|client| (client, Started::Stdio(transport)))
+        }
+        ServerConfig::Sse { url, headers } => {
+            let transport = headers
+                .into_iter()
+                .fold(SseTransportBuilder::new(url), |builder, (name, value)| {
+                    builder.with_header(name, value)
+                })
+                .build();
+            match transport {
+                Ok(transport) => start(&transport, timeout)
+                    .await
+                    .map(|client| (client, Started::Sse(transport))),
+                Err(e) => Err(e),
+            }
+        }
+    };
+    let (mut client, transport) = started.map_err(|source| ConfigError::Start {
+        server: server.to_string(),
+        source,
+    })?;
+
+    if let Err(source) = client.initialize(info, capabilities).await {
+        let _ = transport.close().await;
+        return Err(ConfigError::Initialize {
+            server: server.to_string(),
+            source,
+        });
+    }
+    Ok((client, transport))
+}
+
+/// The clients [`McpServersConfig::connect`] made, by server name, and the servers it couldn't
+/// connect to.
+pub struct ConnectedServers {
+    pub clients: BTreeMap<String, Box<dyn McpClientTrait>>,
+    pub errors: BTreeMap<String, ConfigError>,
+    transports: Vec<(String, Started)>,
+}
+
+impl ConnectedServers {
+    /// Move the clients into a pool, to call them through one catalog. `close` still stops the
+    /// servers.
+    pub fn take_pool(&mut self) -> McpClientPool {
+        std::mem::take(&mut self.clients).into_iter().fold(
+            McpClientPool::new(),
+            |pool, (name, client)| {
+                pool.with_boxed_client(name, client)
+                    .expect("Server names are the keys of a map, so they are unique")
+            },
+        )
+    }
+
+    /// Close every transport, stopping the servers that were run as processes.
+    pub async fn close(&self) {
+        let closing = self.transports.iter().map(|(name, transport)| async move {
+            if let Err(e) = transport.close().await {
+                tracing::warn!(server = %name, error = %e, "Failed to close transport");
+            }
+        });
+        join_all(closing).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_and_toml() {
+        let json = r#"{
+            "mcpServers": {
+                "git": { "command": "uvx", "args": ["mcp-server-git"], "env": { "A": "1" } },
+                "remote": { "url": "https://mcp.example.com/sse", "headers": { "X-Team": "tools" } },
+                "explicit": { "type": "sse", "url": "http://localhost:8000/sse" },
+                "off": { "command": "nope", "disabled": true }
+            },
+            "otherHostSetting": true
+        }"#;
+        let config = McpServersConfig::from_json(json).unwrap();
+        assert_eq!(
+            config.servers.keys().collect::<Vec<_>>(),
+            ["explicit", "git", "remote"]
+        );
+        assert_eq!(
+            config.servers["git"],
+            ServerConfig::Stdio {
+                command: "uvx".to_string(),
+                args: vec!["mcp-server-git".to_string()],
+                env: BTreeMap::from([("A".to_string(), "1".to_string())]),
+            }
+        );
+
+        let toml = r#"
+            [mcpServers.git]
+            command = "uvx"
+            args = ["mcp-server-git"]
+            env = { A = "1" }
+
+            [mcpServers.remote]
+            url = "https://mcp.example.com/sse"
+            headers = { X-Team = "tools" }
+
+            [mcpServers.explicit]
+            type = "sse"
+            url = "http://localhost:8000/sse"
+        "#;
+        assert_eq!(McpServersConfig::from_toml(toml).unwrap(), config);
+    }
+
+    #[test]
+    fn test_invalid_servers() {
+        for (server, reason) in [
+            (r#"{}"#, "needs a command or a url"),
+            (r#"{"command": "a", "url": "b"}"#, "both"),
+            (r#"{"type": "http", "url": "b"}"#, "unsupported type http"),
+            (r#"{"type": "sse", "command": "a"}"#, "only apply to stdio"),
+            (r#"{"command": "a", "args": "b"}"#, "invalid type"),
+        ] {
+            // The bad server is left out, and the good one kept
+            let json =
+                format!(r#"{{"mcpServers": {{"bad": {server}, "good": {{"command": "uvx"}}}}}}"#);
+            let config = McpServersConfig::from_json(&json).unwrap();
+            assert_eq!(config.servers.keys().collect::<Vec<_>>(), ["good"]);
+            let actual = &config.invalid["bad"];
+            assert!(actual.contains(reason), "{actual}");
+        }
+
+        // A disabled server isn't checked
+        let config = McpServersConfig::from_json(
+            r#"{"mcpServers": {"off": {"type": "http", "disabled": true}}}"#,
+        )
+        .unwrap();
+        assert_eq!(config, McpServersConfig::default());
+    }
+
+    #[test]
+    fn test_expand() {
+        let lookup = |variable: &str| (variable == "HOME").then(|| "/home/me".to_string());
+        let expand = |value: &str| expand(value, "git", &lookup);
+        assert_eq!(expand("${HOME}/src").unwrap(), "/home/me/src");
+        assert_eq!(expand("${PORT:-8000}:${HOME:-x}").unwrap(), "8000:/home/me");
+        assert_eq!(
+            expand("no variables, $HOME").unwrap(),
+            "no variables, $HOME"
+        );
+        assert!(matches!(
+            expand("Bearer ${TOKEN}"),
+            Err(ConfigError::MissingVariable { variable, .. }) if variable == "TOKEN"
+        ));
+        assert!(matches!(
+            expand("${HOME"),
+            Err(ConfigError::InvalidServer { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connect_reports_errors_per_server() {
+        let config = McpServersConfig::from_json(
+            r#"{
+                "mcpServers": {
+                    "missing": { "command": "mcp-config-test-no-such-command" },
+                    "secret": {
+                        "url": "http://localhost:1/sse",
+                        "headers": { "Authorization": "Bearer ${MCP_CONFIG_TEST_UNSET}" }
+                    },
+                    "remote": { "type": "http", "url": "http://localhost:1/mcp" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let info = ClientInfo {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+        };
+        let connected = config
+            .connect(info, ClientCapabilities::default(), Duration::from_secs(5))
+            .await;
+        assert!(connected.clients.is_empty());
+        assert!(matches!(
+            connected.errors["missing"],
+            ConfigError::Start { .. } | ConfigError::Initialize { .. }
+        ));
+        assert!(matches!(
+            connected.errors["secret"],
+            ConfigError::MissingVariable { .. }
+        ));
+        assert!(matches!(
+            &connected.errors["remote"],
+            ConfigError::InvalidServer { reason, .. } if reason == "unsupported type http"
+        ));
+        connected.close().await;
+    }
+}
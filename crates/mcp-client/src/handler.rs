@@ -0,0 +1,365 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use futures::Stream;
+use mcp_core::protocol::{
+    CreateElicitationRequestParams, CreateElicitationResult, ErrorData, JsonRpcNotification,
+    JsonRpcRequest, JsonRpcResponse, ServerNotification, INTERNAL_ERROR, INVALID_PARAMS,
+    METHOD_NOT_FOUND,
+};
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+
+/// How many notifications a subscriber may fall behind by before it starts missing them.
+const NOTIFICATION_CAPACITY: usize = 256;
+
+/// Handles `elicitation/create` requests, where the server asks the user for structured input
+/// mid-operation.
+///
+/// Implementations should present `params.message` to the user along with a form built from
+/// `params.requested_schema`, and report whether the user accepted (with the submitted data),
+/// declined, or cancelled. Returning an error sends a JSON-RPC error back to the server instead.
+///
+/// Clients with a handler should advertise the `elicitation` capability in their
+/// `ClientCapabilities` when initializing, and register it with
+/// [`Dispatcher::set_elicitation_handler`].
+#[async_trait]
+pub trait ElicitationHandler: Send + Sync + 'static {
+    async fn elicit(
+        &self,
+        params: CreateElicitationRequestParams,
+    ) -> Result<CreateElicitationResult, ErrorData>;
+}
+
+/// Handles requests the server sends to the client for one method, such as
+/// `sampling/createMessage` or `roots/list`.
+///
+/// The result is sent back to the server as the response. Returning an error sends a JSON-RPC
+/// error instead.
+#[async_trait]
+pub trait RequestHandler: Send + Sync + 'static {
+    async fn handle(&self, params: Option<Value>) -> Result<Value, ErrorData>;
+}
+
+/// Adapts an `ElicitationHandler` to the raw JSON of `elicitation/create` requests.
+struct Elicitation<H>(H);
+
+#[async_trait]
+impl<H: ElicitationHandler> RequestHandler for Elicitation<H> {
+    async fn handle(&self, params: Option<Value>) -> Result<Value, ErrorData> {
+        let params: CreateElicitationRequestParams =
+            serde_json::from_value(params.unwrap_or_default()).map_err(|e| ErrorData {
+                code: INVALID_PARAMS,
+                message: e.to_string(),
+                data: None,
+            })?;
+        let result = self.0.elicit(params).await?;
+        serde_json::to_value(result).map_err(|e| ErrorData {
+            code: INTERNAL_ERROR,
+            message: e.to_string(),
+            data: None,
+        })
+    }
+}
+
+/// Routes the requests and notifications a server sends to the client.
+///
+/// Each transport has a dispatcher, shared by its handles. Requests go to the handler registered
+/// for their method and are answered with its result; `ping` is always answered, and methods
+/// without a handler get a `METHOD_NOT_FOUND` error. Notifications are broadcast to every
+/// subscriber, until the transport stops.
+#[derive(Clone)]
+pub struct Dispatcher {
+    handlers: Arc<RwLock<HashMap<String, Arc<dyn RequestHandler>>>>,
+    notifications: broadcast::Sender<JsonRpcNotification>,
+    /// Which connection of the transport this is, and whether it has stopped
+    connection: Arc<watch::Sender<ConnectionState>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ConnectionState {
+    generation: u64,
+    closed: bool,
+}
+
+/// Keeps a dispatcher's subscriptions open while a transport runs. Dropping it, e.g. as the
+/// transport's actor stops, ends them.
+pub(crate) struct Connected {
+    connection: Arc<watch::Sender<ConnectionState>>,
+    generation: u64,
+}
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        // A transport that was started again has a newer connection, which stays open
+        self.connection.send_if_modified(|state| {
+            let current = state.generation == self.generation && !state.closed;
+            state.closed |= current;
+            current
+        });
+    }
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self {
+            handlers: Arc::new(RwLock::new(HashMap::new())),
+            notifications: broadcast::channel(NOTIFICATION_CAPACITY).0,
+            connection: Arc::new(watch::Sender::new(ConnectionState::default())),
+        }
+    }
+
+    /// Note that the transport started. Subscriptions end once the guard returned is dropped.
+    pub(crate) fn connect(&self) -> Connected {
+        let mut generation = 0;
+        self.connection.send_modify(|state| {
+            state.generation += 1;
+            state.closed = false;
+            generation = state.generation;
+        });
+        Connected {
+            connection: Arc::clone(&self.connection),
+            generation,
+        }
+    }
+
+    /// End every subscription, for a transport that stopped or will never receive anything.
+    pub(crate) fn close(&self) {
+        self.connection.send_modify(|state| state.closed = true);
+    }
+
+    /// Handle server requests for `method` with `handler`, replacing any previous handler.
+    pub fn register<S, H>(&self, method: S, handler: H)
+    where
+        S: Into<String>,
+        H: RequestHandler,
+    {
+        self.handlers
+            .write()
+            .unwrap()
+            .insert(method.into(), Arc::new(handler));
+    }
+
+    /// Handle `elicitation/create` requests with `handler`.
+    pub fn set_elicitation_handler<H: ElicitationHandler>(&self, handler: H) {
+        self.register("elicitation/create", Elicitation(handler));
+    }
+
+    /// Receive every notification the server sends from now on.
+    pub fn subscribe(&self) -> Notifications {
+        Notifications {
+            receiver: self.notifications.subscribe(),
+            connection: self.connection.subscribe(),
+            methods: None,
+        }
+    }
+
+    /// Pass a notification from the server on to subscribers.
+    pub(crate) fn notify(&self, notification: JsonRpcNotification) {
+        tracing::debug!(method = %notification.method, "Received notification");
+        // Nobody listening is fine
+        let _ = self.notifications.send(notification);
+    }
+
+    /// Answer a request from the server.
+    pub(crate) async fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        tracing::debug!(id = ?request.id, method = %request.method, "Received request");
+        if request.method == "ping" {
+            return JsonRpcResponse::success(request.id, serde_json::json!({}));
+        }
+
+        let handler = self.handlers.read().unwrap().get(&request.method).cloned();
+        let result = match handler {
+            Some(handler) => handler.handle(request.params).await,
+            None => Err(ErrorData {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not found: {}", request.method),
+                data: None,
+            }),
+        };
+        match result {
+            Ok(result) => JsonRpcResponse::success(request.id, result),
+            Err(error) => JsonRpcResponse::error(request.id, error),
+        }
+    }
+}
+
+/// A subscription to the notifications a server sends, parsed into `ServerNotification`s.
+///
+/// Every subscription sees every notification sent after it was created, unless it was narrowed
+/// down with [`Notifications::with_methods`]. A subscriber that falls too far behind skips the
+/// notifications it missed. Once the transport stops, the subscription ends.
+pub struct Notifications {
+    receiver: broadcast::Receiver<JsonRpcNotification>,
+    connection: watch::Receiver<ConnectionState>,
+    methods: Option<HashSet<String>>,
+}
+
+impl Notifications {
+    /// Only receive notifications with one of the given methods, e.g.
+    /// `ServerNotification::TOOL_LIST_CHANGED`. Calling this again adds to the methods.
+    pub fn with_methods<I, S>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.methods
+            .get_or_insert_with(HashSet::new)
+            .extend(methods.into_iter().map(Into::into));
+        self
+    }
+
+    /// Wait for the next notification. Returns `None` once the transport has stopped and every
+    /// notification it received has been returned.
+    pub async fn recv(&mut self) -> Option<ServerNotification> {
+        use broadcast::error::{RecvError, TryRecvError};
+
+        loop {
+            let received = tokio::select! {
+                biased;
+                received = self.receiver.recv() => received,
+                _ = self.connection.wait_for(|state| state.closed) => {
+                    self.receiver.try_recv().map_err(|e| match e {
+                        TryRecvError::Lagged(skipped) => RecvError::Lagged(skipped),
+                        TryRecvError::Empty | TryRecvError::Closed => RecvError::Closed,
+                    })
+                }
+            };
+            match received {
+                Ok(notification) => {
+                    if !self.wanted(&notification) {
+                        continue;
+                    }
+                    return Some(notification.into());
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Notification subscriber fell behind");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Whether any notifications arrived since the last call, without waiting for one. Missed
+    /// notifications count, as they may have been wanted.
+    pub(crate) fn drain(&mut self) -> bool {
+        let mut any = false;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(notification) => any |= self.wanted(&notification),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => any = true,
+                Err(_) => return any,
+            }
+        }
+    }
+
+    fn wanted(&self, notification: &JsonRpcNotification) -> bool {
+        self.methods
+            .as_ref()
+            .is_none_or(|methods| methods.contains(&notification.method))
+    }
+
+    /// Turn the subscription into a `Stream` of notifications.
+    pub fn into_stream(self) -> impl Stream<Item = ServerNotification> + Send + 'static {
+        futures::stream::unfold(self, |mut notifications| async move {
+            let notification = notifications.recv().await?;
+            Some((notification, notifications))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::{LoggingLevel, ResourceUpdatedParams};
+    use serde_json::json;
+
+    fn notification(method: &str, params: Value) -> JsonRpcNotification {
+        JsonRpcNotification::new(method.to_string(), Some(params))
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_parsed() {
+        let dispatcher = Dispatcher::new();
+        let mut notifications = dispatcher.subscribe();
+
+        dispatcher.notify(notification(
+            "notifications/message",
+            json!({ "level": "warning", "data": "disk almost full" }),
+        ));
+        dispatcher.notify(notification("notifications/custom", json!({ "a": 1 })));
+
+        let Some(ServerNotification::LoggingMessage(message)) = notifications.recv().await else {
+            panic!("Expected a log message");
+        };
+        assert_eq!(message.level, LoggingLevel::Warning);
+        assert_eq!(message.data, json!("disk almost full"));
+
+        let Some(ServerNotification::Other(custom)) = notifications.recv().await else {
+            panic!("Expected a custom notification");
+        };
+        assert_eq!(custom.method, "notifications/custom");
+    }
+
+    #[tokio::test]
+    async fn test_notifications_filtered_by_method() {
+        let dispatcher = Dispatcher::new();
+        let mut notifications = dispatcher.subscribe().with_methods([
+            ServerNotification::TOOL_LIST_CHANGED,
+            ServerNotification::RESOURCE_UPDATED,
+        ]);
+
+        dispatcher.notify(notification("notifications/message", json!({})));
+        dispatcher.notify(notification("notifications/tools/list_changed", json!({})));
+        dispatcher.notify(notification(
+            "notifications/resources/updated",
+            json!({ "uri": "file:///a.txt" }),
+        ));
+
+        assert_eq!(
+            notifications.recv().await,
+            Some(ServerNotification::ToolListChanged)
+        );
+        assert_eq!(
+            notifications.recv().await,
+            Some(ServerNotification::ResourceUpdated(ResourceUpdatedParams {
+                uri: "file:///a.txt".into()
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_end_when_transport_stops() {
+        let dispatcher = Dispatcher::new();
+        let connected = dispatcher.connect();
+        let mut notifications = dispatcher.subscribe();
+        dispatcher.notify(notification("notifications/tools/list_changed", json!({})));
+        drop(connected);
+
+        // What arrived before the transport stopped is still delivered
+        assert_eq!(
+            notifications.recv().await,
+            Some(ServerNotification::ToolListChanged)
+        );
+        assert_eq!(notifications.recv().await, None);
+        assert_eq!(dispatcher.subscribe().recv().await, None);
+
+        // Starting again opens new subscriptions, which a stale guard doesn't end
+        let stale = dispatcher.connect();
+        let _connected = dispatcher.connect();
+        drop(stale);
+        let mut notifications = dispatcher.subscribe();
+        dispatcher.notify(notification("notifications/tools/list_changed", json!({})));
+        assert_eq!(
+            notifications.recv().await,
+            Some(ServerNotification::ToolListChanged)
+        );
+    }
+}
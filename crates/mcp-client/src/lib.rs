@@ -1,7 +1,16 @@
 pub mod client;
+pub mod config;
+pub mod handler;
+pub mod pool;
 pub mod service;
 pub mod transport;
 
 pub use client::{ClientCapabilities, ClientInfo, Error, McpClient, McpClientTrait};
-pub use service::McpService;
-pub use transport::{SseTransport, StdioTransport, Transport, TransportHandle};
+pub use config::{ConfigError, ConnectedServers, McpServersConfig, ServerConfig};
+pub use handler::{Dispatcher, ElicitationHandler, Notifications, RequestHandler};
+pub use pool::{Catalog, McpClientPool, NamePrefix};
+pub use service::{HasDispatcher, McpService};
+pub use transport::{
+    MemoryTransport, RecordingHandle, ReplayHandle, SseTransport, StdioTransport, Transport,
+    TransportHandle,
+};
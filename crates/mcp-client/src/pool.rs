@@ -0,0 +1,614 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use futures::future::join_all;
+use mcp_core::{
+    prompt::Prompt,
+    protocol::{CallToolResult, GetPromptResult, ReadResourceResult, METHOD_NOT_FOUND},
+    Resource, Tool,
+};
+use serde_json::Value;
+
+use crate::client::{Error, McpClientTrait};
+
+/// When the tools and prompts in a pool's catalog get the name of their server in front.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NamePrefix {
+    /// Only when more than one server has something by that name
+    #[default]
+    OnCollision,
+    /// Always, so names don't change as servers come and go
+    Always,
+}
+
+/// Everything the servers in a pool offer, under the names the pool routes them by.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    pub tools: Vec<Tool>,
+    pub resources: Vec<Resource>,
+    pub prompts: Vec<Prompt>,
+    /// The servers that couldn't be listed, and why. Their tools, resources and prompts are left
+    /// out. Also the tools and prompts left out because another server's already had their name
+    /// in the catalog, as `Error::NameCollision`.
+    pub errors: Vec<(String, Error)>,
+}
+
+/// Where calls made through the pool go: names in the catalog to the server index and the name
+/// the server knows them by.
+#[derive(Default)]
+struct Routes {
+    tools: HashMap<String, (usize, String)>,
+    prompts: HashMap<String, (usize, String)>,
+    resources: HashMap<String, usize>,
+}
+
+/// Many named clients behind one catalog of tools, resources and prompts.
+///
+/// Names that more than one server uses are prefixed with the server's name, e.g. `git__status`
+/// (see [`NamePrefix`]), and calls are routed back to the server they came from. Resources keep
+/// their URIs; one listed by several servers is read from the first. Clients should already be
+/// initialized when they are added, and each needs a name of its own.
+///
+/// ```no_run
+/// # async fn example(
+/// #     git: impl mcp_client::McpClientTrait + 'static,
+/// #     files: impl mcp_client::McpClientTrait + 'static,
+/// # ) -> Result<(), mcp_client::Error> {
+/// use mcp_client::McpClientPool;
+///
+/// let pool = McpClientPool::new()
+///     .with_client("git", git)?
+///     .with_client("files", files)?;
+/// for tool in pool.catalog().await.tools {
+///     println!("{}", tool.name);
+/// }
+/// let result = pool
+///     .call_tool("git__status", serde_json::json!({ "repo_path": "." }))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct McpClientPool {
+    clients: Vec<(String, Box<dyn McpClientTrait>)>,
+    prefix: NamePrefix,
+    separator: String,
+    routes: Mutex<Option<Routes>>,
+}
+
+impl Default for McpClientPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl McpClientPool {
+    pub fn new() -> Self {
+        Self {
+            clients: Vec::new(),
+            prefix: NamePrefix::default(),
+            separator: "__".to_string(),
+            routes: Mutex::new(None),
+        }
+    }
+
+    /// Add a client as `name`, failing with `Error::DuplicateClient` if that name is taken.
+    pub fn with_client(
+        self,
+        name: impl Into<String>,
+        client: impl McpClientTrait + 'static,
+    ) -> Result<Self, Error> {
+        self.with_boxed_client(name, Box::new(client))
+    }
+
+    /// Add a client that is already boxed, e.g. one from
+    /// [`ConnectedServers`](crate::config::ConnectedServers).
+    pub fn with_boxed_client(
+        mut self,
+        name: impl Into<String>,
+        client: Box<dyn McpClientTrait>,
+    ) -> Result<Self, Error> {
+        let name = name.into();
+        if self.client(&name).is_some() {
+            return Err(Error::DuplicateClient(name));
+        }
+        self.clients.push((name, client));
+        Ok(self)
+    }
+
+    pub fn with_prefix(mut self, prefix: NamePrefix) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// What goes between a server's name and a tool or prompt name, `__` by default.
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// The clients in the order they were added, with their names.
+    pub fn clients(&self) -> impl Iterator<Item = (&str, &dyn McpClientTrait)> {
+        self.clients
+            .iter()
+            .map(|(name, client)| (name.as_str(), client.as_ref()))
+    }
+
+    /// The client added as `name`.
+    pub fn client(&self, name: &str) -> Option<&dyn McpClientTrait> {
+        self.clients
+            .iter()
+            .find(|(client, _)| client == name)
+            .map(|(_, client)| client.as_ref())
+    }
+
+    /// List everything every server offers, and route calls by the names in the result. A server
+    /// that fails to answer is reported in `errors` rather than failing the whole catalog.
+    pub async fn catalog(&self) -> Catalog {
+        let listings = self.clients.iter().map(|(_, client)| list(client.as_ref()));
+        let listings = join_all(listings).await;
+
+        let mut catalog = Catalog::default();
+        let mut listed = Vec::new();
+        for ((server, _), listing) in self.clients.iter().zip(listings) {
+            match listing {
+                Ok(listing) => listed.push(Some(listing)),
+                Err(error) => {
+                    tracing::warn!(server, %error, "Failed to list server");
+                    catalog.errors.push((server.clone(), error));
+                    listed.push(None);
+                }
+            }
+        }
+
+        let tool_counts = name_counts(
+            listed
+                .iter()
+                .flatten()
+                .flat_map(|listing| listing.tools.iter().map(|tool| &tool.name)),
+        );
+        let prompt_counts = name_counts(
+            listed
+                .iter()
+                .flatten()
+                .flat_map(|listing| listing.prompts.iter().map(|prompt| &prompt.name)),
+        );
+
+        let mut routes = Routes::default();
+        for (index, listing) in listed.into_iter().enumerate() {
+            let Some(listing) = listing else { continue };
+            let server = &self.clients[index].0;
+            // A prefixed name can still be taken, e.g. `a__b` + `c` and `a` + `b__c`. The first
+            // server keeps it.
+            for mut tool in listing.tools {
+                let name = self.exposed_name(server, &tool.name, tool_counts[&tool.name]);
+                if routes.tools.contains_key(&name) {
+                    catalog
+                        .errors
+                        .push((server.clone(), collision("tool", name)));
+                    continue;
+                }
+                routes.tools.insert(
+                    name.clone(),
+                    (index, std::mem::replace(&mut tool.name, name)),
+                );
+                catalog.tools.push(tool);
+            }
+            for mut prompt in listing.prompts {
+                let name = self.exposed_name(server, &prompt.name, prompt_counts[&prompt.name]);
+                if routes.prompts.contains_key(&name) {
+                    catalog
+                        .errors
+                        .push((server.clone(), collision("prompt", name)));
+                    continue;
+                }
+                routes.prompts.insert(
+                    name.clone(),
+                    (index, std::mem::replace(&mut prompt.name, name)),
+                );
+                catalog.prompts.push(prompt);
+            }
+            for resource in listing.resources {
+                if !routes.resources.contains_key(&resource.uri) {
+                    routes.resources.insert(resource.uri.clone(), index);
+                    catalog.resources.push(resource);
+                }
+            }
+        }
+
+        *self.routes.lock().unwrap() = Some(routes);
+        catalog
+    }
+
+    /// Call a tool by its name in the catalog, listing the servers first if they haven't been.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, Error> {
+        let (client, tool) = self
+            .route(name, "tool", |routes| routes.tools.get(name).cloned())
+            .await?;
+        client.call_tool(&tool, arguments).await
+    }
+
+    /// Read a resource from the server that listed it.
+    pub async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, Error> {
+        let (client, _) = self
+            .route(uri, "resource", |routes| {
+                routes
+                    .resources
+                    .get(uri)
+                    .map(|index| (*index, uri.to_string()))
+            })
+            .await?;
+        client.read_resource(uri).await
+    }
+
+    /// Get a prompt by its name in the catalog.
+    pub async fn get_prompt(&self, name: &str, arguments: Value) -> Result<GetPromptResult, Error> {
+        let (client, prompt) = self
+            .route(name, "prompt", |routes| routes.prompts.get(name).cloned())
+            .await?;
+        client.get_prompt(&prompt, arguments).await
+    }
+
+    fn exposed_name(&self, server: &str, name: &str, count: usize) -> String {
+        if self.prefix == NamePrefix::Always || count > 1 {
+            format!("{server}{}{name}", self.separator)
+        } else {
+            name.to_string()
+        }
+    }
+
+    async fn route(
+        &self,
+        name: &str,
+        kind: &'static str,
+        find: impl Fn(&Routes) -> Option<(usize, String)>,
+    ) -> Result<(&dyn McpClientTrait, String), Error> {
+        if self.routes.lock().unwrap().is_none() {
+            self.catalog().await;
+        }
+        let route = self.routes.lock().unwrap().as_ref().and_then(&find);
+        let (index, name_on_server) = route.ok_or_else(|| Error::UnknownTarget {
+            kind,
+            name: name.to_string(),
+        })?;
+        Ok((self.clients[index].1.as_ref(), name_on_server))
+    }
+}
+
+fn collision(kind: &'static str, name: String) -> Error {
+    tracing::warn!(kind, name, "Name already taken by another server");
+    Error::NameCollision { kind, name }
+}
+
+/// How many times each name appears.
+fn name_counts<'a>(names: impl Iterator<Item = &'a String>) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for name in names {
+        *counts.entry(name.clone()).or_default() += 1;
+    }
+    counts
+}
+
+/// What one server offers.
+struct Listing {
+    tools: Vec<Tool>,
+    resources: Vec<Resource>,
+    prompts: Vec<Prompt>,
+}
+
+async fn list(client: &dyn McpClientTrait) -> Result<Listing, Error> {
+    // Only list what the server advertises, when we know what that is
+    let capabilities = client.server_capabilities();
+    let tools = if capabilities.is_none_or(|capabilities| capabilities.tools.is_some()) {
+        supported(
+            pages(|cursor| async move {
+                let page = client.list_tools(cursor).await?;
+                Ok((page.tools, page.next_cursor))
+            })
+            .await,
+        )?
+    } else {
+        Vec::new()
+    };
+    let resources = if capabilities.is_none_or(|capabilities| capabilities.resources.is_some()) {
+        supported(
+            pages(|cursor| async move {
+                let page = client.list_resources(cursor).await?;
+                Ok((page.resources, page.next_cursor))
+            })
+            .await,
+        )?
+    } else {
+        Vec::new()
+    };
+    let prompts = if capabilities.is_none_or(|capabilities| capabilities.prompts.is_some()) {
+        supported(
+            pages(|cursor| async move {
+                let page = client.list_prompts(cursor).await?;
+                Ok((page.prompts, page.next_cursor))
+            })
+            .await,
+        )?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Listing {
+        tools,
+        resources,
+        prompts,
+    })
+}
+
+/// Every page of a `*/list` request, from `list` called with each page's cursor.
+async fn pages<T, F>(mut list: impl FnMut(Option<String>) -> F) -> Result<Vec<T>, Error>
+where
+    F: Future<Output = Result<(Vec<T>, Option<String>), Error>>,
+{
+    let mut items = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next_cursor) = list(cursor).await?;
+        items.extend(page);
+        cursor = next_cursor;
+        if cursor.is_none() {
+            return Ok(items);
+        }
+    }
+}
+
+/// Servers that don't offer something refuse to list it, which is no reason to leave them out.
+fn supported<T>(listed: Result<Vec<T>, Error>) -> Result<Vec<T>, Error> {
+    match listed {
+        Err(Error::RpcError { code, .. }) if code == METHOD_NOT_FOUND => Ok(Vec::new()),
+        listed => listed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCapabilities, ClientInfo};
+    use crate::Notifications;
+    use crate::{McpClient, McpService, MemoryTransport, Transport};
+    use mcp_core::handler::ToolError;
+    use mcp_core::protocol::{
+        InitializeResult, ListPromptsResult, ListResourcesResult, ListToolsResult,
+        ServerCapabilities,
+    };
+    use mcp_core::Content;
+    use mcp_macros::tool;
+    use mcp_server::{router::RouterService, server::MCPServerBuilder, Server};
+    use serde_json::json;
+
+    /// A client for a server with the given tools, which answers calls with the server and tool
+    /// name, or fails everything if `tools` is `None`.
+    struct FakeClient {
+        server: &'static str,
+        tools: Option<Vec<&'static str>>,
+    }
+
+    #[async_trait::async_trait]
+    impl McpClientTrait for FakeClient {
+        async fn initialize(
+            &mut self,
+            _info: ClientInfo,
+            _capabilities: ClientCapabilities,
+        ) -> Result<InitializeResult, Error> {
+            unimplemented!()
+        }
+
+        async fn list_resources(
+            &self,
+            _next_cursor: Option<String>,
+        ) -> Result<ListResourcesResult, Error> {
+            Ok(ListResourcesResult {
+                resources: vec![],
+                next_cursor: None,
+            })
+        }
+
+        async fn read_resource(&self, _uri: &str) -> Result<ReadResourceResult, Error> {
+            unimplemented!()
+        }
+
+        async fn list_tools(&self, _next_cursor: Option<String>) -> Result<ListToolsResult, Error> {
+            let tools = self.tools.as_ref().ok_or(Error::NotReady)?;
+            Ok(ListToolsResult {
+                tools: tools
+                    .iter()
+                    .map(|name| Tool::new(*name, "", json!({})))
+                    .collect(),
+                next_cursor: None,
+            })
+        }
+
+        async fn call_tool(&self, name: &str, _arguments: Value) -> Result<CallToolResult, Error> {
+            Ok(CallToolResult {
+                content: vec![Content::text(format!("{}:{name}", self.server))],
+                is_error: None,
+            })
+        }
+
+        async fn list_prompts(
+            &self,
+            _next_cursor: Option<String>,
+        ) -> Result<ListPromptsResult, Error> {
+            Ok(ListPromptsResult {
+                prompts: vec![],
+                next_cursor: None,
+            })
+        }
+
+        async fn get_prompt(
+            &self,
+            _name: &str,
+            _arguments: Value,
+        ) -> Result<GetPromptResult, Error> {
+            unimplemented!()
+        }
+
+        fn server_capabilities(&self) -> Option<&ServerCapabilities> {
+            None
+        }
+
+        fn subscribe(&self) -> Notifications {
+            unimplemented!()
+        }
+    }
+
+    fn pool() -> McpClientPool {
+        McpClientPool::new()
+            .with_client(
+                "git",
+                FakeClient {
+                    server: "git",
+                    tools: Some(vec!["status", "log"]),
+                },
+            )
+            .and_then(|pool| {
+                pool.with_client(
+                    "svn",
+                    FakeClient {
+                        server: "svn",
+                        tools: Some(vec!["status", "blame"]),
+                    },
+                )
+            })
+            .and_then(|pool| {
+                pool.with_client(
+                    "down",
+                    FakeClient {
+                        server: "down",
+                        tools: None,
+                    },
+                )
+            })
+            .unwrap()
+    }
+
+    async fn call(pool: &McpClientPool, name: &str) -> Result<String, Error> {
+        let result = pool.call_tool(name, json!({})).await?;
+        Ok(result.content[0].as_text().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_catalog_prefixes_collisions_and_routes_calls() {
+        let pool = pool();
+        let catalog = pool.catalog().await;
+        let names: Vec<&str> = catalog.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["git__status", "log", "svn__status", "blame"]);
+        assert_eq!(catalog.errors.len(), 1);
+        assert_eq!(catalog.errors[0].0, "down");
+
+        assert_eq!(call(&pool, "git__status").await.unwrap(), "git:status");
+        assert_eq!(call(&pool, "svn__status").await.unwrap(), "svn:status");
+        assert_eq!(call(&pool, "blame").await.unwrap(), "svn:blame");
+        assert!(matches!(
+            call(&pool, "status").await,
+            Err(Error::UnknownTarget { kind: "tool", .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_always_prefix() {
+        // Calls list the servers first if nobody has yet
+        let pool = pool().with_prefix(NamePrefix::Always).with_separator(".");
+        assert_eq!(call(&pool, "git.log").await.unwrap(), "git:log");
+        assert!(call(&pool, "log").await.is_err());
+    }
+
+    #[test]
+    fn test_client_names_must_be_unique() {
+        let fake = || FakeClient {
+            server: "git",
+            tools: Some(vec![]),
+        };
+        let pool = McpClientPool::new().with_client("git", fake()).unwrap();
+        assert!(matches!(
+            pool.with_client("git", fake()),
+            Err(Error::DuplicateClient(name)) if name == "git"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_name_collision() {
+        let pool = McpClientPool::new()
+            .with_client(
+                "a__b",
+                FakeClient {
+                    server: "a__b",
+                    tools: Some(vec!["c"]),
+                },
+            )
+            .and_then(|pool| {
+                pool.with_client(
+                    "a",
+                    FakeClient {
+                        server: "a",
+                        tools: Some(vec!["b__c"]),
+                    },
+                )
+            })
+            .unwrap()
+            .with_prefix(NamePrefix::Always);
+        let catalog = pool.catalog().await;
+        let names: Vec<&str> = catalog.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["a__b__c"]);
+        assert!(matches!(
+            &catalog.errors[..],
+            [(server, Error::NameCollision { kind: "tool", name })]
+                if server == "a" && name == "a__b__c"
+        ));
+        assert_eq!(call(&pool, "a__b__c").await.unwrap(), "a__b:c");
+    }
+
+    #[tool(description = "Greet someone")]
+    async fn greet(name: String) -> Result<String, ToolError> {
+        Ok(format!("Hello {name}"))
+    }
+
+    #[tokio::test]
+    async fn test_catalog_of_an_mcp_server() {
+        let (client_end, server_end) = mcp_core::transport::duplex();
+        let server = MCPServerBuilder::new("greeter".to_string(), String::new())
+            .with_tool(Greet)
+            .build()
+            .unwrap();
+        let serving = Server::new(RouterService::new(server))
+            .run(mcp_server::MemoryTransport::new(server_end));
+
+        let pooled = async {
+            let transport = MemoryTransport::new(client_end);
+            let handle = transport.start().await.unwrap();
+            let mut client = McpClient::new(McpService::new(handle));
+            let info = ClientInfo {
+                name: "pool".to_string(),
+                version: "1.0.0".to_string(),
+            };
+            client
+                .initialize(info, ClientCapabilities::default())
+                .await
+                .unwrap();
+            let pool = McpClientPool::new().with_client("greeter", client).unwrap();
+
+            let catalog = pool.catalog().await;
+            assert!(catalog.errors.is_empty(), "{:?}", catalog.errors);
+            let names: Vec<&str> = catalog.tools.iter().map(|t| t.name.as_str()).collect();
+            assert_eq!(names, vec!["greet"]);
+            assert!(catalog.resources.is_empty());
+            assert!(catalog.prompts.is_empty());
+
+            let result = pool
+                .call_tool("greet", json!({ "name": "Ada" }))
+                .await
+                .unwrap();
+            assert_eq!(result.content[0].as_text(), Some("Hello Ada"));
+        };
+
+        tokio::select! {
+            result = serving => panic!("The server stopped: {result:?}"),
+            () = pooled => {}
+        }
+    }
+}
@@ -5,8 +5,18 @@ use std::sync::Arc;
 use std::task::{Context, Poll};
 use tower::{timeout::Timeout, Service, ServiceBuilder};
 
+use crate::handler::Dispatcher;
 use crate::transport::{Error, TransportHandle};
 
+/// A service that talks to a server over a transport, and can hand out the transport's dispatcher
+/// for the requests and notifications the server sends back.
+///
+/// Implemented by `McpService`, and by middleware wrapping it, so `McpClient` can subscribe to
+/// notifications whatever the service stack looks like.
+pub trait HasDispatcher {
+    fn dispatcher(&self) -> &Dispatcher;
+}
+
 /// A wrapper service that implements Tower's Service trait for MCP transport
 #[derive(Clone)]
 pub struct McpService<T: TransportHandle> {
@@ -19,6 +29,27 @@ impl<T: TransportHandle> McpService<T> {
             inner: Arc::new(transport),
         }
     }
+
+    /// Send several messages as a single JSON-RPC batch, returning the responses to its requests
+    /// in order. Batches bypass any middleware wrapping this service.
+    pub async fn send_batch(
+        &self,
+        messages: Vec<SendableMessage>,
+    ) -> Result<Vec<JsonRpcResponse>, Error> {
+        self.inner.send_batch(messages).await
+    }
+}
+
+impl<T: TransportHandle> HasDispatcher for McpService<T> {
+    fn dispatcher(&self) -> &Dispatcher {
+        self.inner.dispatcher()
+    }
+}
+
+impl<S: HasDispatcher> HasDispatcher for Timeout<S> {
+    fn dispatcher(&self) -> &Dispatcher {
+        self.get_ref().dispatcher()
+    }
 }
 
 impl<T> Service<SendableMessage> for McpService<T>
@@ -0,0 +1,951 @@
+//! OAuth 2.1 authorization for the HTTP transports.
+//!
+//! When a server answers with `401 Unauthorized`, the transport discovers the server's
+//! authorization server through its protected resource metadata (RFC 9728) and the authorization
+//! server's metadata (RFC 8414), then gets a token with the authorization code flow and PKCE. The
+//! user is sent to the authorization server by an [`AuthorizationHandler`], and tokens are kept in
+//! a [`TokenStore`] and refreshed when they expire.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use reqwest::header::WWW_AUTHENTICATE;
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use url::Url;
+
+/// Tokens this close to expiring are refreshed before use.
+const EXPIRY_LEEWAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Error)]
+pub enum AuthError {
+    #[error("Failed to discover the authorization server: {0}")]
+    Discovery(String),
+
+    #[error("Authorization failed: {0}")]
+    Authorization(String),
+
+    #[error("Token request failed: {0}")]
+    Token(String),
+}
+
+/// An access token, and what is needed to refresh it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl Token {
+    /// Whether the token has expired, or is about to.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= SystemTime::now() + EXPIRY_LEEWAY)
+    }
+}
+
+/// Keeps the token for a server, e.g. in memory, a file, or the system keychain.
+#[async_trait]
+pub trait TokenStore: Send + Sync + 'static {
+    async fn load(&self) -> Option<Token>;
+    async fn save(&self, token: &Token);
+    /// Forget the token, once it can no longer be refreshed.
+    async fn clear(&self);
+}
+
+/// Keeps the token in memory, for as long as the process runs.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore(Mutex<Option<Token>>);
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    async fn load(&self) -> Option<Token> {
+        self.0.lock().unwrap().clone()
+    }
+
+    async fn save(&self, token: &Token) {
+        *self.0.lock().unwrap() = Some(token.clone());
+    }
+
+    async fn clear(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+/// Sends the user to the authorization server, and brings back where it redirected them to.
+///
+/// Implementations open `authorization_url` for the user, e.g. in a browser, and wait for the
+/// authorization server to redirect to the client's `redirect_uri`. They return the full URL of
+/// that redirect, with its `code` and `state` parameters.
+#[async_trait]
+pub trait AuthorizationHandler: Send + Sync + 'static {
+    async fn authorize(&self, authorization_url: Url) -> Result<Url, AuthError>;
+}
+
+/// Receives the redirect on a loopback `redirect_uri` such as `http://127.0.0.1:8765/callback`,
+/// by listening on its port until the browser arrives.
+pub struct LoopbackHandler {
+    open: Box<dyn Fn(&Url) + Send + Sync>,
+}
+
+impl LoopbackHandler {
+    /// `open` sends the user to the authorization URL, e.g. by launching a browser or printing it.
+    pub fn new(open: impl Fn(&Url) + Send + Sync + 'static) -> Self {
+        Self {
+            open: Box::new(open),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthorizationHandler for LoopbackHandler {
+    async fn authorize(&self, authorization_url: Url) -> Result<Url, AuthError> {
+        let failed = |e: &dyn std::fmt::Display| AuthError::Authorization(e.to_string());
+        let redirect_uri = authorization_url
+            .query_pairs()
+            .find(|(name, _)| name == "redirect_uri")
+            .ok_or_else(|| AuthError::Authorization("No redirect_uri".to_string()))
+            .and_then(|(_, uri)| Url::parse(&uri).map_err(|e| failed(&e)))?;
+        let host = redirect_uri.host_str().unwrap_or("127.0.0.1");
+        let port = redirect_uri.port_or_known_default().unwrap_or(80);
+        let listener = TcpListener::bind((host, port))
+            .await
+            .map_err(|e| failed(&e))?;
+
+        (self.open)(&authorization_url);
+
+        loop {
+            let (mut stream, _) = listener.accept().await.map_err(|e| failed(&e))?;
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") && head.len() < 8192 {
+                match stream.read_u8().await {
+                    Ok(byte) => head.push(byte),
+                    Err(_) => break,
+                }
+            }
+            let head = String::from_utf8_lossy(&head);
+            let target = head.split_whitespace().nth(1).unwrap_or_default();
+            let Ok(redirect) = redirect_uri.join(target) else {
+                continue;
+            };
+            if redirect.path() != redirect_uri.path() {
+                let _ = stream
+                    .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                    .await;
+                continue;
+            }
+            let body = "Authorization complete. You can close this window.";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            return Ok(redirect);
+        }
+    }
+}
+
+/// The client's registration with the authorization server.
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    /// For confidential clients. Public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    /// The scopes to ask for. If empty, the scopes the server says it supports are used.
+    pub scopes: Vec<String>,
+}
+
+/// Where an authorization server takes authorization requests and hands out tokens.
+#[derive(Debug, Clone, Deserialize)]
+struct AuthorizationServerMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    #[serde(default)]
+    code_challenge_methods_supported: Option<Vec<String>>,
+}
+
+/// What a server publishes about how it is protected (RFC 9728).
+#[derive(Debug, Clone, Deserialize)]
+struct ProtectedResourceMetadata {
+    resource: String,
+    authorization_servers: Vec<String>,
+    #[serde(default)]
+    scopes_supported: Vec<String>,
+}
+
+/// Everything discovery finds out about a server.
+#[derive(Debug, Clone)]
+struct Discovered {
+    resource: String,
+    scopes: Vec<String>,
+    authorization_server: AuthorizationServerMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// OAuth 2.1 authorization for a server. Configure a transport with it, e.g. with
+/// [`SseTransportBuilder::with_oauth`](super::SseTransportBuilder::with_oauth), and it takes care
+/// of getting, refreshing and attaching tokens.
+#[derive(Clone)]
+pub struct OAuth {
+    config: Arc<OAuthConfig>,
+    handler: Arc<dyn AuthorizationHandler>,
+    store: Arc<dyn TokenStore>,
+    discovered: Arc<Mutex<Option<Discovered>>>,
+    /// Held while getting a new token, so concurrent 401s lead to a single authorization
+    authorizing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl OAuth {
+    pub fn new<H: AuthorizationHandler>(config: OAuthConfig, handler: H) -> Self {
+        Self {
+            config: Arc::new(config),
+            handler: Arc::new(handler),
+            store: Arc::new(MemoryTokenStore::default()),
+            discovered: Arc::new(Mutex::new(None)),
+            authorizing: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Keep tokens in `store` instead of in memory.
+    pub fn with_token_store<S: TokenStore>(mut self, store: S) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// The access token to send to the server at `server_url`, refreshing it if it has expired.
+    /// `None` if there is no usable token yet; the server's 401 will start authorization.
+    pub(crate) async fn access_token(&self, http: &HttpClient, server_url: &str) -> Option<String> {
+        let token = self.store.load().await?;
+        if !token.is_expired() {
+            return Some(token.access_token);
+        }
+
+        let _authorizing = self.authorizing.lock().await;
+        // Someone else may have refreshed it while we waited
+        let token = self.store.load().await?;
+        if !token.is_expired() {
+            return Some(token.access_token);
+        }
+        let discovered = self.discover(http, server_url, None).await.ok()?;
+        self.refresh(http, &discovered, token)
+            .await
+            .map(|token| token.access_token)
+    }
+
+    /// Get a new access token, after the server at `server_url` rejected `rejected` (or the lack
+    /// of a token) with a 401 and the given `WWW-Authenticate` header.
+    ///
+    /// Refreshes the current token if possible, and otherwise runs the authorization code flow.
+    pub(crate) async fn authorize(
+        &self,
+        http: &HttpClient,
+        server_url: &str,
+        www_authenticate: Option<&str>,
+        rejected: Option<&str>,
+    ) -> Result<String, AuthError> {
+        let _authorizing = self.authorizing.lock().await;
+
+        // Someone else may have gotten a new token while we waited
+        let token = self.store.load().await;
+        if let Some(token) = &token {
+            if Some(token.access_token.as_str()) != rejected && !token.is_expired() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let discovered = self.discover(http, server_url, www_authenticate).await?;
+        if let Some(token) = token {
+            if let Some(token) = self.refresh(http, &discovered, token).await {
+                return Ok(token.access_token);
+            }
+        }
+        let token = self.authorization_code_flow(http, &discovered).await?;
+        Ok(token.access_token)
+    }
+
+    /// Find the authorization server for the server at `server_url`, from the metadata the
+    /// `WWW-Authenticate` header points to, or the well-known location.
+    ///
+    /// The metadata has to be on the server's own origin, and be about the server itself or a
+    /// resource on its origin that contains it (RFC 9728 section 3.3), so a server can't have the
+    /// client send its tokens to somebody else's authorization server in its name.
+    async fn discover(
+        &self,
+        http: &HttpClient,
+        server_url: &str,
+        www_authenticate: Option<&str>,
+    ) -> Result<Discovered, AuthError> {
+        let resource_metadata_url =
+            www_authenticate.and_then(|header| auth_param(header, "resource_metadata"));
+        if resource_metadata_url.is_none() {
+            if let Some(discovered) = self.discovered.lock().unwrap().clone() {
+                return Ok(discovered);
+            }
+        }
+
+        let failed = |e: &dyn std::fmt::Display| AuthError::Discovery(e.to_string());
+        let mut server_url = Url::parse(server_url).map_err(|e| failed(&e))?;
+        server_url.set_fragment(None);
+        let candidates = match resource_metadata_url {
+            Some(url) => {
+                let url = Url::parse(&url).map_err(|e| failed(&e))?;
+                if url.origin() != server_url.origin() {
+                    return Err(AuthError::Discovery(format!(
+                        "The resource metadata at {url} is not on the server's origin"
+                    )));
+                }
+                vec![url]
+            }
+            None => well_known(&server_url, "oauth-protected-resource"),
+        };
+        let resource: ProtectedResourceMetadata = fetch_first(http, &candidates).await?;
+        let matches = Url::parse(&resource.resource)
+            .is_ok_and(|resource| resource_covers(&resource, &server_url));
+        if !matches {
+            return Err(AuthError::Discovery(format!(
+                "The resource metadata is for {}, not {server_url}",
+                resource.resource
+            )));
+        }
+
+        let issuer = resource.authorization_servers.first().ok_or_else(|| {
+            AuthError::Discovery("The server names no authorization server".to_string())
+        })?;
+        let issuer = Url::parse(issuer).map_err(|e| failed(&e))?;
+        let mut candidates = well_known(&issuer, "oauth-authorization-server");
+        candidates.extend(well_known(&issuer, "openid-configuration"));
+        let authorization_server: AuthorizationServerMetadata =
+            fetch_first(http, &candidates).await?;
+        if let Some(methods) = &authorization_server.code_challenge_methods_supported {
+            if !methods.iter().any(|method| method == "S256") {
+                return Err(AuthError::Discovery(
+                    "The authorization server does not support PKCE with S256".to_string(),
+                ));
+            }
+        }
+
+        let discovered = Discovered {
+            resource: resource.resource,
+            scopes: resource.scopes_supported,
+            authorization_server,
+        };
+        *self.discovered.lock().unwrap() = Some(discovered.clone());
+        Ok(discovered)
+    }
+
+    /// Send the user to the authorization server, and trade the code it redirects back with for a
+    /// token.
+    async fn authorization_code_flow(
+        &self,
+        http: &HttpClient,
+        discovered: &Discovered,
+    ) -> Result<Token, AuthError> {
+        let verifier = random_string();
+        let challenge = URL_SAFE_NO_PAD.encode(ring::digest::digest(
+            &ring::digest::SHA256,
+            verifier.as_bytes(),
+        ));
+        let state = random_string();
+        let scopes = if self.config.scopes.is_empty() {
+            &discovered.scopes
+        } else {
+            &self.config.scopes
+        };
+
+        let mut authorization_url =
+            Url::parse(&discovered.authorization_server.authorization_endpoint)
+                .map_err(|e| AuthError::Discovery(e.to_string()))?;
+        {
+            let mut query = authorization_url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &self.config.client_id)
+                .append_pair("redirect_uri", &self.config.redirect_uri)
+                .append_pair("code_challenge", &challenge)
+                .append_pair("code_challenge_method", "S256")
+                .append_pair("state", &state)
+                .append_pair("resource", &discovered.resource);
+            if !scopes.is_empty() {
+                query.append_pair("scope", &scopes.join(" "));
+            }
+        }
+
+        let redirect = self.handler.authorize(authorization_url).await?;
+        let param = |name: &str| {
+            redirect
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or_default();
+            return Err(AuthError::Authorization(format!("{error} {description}")));
+        }
+        if param("state").as_deref() != Some(state.as_str()) {
+            return Err(AuthError::Authorization(
+                "The redirect's state does not match the request".to_string(),
+            ));
+        }
+        let code = param("code")
+            .ok_or_else(|| AuthError::Authorization("The redirect has no code".to_string()))?;
+
+        self.request_token(
+            http,
+            discovered,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", &self.config.redirect_uri),
+                ("code_verifier", &verifier),
+            ],
+            None,
+        )
+        .await
+    }
+
+    /// Trade the refresh token for a new token. On failure the token is forgotten, and `None` is
+    /// returned so the caller can start over.
+    async fn refresh(
+        &self,
+        http: &HttpClient,
+        discovered: &Discovered,
+        token: Token,
+    ) -> Option<Token> {
+        let refresh_token = token.refresh_token?;
+        let refreshed = self
+            .request_token(
+                http,
+                discovered,
+                &[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", &refresh_token),
+                ],
+                Some(&refresh_token),
+            )
+            .await;
+        match refreshed {
+            Ok(token) => Some(token),
+            Err(e) => {
+                tracing::warn!("Failed to refresh token: {e}");
+                self.store.clear().await;
+                None
+            }
+        }
+    }
+
+    /// Request a token from the token endpoint, and store it. `refresh_token` is kept if the
+    /// response doesn't rotate it.
+    async fn request_token(
+        &self,
+        http: &HttpClient,
+        discovered: &Discovered,
+        params: &[(&str, &str)],
+        refresh_token: Option<&str>,
+    ) -> Result<Token, AuthError> {
+        let failed = |e: &dyn std::fmt::Display| AuthError::Token(e.to_string());
+        let mut form = params.to_vec();
+        form.push(("resource", &discovered.resource));
+        let mut request = http.post(&discovered.authorization_server.token_endpoint);
+        match &self.config.client_secret {
+            Some(secret) => request = request.basic_auth(&self.config.client_id, Some(secret)),
+            None => form.push(("client_id", &self.config.client_id)),
+        }
+
+        let response = request.form(&form).send().await.map_err(|e| failed(&e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(match response.json::<TokenErrorResponse>().await {
+                Ok(error) => AuthError::Token(format!(
+                    "{} {}",
+                    error.error,
+                    error.error_description.unwrap_or_default()
+                )),
+                Err(_) => AuthError::Token(status.to_string()),
+            });
+        }
+        let response: TokenResponse = response.json().await.map_err(|e| failed(&e))?;
+
+        let token = Token {
+            access_token: response.access_token,
+            refresh_token: response
+                .refresh_token
+                .or_else(|| refresh_token.map(String::from)),
+            expires_at: response
+                .expires_in
+                .map(|expires_in| SystemTime::now() + Duration::from_secs(expires_in)),
+        };
+        self.store.save(&token).await;
+        Ok(token)
+    }
+}
+
+/// Whether a response is a 401 the transport should answer by authorizing, and the
+/// `WWW-Authenticate` header that came with it.
+pub(crate) fn unauthorized(response: &reqwest::Response) -> Option<Option<String>> {
+    (response.status() == reqwest::StatusCode::UNAUTHORIZED).then(|| {
+        response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|header| header.to_str().ok())
+            .map(String::from)
+    })
+}
+
+/// The well-known URLs for `name` under `url`: the one with the path of `url` appended, and the
+/// one at the root.
+fn well_known(url: &Url, name: &str) -> Vec<Url> {
+    let mut candidates = Vec::new();
+    let path = url.path().trim_end_matches('/');
+    let mut root = url.clone();
+    root.set_query(None);
+    root.set_fragment(None);
+    if !path.is_empty() {
+        let mut with_path = root.clone();
+        with_path.set_path(&format!("/.well-known/{name}{path}"));
+        candidates.push(with_path);
+    }
+    root.set_path(&format!("/.well-known/{name}"));
+    candidates.push(root);
+    candidates
+}
+
+/// Fetch the JSON document at the first of `candidates` that has one.
+async fn fetch_first<T: serde::de::DeserializeOwned>(
+    http: &HttpClient,
+    candidates: &[Url],
+) -> Result<T, AuthError> {
+    let mut last_error = String::new();
+    for url in candidates {
+        match http.get(url.clone()).send().await {
+            Ok(response) if response.status().is_success() => {
+                return response
+                    .json()
+                    .await
+                    .map_err(|e| AuthError::Discovery(format!("{url}: {e}")));
+            }
+            Ok(response) => last_error = format!("{url}: {}", response.status()),
+            Err(e) => last_error = format!("{url}: {e}"),
+        }
+    }
+    Err(AuthError::Discovery(last_error))
+}
+
+/// Whether protected resource metadata about `resource` applies to `server_url`: the resource must
+/// be on the same origin, and its path the server's or a parent of it, such as `https://host/` for
+/// `https://host/sse`.
+fn resource_covers(resource: &Url, server_url: &Url) -> bool {
+    if resource.fragment().is_some() || resource.origin() != server_url.origin() {
+        return false;
+    }
+    if resource.query().is_some() {
+        return resource == server_url;
+    }
+    let (parent, path) = (resource.path(), server_url.path());
+    match path.strip_prefix(parent) {
+        Some(rest) => rest.is_empty() || parent.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Find a parameter such as `resource_metadata="https://..."` of the `Bearer` challenge in a
+/// `WWW-Authenticate` header, which may hold several challenges (RFC 9110 section 11.6.1).
+fn auth_param(header: &str, name: &str) -> Option<String> {
+    let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    let mut scheme = "";
+    let mut rest = header;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        let end = rest.find(|c| !is_tchar(c)).unwrap_or(rest.len());
+        if end == 0 {
+            // The end of the header, or something that isn't a token, like a token68's padding
+            let mut chars = rest.chars();
+            chars.next()?;
+            rest = chars.as_str();
+            continue;
+        }
+        let token = &rest[..end];
+        rest = rest[end..].trim_start_matches([' ', '\t']);
+        let Some(after) = rest.strip_prefix('=') else {
+            // A token not followed by `=` starts the next challenge
+            scheme = token;
+            continue;
+        };
+        rest = after.trim_start_matches([' ', '\t']);
+        let value = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                loop {
+                    match chars.next()? {
+                        (i, '"') => {
+                            rest = &quoted[i + 1..];
+                            break;
+                        }
+                        (_, '\\') => value.push(chars.next()?.1),
+                        (_, c) => value.push(c),
+                    }
+                }
+                value
+            }
+            None => {
+                // Strictly a token, but servers leave URLs unquoted too
+                let end = rest.find([',', ' ', '\t']).unwrap_or(rest.len());
+                let value = rest[..end].to_string();
+                rest = &rest[end..];
+                value
+            }
+        };
+        if scheme.eq_ignore_ascii_case("Bearer") && token.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+}
+
+/// A random URL-safe string, for PKCE verifiers and `state`.
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MockState {
+        base: String,
+        expires_in: u64,
+        /// PKCE challenges by the code handed out with them
+        challenges: HashMap<String, String>,
+        access_token: Option<String>,
+        refresh_tokens: Vec<String>,
+        grants: Vec<String>,
+    }
+
+    /// An authorization server that publishes metadata for itself and the protected resource at
+    /// the same address, and issues `token-1`, `token-2`, ... that expire after `expires_in`
+    /// seconds.
+    #[derive(Clone, Default)]
+    pub(crate) struct MockAuthorizationServer(Arc<Mutex<MockState>>);
+
+    impl MockAuthorizationServer {
+        pub(crate) fn new(expires_in: u64) -> Self {
+            let server = Self::default();
+            server.0.lock().unwrap().expires_in = expires_in;
+            server
+        }
+
+        /// Set the address the server is reachable at, once it is known.
+        pub(crate) fn set_base(&self, base: String) {
+            self.0.lock().unwrap().base = base;
+        }
+
+        pub(crate) fn routes(&self) -> Router {
+            Router::new()
+                .route(
+                    "/.well-known/oauth-protected-resource",
+                    get(protected_resource),
+                )
+                .route(
+                    "/.well-known/oauth-authorization-server",
+                    get(authorization_server),
+                )
+                .route("/token", post(token))
+                .with_state(self.clone())
+        }
+
+        /// Whether an `Authorization` header carries the current access token.
+        pub(crate) fn accepts(&self, authorization: Option<&str>) -> bool {
+            let state = self.0.lock().unwrap();
+            state
+                .access_token
+                .as_ref()
+                .is_some_and(|token| authorization == Some(&format!("Bearer {token}")))
+        }
+
+        /// Stop accepting the current access token.
+        pub(crate) fn revoke(&self) {
+            self.0.lock().unwrap().access_token = None;
+        }
+
+        pub(crate) fn grants(&self) -> Vec<String> {
+            self.0.lock().unwrap().grants.clone()
+        }
+
+        fn issue(&self, grant: &str) -> Value {
+            let mut state = self.0.lock().unwrap();
+            state.grants.push(grant.to_string());
+            let n = state.grants.len();
+            state.access_token = Some(format!("token-{n}"));
+            state.refresh_tokens.push(format!("refresh-{n}"));
+            json!({
+                "access_token": format!("token-{n}"),
+                "token_type": "Bearer",
+                "expires_in": state.expires_in,
+                "refresh_token": format!("refresh-{n}"),
+            })
+        }
+    }
+
+    async fn protected_resource(State(server): State<MockAuthorizationServer>) -> Json<Value> {
+        let base = server.0.lock().unwrap().base.clone();
+        Json(json!({
+            "resource": format!("{base}/sse"),
+            "authorization_servers": [base],
+            "scopes_supported": ["mcp"],
+        }))
+    }
+
+    async fn authorization_server(State(server): State<MockAuthorizationServer>) -> Json<Value> {
+        let base = server.0.lock().unwrap().base.clone();
+        Json(json!({
+            "issuer": base,
+            "authorization_endpoint": format!("{base}/authorize"),
+            "token_endpoint": format!("{base}/token"),
+            "code_challenge_methods_supported": ["S256"],
+        }))
+    }
+
+    async fn token(
+        State(server): State<MockAuthorizationServer>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+        let invalid_grant = || {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "invalid_grant" })),
+            )
+        };
+        assert_eq!(form["client_id"], "test-client");
+        match form["grant_type"].as_str() {
+            "authorization_code" => {
+                let challenge = server
+                    .0
+                    .lock()
+                    .unwrap()
+                    .challenges
+                    .remove(&form["code"])
+                    .ok_or_else(invalid_grant)?;
+                let verified = URL_SAFE_NO_PAD.encode(ring::digest::digest(
+                    &ring::digest::SHA256,
+                    form["code_verifier"].as_bytes(),
+                ));
+                if verified != challenge {
+                    return Err(invalid_grant());
+                }
+                Ok(Json(server.issue("authorization_code")))
+            }
+            "refresh_token" => {
+                let known = server
+                    .0
+                    .lock()
+                    .unwrap()
+                    .refresh_tokens
+                    .contains(&form["refresh_token"]);
+                if !known {
+                    return Err(invalid_grant());
+                }
+                Ok(Json(server.issue("refresh_token")))
+            }
+            _ => Err(invalid_grant()),
+        }
+    }
+
+    /// Plays the user approving every authorization request.
+    #[async_trait]
+    impl AuthorizationHandler for MockAuthorizationServer {
+        async fn authorize(&self, authorization_url: Url) -> Result<Url, AuthError> {
+            let params: HashMap<String, String> =
+                authorization_url.query_pairs().into_owned().collect();
+            assert_eq!(params["response_type"], "code");
+            assert_eq!(params["code_challenge_method"], "S256");
+            assert_eq!(params["scope"], "mcp");
+            assert!(params["resource"].ends_with("/sse"));
+
+            let code = random_string();
+            self.0
+                .lock()
+                .unwrap()
+                .challenges
+                .insert(code.clone(), params["code_challenge"].clone());
+            let mut redirect = Url::parse(&params["redirect_uri"]).unwrap();
+            redirect
+                .query_pairs_mut()
+                .append_pair("code", &code)
+                .append_pair("state", &params["state"]);
+            Ok(redirect)
+        }
+    }
+
+    pub(crate) fn oauth(server: &MockAuthorizationServer) -> OAuth {
+        OAuth::new(
+            OAuthConfig {
+                client_id: "test-client".to_string(),
+                client_secret: None,
+                redirect_uri: "http://127.0.0.1/callback".to_string(),
+                scopes: vec![],
+            },
+            server.clone(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_authorization_code_flow_and_refresh() {
+        // Tokens expire right away, so the next use refreshes them
+        let server = MockAuthorizationServer::new(0);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        server.set_base(base.clone());
+        let routes = server.routes();
+        tokio::spawn(async move { axum::serve(listener, routes).await.unwrap() });
+
+        let http = HttpClient::new();
+        let sse_url = format!("{base}/sse");
+        let oauth = oauth(&server);
+        assert_eq!(oauth.access_token(&http, &sse_url).await, None);
+
+        let www_authenticate =
+            format!(r#"Bearer resource_metadata="{base}/.well-known/oauth-protected-resource""#);
+        let token = oauth
+            .authorize(&http, &sse_url, Some(&www_authenticate), None)
+            .await
+            .unwrap();
+        assert_eq!(token, "token-1");
+
+        let token = oauth.access_token(&http, &sse_url).await;
+        assert_eq!(token.as_deref(), Some("token-2"));
+        assert_eq!(server.grants(), ["authorization_code", "refresh_token"]);
+    }
+
+    #[test]
+    fn test_auth_param() {
+        let header = r#"Bearer error="invalid_token", resource_metadata="https://mcp.example.com/.well-known/oauth-protected-resource", scope=mcp"#;
+        assert_eq!(
+            auth_param(header, "resource_metadata").as_deref(),
+            Some("https://mcp.example.com/.well-known/oauth-protected-resource")
+        );
+        assert_eq!(auth_param(header, "scope").as_deref(), Some("mcp"));
+        assert_eq!(auth_param(header, "realm"), None);
+
+        // Names are matched whole, values may hold separators and escapes, and only the Bearer
+        // challenge counts
+        let header = r#"Basic realm="a, b", x_resource_metadata="https://evil.example.com", Bearer error_description="no \"resource_metadata=\" here", RESOURCE_METADATA=https://mcp.example.com/meta"#;
+        assert_eq!(
+            auth_param(header, "resource_metadata").as_deref(),
+            Some("https://mcp.example.com/meta")
+        );
+        assert_eq!(
+            auth_param(header, "error_description").as_deref(),
+            Some(r#"no "resource_metadata=" here"#)
+        );
+        assert_eq!(auth_param(header, "realm"), None);
+        assert_eq!(
+            auth_param("Basic dXNlcg==, Bearer scope=mcp", "scope").as_deref(),
+            Some("mcp")
+        );
+    }
+
+    #[test]
+    fn test_resource_covers() {
+        let covers = |resource: &str, server_url: &str| {
+            resource_covers(
+                &Url::parse(resource).unwrap(),
+                &Url::parse(server_url).unwrap(),
+            )
+        };
+        assert!(covers("https://host/sse", "https://host/sse"));
+        assert!(covers("https://host", "https://host/sse"));
+        assert!(covers("https://host/", "https://host/sse"));
+        assert!(covers("https://host/mcp", "https://host/mcp/sse"));
+        assert!(covers(
+            "https://host/mcp/",
+            "https://host/mcp/sse?session=1"
+        ));
+        assert!(!covers("https://host/ss", "https://host/sse"));
+        assert!(!covers("https://host/sse", "https://host/"));
+        assert!(!covers("https://other/", "https://host/sse"));
+        assert!(!covers("http://host/", "https://host/sse"));
+        assert!(!covers("https://host/#sse", "https://host/sse"));
+        assert!(!covers("https://host/?a=1", "https://host/sse"));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_checks_the_resource() {
+        let server = MockAuthorizationServer::new(3600);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        server.set_base(base.clone());
+        let routes = server.routes();
+        tokio::spawn(async move { axum::serve(listener, routes).await.unwrap() });
+        let http = HttpClient::new();
+        let oauth = oauth(&server);
+
+        // The metadata about `/sse` covers the paths below it
+        let www_authenticate =
+            format!(r#"Bearer resource_metadata="{base}/.well-known/oauth-protected-resource""#);
+        let discovered = oauth
+            .discover(
+                &http,
+                &format!("{base}/sse/messages"),
+                Some(&www_authenticate),
+            )
+            .await
+            .unwrap();
+        assert_eq!(discovered.resource, format!("{base}/sse"));
+
+        // The metadata is about `/sse`, not `/other`
+        let www_authenticate =
+            format!(r#"Bearer resource_metadata="{base}/.well-known/oauth-protected-resource""#);
+        let discovered = oauth
+            .discover(&http, &format!("{base}/other"), Some(&www_authenticate))
+            .await;
+        assert!(matches!(discovered, Err(AuthError::Discovery(_))));
+
+        // Metadata on another origin isn't even fetched
+        let www_authenticate =
+            r#"Bearer resource_metadata="http://127.0.0.1:1/.well-known/oauth-protected-resource""#;
+        let discovered = oauth
+            .discover(&http, &format!("{base}/sse"), Some(www_authenticate))
+            .await;
+        assert!(
+            matches!(discovered, Err(AuthError::Discovery(e)) if e.contains("not on the server's origin"))
+        );
+
+        let discovered = oauth
+            .discover(&http, &format!("{base}/sse#fragment"), None)
+            .await
+            .unwrap();
+        assert_eq!(discovered.resource, format!("{base}/sse"));
+    }
+}
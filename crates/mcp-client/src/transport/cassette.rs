@@ -0,0 +1,330 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use mcp_core::protocol::{ErrorData, JsonRpcResponse, MessageId};
+use mcp_core::transport::SendableMessage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+use super::{Error, TransportHandle};
+use crate::handler::Dispatcher;
+
+/// One line of a cassette: a request and the server's answer to it. Request IDs aren't kept, as
+/// they differ from run to run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    params: Option<Value>,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Result(Value),
+    Error(ErrorData),
+}
+
+impl Exchange {
+    fn new(method: String, params: Option<Value>, response: JsonRpcResponse) -> Self {
+        let outcome = match response {
+            JsonRpcResponse::Success { result, .. } => Outcome::Result(result),
+            JsonRpcResponse::Error { error, .. } => Outcome::Error(error),
+        };
+        Self {
+            method,
+            params: normalize(params),
+            outcome,
+        }
+    }
+
+    fn response(&self, id: MessageId) -> JsonRpcResponse {
+        match &self.outcome {
+            Outcome::Result(result) => JsonRpcResponse::success(id, result.clone()),
+            Outcome::Error(error) => JsonRpcResponse::error(id, error.clone()),
+        }
+    }
+}
+
+/// Missing and null params are recorded, and matched, the same way.
+fn normalize(params: Option<Value>) -> Option<Value> {
+    params.filter(|params| !params.is_null())
+}
+
+/// Wraps a transport handle, writing every request and the response to it to a cassette: a JSONL
+/// file, one exchange per line, for `ReplayHandle` to play back later.
+///
+/// Notifications are passed on but not recorded, and neither is anything the server sends
+/// unprompted.
+#[derive(Clone)]
+pub struct RecordingHandle<T> {
+    inner: T,
+    file: Arc<tokio::sync::Mutex<tokio::fs::File>>,
+}
+
+impl<T: TransportHandle> RecordingHandle<T> {
+    /// Record the exchanges over `inner` to the file at `path`, replacing anything already there.
+    pub async fn new(inner: T, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = tokio::fs::File::create(path).await?;
+        Ok(Self {
+            inner,
+            file: Arc::new(tokio::sync::Mutex::new(file)),
+        })
+    }
+
+    async fn record(&self, exchanges: Vec<Exchange>) -> Result<(), Error> {
+        let mut lines = String::new();
+        for exchange in exchanges {
+            lines.push_str(&serde_json::to_string(&exchange)?);
+            lines.push('\n');
+        }
+        let mut file = self.file.lock().await;
+        file.write_all(lines.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: TransportHandle> TransportHandle for RecordingHandle<T> {
+    async fn send(&self, message: SendableMessage) -> Result<Option<JsonRpcResponse>, Error> {
+        let request = match &message {
+            SendableMessage::Request(request) => {
+                Some((request.method.clone(), request.params.clone()))
+            }
+            SendableMessage::Notification(_) => None,
+        };
+        let response = self.inner.send(message).await?;
+        if let (Some((method, params)), Some(response)) = (request, &response) {
+            self.record(vec![Exchange::new(method, params, response.clone())])
+                .await?;
+        }
+        Ok(response)
+    }
+
+    async fn send_batch(
+        &self,
+        messages: Vec<SendableMessage>,
+    ) -> Result<Vec<JsonRpcResponse>, Error> {
+        let requests: Vec<_> = messages
+            .iter()
+            .filter_map(|message| match message {
+                SendableMessage::Request(request) => {
+                    Some((request.method.clone(), request.params.clone()))
+                }
+                SendableMessage::Notification(_) => None,
+            })
+            .collect();
+        let responses = self.inner.send_batch(messages).await?;
+        // Responses come back in the order of the requests
+        let exchanges = requests
+            .into_iter()
+            .zip(responses.iter().cloned())
+            .map(|((method, params), response)| Exchange::new(method, params, response))
+            .collect();
+        self.record(exchanges).await?;
+        Ok(responses)
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        self.inner.dispatcher()
+    }
+}
+
+/// The recorded exchanges by method, then by params, in the order they were recorded.
+type Recorded = HashMap<String, Vec<(Option<Value>, VecDeque<Exchange>)>>;
+
+/// Answers requests from a cassette written by `RecordingHandle`, without any server.
+///
+/// Requests are matched by method and params. When the same request was recorded several times,
+/// the responses are played back in the order they were recorded, and the last one is repeated
+/// once they run out. A request that was never recorded fails with `Error::NotRecorded`.
+/// Notifications are accepted and dropped.
+#[derive(Clone)]
+pub struct ReplayHandle {
+    exchanges: Arc<Mutex<Recorded>>,
+    dispatcher: Dispatcher,
+}
+
+impl ReplayHandle {
+    /// Load the cassette at `path`.
+    pub async fn new(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        Self::from_jsonl(&contents)
+    }
+
+    /// Load a cassette from its contents.
+    pub fn from_jsonl(contents: &str) -> Result<Self, Error> {
+        let mut exchanges = Recorded::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exchange: Exchange =
+                serde_json::from_str(line).map_err(|source| Error::InvalidCassette {
+                    line: index + 1,
+                    source,
+                })?;
+            let params = normalize(exchange.params.clone());
+            let recorded = exchanges.entry(exchange.method.clone()).or_default();
+            match recorded.iter_mut().find(|(p, _)| *p == params) {
+                Some((_, queue)) => queue.push_back(exchange),
+                None => recorded.push((params, VecDeque::from([exchange]))),
+            }
+        }
+        Ok(Self {
+            exchanges: Arc::new(Mutex::new(exchanges)),
+            dispatcher: Dispatcher::new(),
+        })
+    }
+
+    fn replay(&self, method: &str, params: Option<Value>) -> Option<Exchange> {
+        let params = normalize(params);
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let (_, queue) = exchanges
+            .get_mut(method)?
+            .iter_mut()
+            .find(|(p, _)| *p == params)?;
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    }
+}
+
+#[async_trait]
+impl TransportHandle for ReplayHandle {
+    async fn send(&self, message: SendableMessage) -> Result<Option<JsonRpcResponse>, Error> {
+        let SendableMessage::Request(request) = message else {
+            return Ok(None);
+        };
+        match self.replay(&request.method, request.params.clone()) {
+            Some(exchange) => Ok(Some(exchange.response(request.id))),
+            None => Err(Error::NotRecorded {
+                method: request.method,
+                params: request.params.unwrap_or(Value::Null).to_string(),
+            }),
+        }
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::{JsonRpcNotification, JsonRpcRequest, INVALID_PARAMS};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Counts calls to `increment`, and rejects any other method
+    #[derive(Clone, Default)]
+    struct Counter {
+        count: Arc<AtomicU64>,
+        dispatcher: Dispatcher,
+    }
+
+    #[async_trait]
+    impl TransportHandle for Counter {
+        async fn send(&self, message: SendableMessage) -> Result<Option<JsonRpcResponse>, Error> {
+            let SendableMessage::Request(request) = message else {
+                return Ok(None);
+            };
+            Ok(Some(match request.method.as_str() {
+                "increment" => {
+                    let by = request.params.map_or(1, |p| p["by"].as_u64().unwrap());
+                    let count = self.count.fetch_add(by, Ordering::SeqCst) + by;
+                    JsonRpcResponse::success(request.id, json!(count))
+                }
+                _ => JsonRpcResponse::error(
+                    request.id,
+                    ErrorData {
+                        code: INVALID_PARAMS,
+                        message: "Unknown method".to_string(),
+                        data: None,
+                    },
+                ),
+            }))
+        }
+
+        fn dispatcher(&self) -> &Dispatcher {
+            &self.dispatcher
+        }
+    }
+
+    fn request(id: u64, method: &str, params: Option<Value>) -> SendableMessage {
+        JsonRpcRequest::new(MessageId::Num(id), method.to_string(), params).into()
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.jsonl", std::process::id()));
+        let recorder = RecordingHandle::new(Counter::default(), &path)
+            .await
+            .unwrap();
+        recorder.send(request(1, "increment", None)).await.unwrap();
+        recorder
+            .send(JsonRpcNotification::new("notifications/initialized".to_string(), None).into())
+            .await
+            .unwrap();
+        recorder
+            .send_batch(vec![
+                request(2, "increment", Some(json!({"by": 5}))),
+                request(3, "decrement", None),
+            ])
+            .await
+            .unwrap();
+        recorder.send(request(4, "increment", None)).await.unwrap();
+
+        let contents = tokio::fs::read_to_string(&path).await.unwrap();
+        let _ = tokio::fs::remove_file(&path).await;
+        assert_eq!(contents.lines().count(), 4);
+
+        let replay = ReplayHandle::from_jsonl(&contents).unwrap();
+        let replayed = |id, method, params| {
+            let replay = replay.clone();
+            async move { replay.send(request(id, method, params)).await }
+        };
+        // Identical requests get the recorded responses in order, then the last one again
+        for (id, expected) in [(10, 1), (11, 7), (12, 7)] {
+            assert_eq!(
+                replayed(id, "increment", Some(Value::Null)).await.unwrap(),
+                Some(JsonRpcResponse::success(
+                    MessageId::Num(id),
+                    json!(expected)
+                ))
+            );
+        }
+        assert_eq!(
+            replayed(13, "increment", Some(json!({"by": 5})))
+                .await
+                .unwrap(),
+            Some(JsonRpcResponse::success(MessageId::Num(13), json!(6)))
+        );
+        assert!(matches!(
+            replayed(14, "decrement", None).await.unwrap(),
+            Some(JsonRpcResponse::Error { error, .. }) if error.code == INVALID_PARAMS
+        ));
+        assert!(matches!(
+            replayed(15, "increment", Some(json!({"by": 2}))).await,
+            Err(Error::NotRecorded { method, .. }) if method == "increment"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_cassette() {
+        let contents = "{\"method\": \"ping\", \"result\": {}}\n\nnot json\n";
+        assert!(matches!(
+            ReplayHandle::from_jsonl(contents),
+            Err(Error::InvalidCassette { line: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mcp_core::protocol::JsonRpcResponse;
+use mcp_core::transport::{Duplex, SendableMessage};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use super::{
+    handle_incoming, send_batch_message, send_message, Error, OutgoingMessage, PendingRequests,
+    Transport, TransportHandle,
+};
+use crate::handler::{Connected, Dispatcher};
+
+/// Passes messages between the handle and the other end of the duplex until either end goes away
+/// or `close_receiver` fires. The dispatcher's subscriptions end with it.
+struct MemoryActor {
+    receiver: mpsc::Receiver<OutgoingMessage>,
+    pending_requests: Arc<PendingRequests>,
+    duplex: Duplex,
+    dispatcher: Dispatcher,
+    _connected: Connected,
+    replies: mpsc::WeakSender<OutgoingMessage>,
+    close_receiver: oneshot::Receiver<()>,
+}
+
+impl MemoryActor {
+    async fn run(mut self) {
+        // The transport may be dropped without closing, which drops the close sender. The receiver
+        // then mustn't be polled again.
+        let mut close_dropped = false;
+        let closing = loop {
+            tokio::select! {
+                outgoing = self.receiver.recv() => {
+                    let Some(outgoing) = outgoing else {
+                        break false;
+                    };
+                    let Some(message) = outgoing.into_message(&self.pending_requests).await else {
+                        continue;
+                    };
+                    if self.duplex.send(message).await.is_err() {
+                        tracing::debug!("Server end of the in-memory transport was dropped");
+                        break false;
+                    }
+                }
+                incoming = self.duplex.recv() => {
+                    let Some(message) = incoming else {
+                        tracing::debug!("Server end of the in-memory transport was dropped");
+                        break false;
+                    };
+                    handle_incoming(message, &self.pending_requests, &self.dispatcher, &self.replies)
+                        .await;
+                }
+                closed = &mut self.close_receiver, if !close_dropped => {
+                    if closed.is_ok() {
+                        break true;
+                    }
+                    close_dropped = true;
+                }
+            }
+        };
+
+        // Nothing more will be sent or answered
+        self.pending_requests
+            .fail_all(|| {
+                if closing {
+                    Error::NotConnected
+                } else {
+                    Error::ChannelClosed
+                }
+            })
+            .await;
+    }
+}
+
+#[derive(Clone)]
+pub struct MemoryTransportHandle {
+    sender: mpsc::Sender<OutgoingMessage>,
+    dispatcher: Dispatcher,
+    closed: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl TransportHandle for MemoryTransportHandle {
+    async fn send(&self, message: SendableMessage) -> Result<Option<JsonRpcResponse>, Error> {
+        self.check_connected()?;
+        send_message(&self.sender, message).await
+    }
+
+    async fn send_batch(
+        &self,
+        messages: Vec<SendableMessage>,
+    ) -> Result<Vec<JsonRpcResponse>, Error> {
+        self.check_connected()?;
+        send_batch_message(&self.sender, messages).await
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+}
+
+impl MemoryTransportHandle {
+    fn check_connected(&self) -> Result<(), Error> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::NotConnected);
+        }
+        Ok(())
+    }
+}
+
+/// A transport to a server in the same process, over one end of `mcp_core::transport::duplex`.
+/// The server runs on the other end, e.g. with `mcp_server::MemoryTransport`. Messages are passed
+/// as they are rather than written out, and there is no process to spawn.
+///
+/// The transport can only be started once, as the duplex end is used up. Starting it again fails
+/// with `Error::AlreadyStarted`.
+pub struct MemoryTransport {
+    duplex: Mutex<Option<Duplex>>,
+    dispatcher: Dispatcher,
+    connection: Mutex<Option<(oneshot::Sender<()>, Arc<AtomicBool>)>>,
+}
+
+impl MemoryTransport {
+    pub fn new(duplex: Duplex) -> Self {
+        Self {
+            duplex: Mutex::new(Some(duplex)),
+            dispatcher: Dispatcher::new(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// The dispatcher for requests and notifications from the server. Register handlers on it to
+    /// answer the server's requests.
+    pub fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    type Handle = MemoryTransportHandle;
+
+    async fn start(&self) -> Result<Self::Handle, Error> {
+        let duplex = self
+            .duplex
+            .lock()
+            .await
+            .take()
+            .ok_or(Error::AlreadyStarted)?;
+        let (message_tx, message_rx) = mpsc::channel(32);
+        let (close_tx, close_rx) = oneshot::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let actor = MemoryActor {
+            receiver: message_rx,
+            pending_requests: Arc::new(PendingRequests::new()),
+            duplex,
+            dispatcher: self.dispatcher.clone(),
+            _connected: self.dispatcher.connect(),
+            replies: message_tx.downgrade(),
+            close_receiver: close_rx,
+        };
+        tokio::spawn(actor.run());
+
+        *self.connection.lock().await = Some((close_tx, closed.clone()));
+
+        Ok(MemoryTransportHandle {
+            sender: message_tx,
+            dispatcher: self.dispatcher.clone(),
+            closed,
+        })
+    }
+
+    /// Drop the transport's end of the duplex, which the server sees as the client disconnecting.
+    async fn close(&self) -> Result<(), Error> {
+        if let Some((close_sender, closed)) = self.connection.lock().await.take() {
+            closed.store(true, Ordering::SeqCst);
+            let _ = close_sender.send(());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::{
+        JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, MessageId, ServerNotification,
+    };
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_passes_messages_both_ways() {
+        let (client_end, mut server_end) = mcp_core::transport::duplex();
+        let transport = MemoryTransport::new(client_end);
+        let mut notifications = transport.dispatcher().subscribe();
+        let handle = transport.start().await.unwrap();
+        assert!(matches!(
+            transport.start().await,
+            Err(Error::AlreadyStarted)
+        ));
+
+        let request = JsonRpcRequest::new(MessageId::Num(7), "ping".to_string(), None);
+        let response = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send(request.into()).await }
+        });
+
+        // The server end gets the request itself, and answers with a notification and a response
+        let Some(JsonRpcMessage::Request(request)) = server_end.recv().await else {
+            panic!("Expected a request");
+        };
+        assert_eq!(request.method, "ping");
+        server_end
+            .send(
+                JsonRpcNotification::new(ServerNotification::TOOL_LIST_CHANGED.to_string(), None)
+                    .into(),
+            )
+            .await
+            .unwrap();
+        server_end
+            .send(JsonRpcResponse::success(request.id, json!({})).into())
+            .await
+            .unwrap();
+
+        let response = response.await.unwrap().unwrap().unwrap();
+        assert_eq!(
+            response,
+            JsonRpcResponse::success(MessageId::Num(7), json!({}))
+        );
+        assert!(matches!(
+            notifications.recv().await,
+            Some(ServerNotification::ToolListChanged)
+        ));
+
+        // Closing disconnects the server end, and ends the subscription
+        transport.close().await.unwrap();
+        assert!(server_end.recv().await.is_none());
+        assert!(notifications.recv().await.is_none());
+        let request = JsonRpcRequest::new(MessageId::Num(8), "ping".to_string(), None);
+        assert!(matches!(
+            handle.send(request.into()).await,
+            Err(Error::NotConnected)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_batch_gets_one_batch_reply() {
+        let (client_end, mut server_end) = mcp_core::transport::duplex();
+        let transport = MemoryTransport::new(client_end);
+        let _handle = transport.start().await.unwrap();
+
+        let batch = vec![
+            JsonRpcRequest::new(MessageId::Num(1), "ping".to_string(), None).into(),
+            JsonRpcNotification::new("notifications/message".to_string(), None).into(),
+            JsonRpcRequest::new(MessageId::Num(2), "roots/list".to_string(), None).into(),
+        ];
+        server_end.send(JsonRpcMessage::Batch(batch)).await.unwrap();
+
+        let Some(JsonRpcMessage::Batch(replies)) = server_end.recv().await else {
+            panic!("Expected a batch");
+        };
+        let replies: Vec<_> = replies
+            .into_iter()
+            .map(|reply| serde_json::to_value(reply).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0], json!({"jsonrpc": "2.0", "id": 1, "result": {}}));
+        assert_eq!(replies[1]["id"], 2);
+        assert_eq!(
+            replies[1]["error"]["code"],
+            mcp_core::protocol::METHOD_NOT_FOUND
+        );
+        transport.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handle_outlives_the_transport() {
+        let (client_end, mut server_end) = mcp_core::transport::duplex();
+        let transport = MemoryTransport::new(client_end);
+        let handle = transport.start().await.unwrap();
+        drop(transport);
+
+        let request = JsonRpcRequest::new(MessageId::Num(1), "ping".to_string(), None);
+        let response = tokio::spawn(async move { handle.send(request.into()).await });
+        let Some(JsonRpcMessage::Request(request)) = server_end.recv().await else {
+            panic!("Expected a request");
+        };
+        server_end
+            .send(JsonRpcResponse::success(request.id, json!({})).into())
+            .await
+            .unwrap();
+        assert!(response.await.unwrap().unwrap().is_some());
+    }
+}
@@ -1,9 +1,15 @@
 use async_trait::async_trait;
-use mcp_core::{protocol::JsonRpcResponse, protocol::MessageId, transport::SendableMessage};
+use mcp_core::{
+    protocol::{JsonRpcMessage, JsonRpcResponse, MessageId},
+    transport::SendableMessage,
+};
 use std::collections::HashMap;
+use std::sync::OnceLock;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot, RwLock};
 
+use crate::handler::Dispatcher;
+
 pub type BoxError = Box<dyn std::error::Error + Sync + Send>;
 
 /// A generic error type for transport operations.
@@ -19,6 +25,9 @@ pub enum Error {
     #[error("Channel closed")]
     ChannelClosed,
 
+    #[error("Transport can only be started once")]
+    AlreadyStarted,
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -28,11 +37,43 @@ pub enum Error {
     #[error("Stdio process error: {0}")]
     StdioProcessError(String),
 
+    #[error("Command {command} not found, looked in {searched}")]
+    CommandNotFound {
+        command: String,
+        /// Where the command was looked for: the directories on `PATH`, or the path it resolved to
+        searched: String,
+    },
+
+    #[error(
+        "Stdio process exited ({}). Last stderr output:\n{stderr}",
+        .status.map_or_else(|| "unknown status".to_string(), |status| status.to_string())
+    )]
+    StdioProcessExited {
+        status: Option<std::process::ExitStatus>,
+        /// The last lines the process wrote to stderr
+        stderr: String,
+    },
+
     #[error("SSE connection error: {0}")]
     SseConnection(String),
 
     #[error("HTTP error: {status} - {message}")]
     HttpError { status: u16, message: String },
+
+    #[error("Invalid HTTP configuration: {0}")]
+    HttpConfig(String),
+
+    #[error(transparent)]
+    Auth(#[from] auth::AuthError),
+
+    #[error("No recorded response to {method} with params {params}")]
+    NotRecorded { method: String, params: String },
+
+    #[error("Invalid cassette, line {line}: {source}")]
+    InvalidCassette {
+        line: usize,
+        source: serde_json::Error,
+    },
 }
 
 /// A message that can be sent through the transport
@@ -44,6 +85,136 @@ pub struct TransportMessage {
     pub response_tx: Option<oneshot::Sender<Result<JsonRpcResponse, Error>>>,
 }
 
+/// What the handle passes to a transport's actor: a single message, or several messages to be sent
+/// together as one JSON-RPC batch. The actor also uses it to answer requests from the server: a
+/// single request with a `Reply`, and a batch of them with one batch of `Replies`.
+#[derive(Debug)]
+pub enum OutgoingMessage {
+    Single(TransportMessage),
+    Batch(Vec<TransportMessage>),
+    Reply(JsonRpcResponse),
+    Replies(Vec<JsonRpcResponse>),
+}
+
+impl OutgoingMessage {
+    fn into_messages(self) -> (Vec<TransportMessage>, bool) {
+        match self {
+            OutgoingMessage::Single(msg) => (vec![msg], false),
+            OutgoingMessage::Batch(batch) => (batch, true),
+            OutgoingMessage::Reply(_) | OutgoingMessage::Replies(_) => (vec![], false),
+        }
+    }
+
+    /// Serialize the message (or batch) for the wire, and register the response channels of any
+    /// requests in `pending_requests`.
+    ///
+    /// A message which can't be serialized gets the error on its response channel and is left out.
+    /// Returns `None` if nothing is left to send.
+    pub async fn prepare(self, pending_requests: &PendingRequests) -> Option<String> {
+        let replies = match &self {
+            OutgoingMessage::Reply(response) => Some(serde_json::to_string(response)),
+            OutgoingMessage::Replies(responses) => Some(serde_json::to_string(responses)),
+            _ => None,
+        };
+        if let Some(replies) = replies {
+            return match replies {
+                Ok(response_str) => Some(response_str),
+                Err(e) => {
+                    tracing::error!(error = ?e, "Failed to serialize response to server");
+                    None
+                }
+            };
+        }
+
+        let (messages, is_batch) = self.into_messages();
+        let mut serialized = Vec::with_capacity(messages.len());
+        for transport_msg in messages {
+            match serde_json::to_string(&transport_msg.message) {
+                Ok(message_str) => {
+                    // If the message requires a response, insert it into the pending requests map.
+                    if let (Some(response_tx), SendableMessage::Request(request)) =
+                        (transport_msg.response_tx, &transport_msg.message)
+                    {
+                        pending_requests
+                            .insert(request.id.clone(), response_tx)
+                            .await;
+                    }
+                    serialized.push(message_str);
+                }
+                Err(e) => {
+                    // If we can't serialize the message, send an error response on the response channel.
+                    if let Some(tx) = transport_msg.response_tx {
+                        let _ = tx.send(Err(Error::Serialization(e)));
+                    }
+                }
+            }
+        }
+
+        match serialized.len() {
+            0 => None,
+            _ if is_batch => Some(format!("[{}]", serialized.join(","))),
+            _ => serialized.pop(),
+        }
+    }
+
+    /// Like `prepare`, but keep the message as it is, for transports that pass messages along
+    /// without writing them out.
+    pub async fn into_message(self, pending_requests: &PendingRequests) -> Option<JsonRpcMessage> {
+        match self {
+            OutgoingMessage::Reply(response) => return Some(response.into()),
+            OutgoingMessage::Replies(responses) => {
+                let responses = responses.into_iter().map(JsonRpcMessage::from).collect();
+                return Some(JsonRpcMessage::Batch(responses));
+            }
+            _ => {}
+        }
+
+        let (messages, is_batch) = self.into_messages();
+        let mut batch = Vec::with_capacity(messages.len());
+        for transport_msg in messages {
+            if let (Some(response_tx), SendableMessage::Request(request)) =
+                (transport_msg.response_tx, &transport_msg.message)
+            {
+                pending_requests
+                    .insert(request.id.clone(), response_tx)
+                    .await;
+            }
+            batch.push(transport_msg.message.into());
+        }
+
+        match batch.len() {
+            0 => None,
+            _ if is_batch => Some(JsonRpcMessage::Batch(batch)),
+            _ => batch.pop(),
+        }
+    }
+
+    /// The IDs of the requests in the message (or batch).
+    pub fn request_ids(&self) -> Vec<MessageId> {
+        let messages = match self {
+            OutgoingMessage::Single(msg) => std::slice::from_ref(msg),
+            OutgoingMessage::Batch(batch) => batch.as_slice(),
+            OutgoingMessage::Reply(_) | OutgoingMessage::Replies(_) => &[],
+        };
+        messages
+            .iter()
+            .filter_map(|msg| match &msg.message {
+                SendableMessage::Request(request) => Some(request.id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Fail the message (or every message in the batch) without sending it.
+    pub fn fail(self, error: impl Fn() -> Error) {
+        for transport_msg in self.into_messages().0 {
+            if let Some(tx) = transport_msg.response_tx {
+                let _ = tx.send(Err(error()));
+            }
+        }
+    }
+}
+
 /// A generic asynchronous transport trait, used to abstract over the underlying transport mechanism.
 ///
 /// The transport can be started and closed. Starting the transport returns a handle, which can be
@@ -57,6 +228,9 @@ pub trait Transport {
     async fn start(&self) -> Result<Self::Handle, Error>;
 
     /// Close the transport and free any resources.
+    ///
+    /// Requests still waiting on a response fail, and handles to the transport fail with
+    /// `Error::NotConnected` from then on.
     async fn close(&self) -> Result<(), Error>;
 }
 
@@ -68,11 +242,38 @@ pub trait TransportHandle: Send + Sync + Clone + 'static {
     /// For requests, a `JsonRpcResponse` (or error) is returned. For notifications, there is no
     /// response if the request is successful.
     async fn send(&self, message: SendableMessage) -> Result<Option<JsonRpcResponse>, Error>;
+
+    /// Send several messages as a single JSON-RPC batch.
+    ///
+    /// Returns the responses to the requests in the batch, in the order the requests were given.
+    /// Notifications in the batch have no response. The default implementation sends each message
+    /// on its own, for transports that can't batch.
+    async fn send_batch(
+        &self,
+        messages: Vec<SendableMessage>,
+    ) -> Result<Vec<JsonRpcResponse>, Error> {
+        let responses =
+            futures::future::try_join_all(messages.into_iter().map(|m| self.send(m))).await?;
+        Ok(responses.into_iter().flatten().collect())
+    }
+
+    /// The dispatcher handling requests and notifications the server sends over this transport.
+    ///
+    /// The default is a dispatcher nothing is ever sent to, for transports that can't receive
+    /// messages from the server. Its subscriptions end straight away.
+    fn dispatcher(&self) -> &Dispatcher {
+        static DETACHED: OnceLock<Dispatcher> = OnceLock::new();
+        DETACHED.get_or_init(|| {
+            let dispatcher = Dispatcher::new();
+            dispatcher.close();
+            dispatcher
+        })
+    }
 }
 
 // Helper function that contains the common send implementation
 pub async fn send_message(
-    sender: &mpsc::Sender<TransportMessage>,
+    sender: &mpsc::Sender<OutgoingMessage>,
     message: SendableMessage,
 ) -> Result<Option<JsonRpcResponse>, Error> {
     match message {
@@ -82,7 +283,10 @@ pub async fn send_message(
                 message,
                 response_tx: Some(respond_to),
             };
-            sender.send(msg).await.map_err(|_| Error::ChannelClosed)?;
+            sender
+                .send(OutgoingMessage::Single(msg))
+                .await
+                .map_err(|_| Error::ChannelClosed)?;
             Ok(Some(response.await.map_err(|_| Error::ChannelClosed)??))
         }
         SendableMessage::Notification(_) => {
@@ -90,12 +294,109 @@ pub async fn send_message(
                 message,
                 response_tx: None,
             };
-            sender.send(msg).await.map_err(|_| Error::ChannelClosed)?;
+            sender
+                .send(OutgoingMessage::Single(msg))
+                .await
+                .map_err(|_| Error::ChannelClosed)?;
             Ok(None)
         }
     }
 }
 
+// Helper function that contains the common batch send implementation
+pub async fn send_batch_message(
+    sender: &mpsc::Sender<OutgoingMessage>,
+    messages: Vec<SendableMessage>,
+) -> Result<Vec<JsonRpcResponse>, Error> {
+    let mut batch = Vec::with_capacity(messages.len());
+    let mut responses = Vec::new();
+    for message in messages {
+        let response_tx = match message {
+            SendableMessage::Request(_) => {
+                let (respond_to, response) = oneshot::channel();
+                responses.push(response);
+                Some(respond_to)
+            }
+            SendableMessage::Notification(_) => None,
+        };
+        batch.push(TransportMessage {
+            message,
+            response_tx,
+        });
+    }
+
+    sender
+        .send(OutgoingMessage::Batch(batch))
+        .await
+        .map_err(|_| Error::ChannelClosed)?;
+
+    let mut results = Vec::with_capacity(responses.len());
+    for response in responses {
+        results.push(response.await.map_err(|_| Error::ChannelClosed)??);
+    }
+    Ok(results)
+}
+
+/// Parse an incoming line as a message, or a batch of messages.
+pub(crate) fn parse_message(data: &str) -> Option<JsonRpcMessage> {
+    serde_json::from_str(data).ok()
+}
+
+/// Act on a message (or batch) from the server: deliver responses to the requests waiting on
+/// them, pass notifications to subscribers, and answer requests through `replies`.
+///
+/// Requests are answered on their own task, so a handler waiting on the user doesn't hold up
+/// reading further messages. The requests of a batch are answered together, with one batch.
+pub(crate) async fn handle_incoming(
+    message: JsonRpcMessage,
+    pending_requests: &PendingRequests,
+    dispatcher: &Dispatcher,
+    replies: &mpsc::WeakSender<OutgoingMessage>,
+) {
+    let (messages, is_batch) = match message {
+        JsonRpcMessage::Batch(messages) => (messages, true),
+        message => (vec![message], false),
+    };
+    let mut requests = Vec::new();
+    for message in messages {
+        match message {
+            JsonRpcMessage::Response(response) => {
+                tracing::debug!(message = ?response, "Received incoming message");
+                pending_requests.respond_to(response).await;
+            }
+            JsonRpcMessage::Notification(notification) => dispatcher.notify(notification),
+            JsonRpcMessage::Request(request) => requests.push(request),
+            JsonRpcMessage::Batch(_) => tracing::error!("Ignoring nested batch"),
+        }
+    }
+    if requests.is_empty() {
+        return;
+    }
+
+    let dispatcher = dispatcher.clone();
+    let replies = replies.clone();
+    tokio::spawn(async move {
+        let mut responses = futures::future::join_all(
+            requests
+                .into_iter()
+                .map(|request| dispatcher.handle_request(request)),
+        )
+        .await;
+        let reply = match responses.pop() {
+            Some(response) if !is_batch => OutgoingMessage::Reply(response),
+            Some(response) => {
+                responses.push(response);
+                OutgoingMessage::Replies(responses)
+            }
+            None => return,
+        };
+        // The transport may have stopped while the handlers ran
+        if let Some(replies) = replies.upgrade() {
+            let _ = replies.send(reply).await;
+        }
+    });
+}
+
 // A data structure to store pending requests and their response channels
 pub struct PendingRequests {
     requests: RwLock<HashMap<MessageId, oneshot::Sender<Result<JsonRpcResponse, Error>>>>,
@@ -122,19 +423,56 @@ impl PendingRequests {
         self.requests.write().await.insert(id, sender);
     }
 
+    /// Deliver a response to the request waiting on it.
+    pub async fn respond_to(&self, response: JsonRpcResponse) {
+        let id = match &response {
+            JsonRpcResponse::Success { id, .. } => id.clone(),
+            JsonRpcResponse::Error { id, .. } => id.clone(),
+        };
+        self.respond(&id, Ok(response)).await;
+    }
+
     pub async fn respond(&self, id: &MessageId, response: Result<JsonRpcResponse, Error>) {
         if let Some(tx) = self.requests.write().await.remove(id) {
             let _ = tx.send(response);
         }
     }
 
+    /// The IDs of the requests waiting on a response.
+    pub async fn ids(&self) -> Vec<MessageId> {
+        self.requests.read().await.keys().cloned().collect()
+    }
+
     pub async fn clear(&self) {
         self.requests.write().await.clear();
     }
+
+    /// Fail every request still waiting on a response.
+    pub async fn fail_all(&self, error: impl Fn() -> Error) {
+        for (_, tx) in self.requests.write().await.drain() {
+            let _ = tx.send(Err(error()));
+        }
+    }
 }
 
 pub mod stdio;
-pub use stdio::StdioTransport;
+pub use stdio::{StdioTransport, StdioTransportBuilder};
+
+#[cfg(target_os = "linux")]
+pub mod sandbox;
+#[cfg(target_os = "linux")]
+pub use sandbox::Sandbox;
+
+pub mod memory;
+pub use memory::MemoryTransport;
+
+pub mod cassette;
+pub use cassette::{RecordingHandle, ReplayHandle};
+
+pub mod auth;
+pub use auth::{OAuth, OAuthConfig};
+
+mod http;
 
 pub mod sse;
-pub use sse::SseTransport;
+pub use sse::{ConnectionState, ReconnectPolicy, SseTransport, SseTransportBuilder};
@@ -0,0 +1,553 @@
+use std::ffi::CStr;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use super::Error;
+
+// Landlock's ABI, from linux/landlock.h
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+const ACCESS_FS_IOCTL_DEV: u64 = 1 << 15;
+
+/// The rights that apply to files, rather than to directories and what is in them.
+const FILE_ACCESS: u64 = ACCESS_FS_EXECUTE
+    | ACCESS_FS_WRITE_FILE
+    | ACCESS_FS_READ_FILE
+    | ACCESS_FS_TRUNCATE
+    | ACCESS_FS_IOCTL_DEV;
+const READ_ONLY_ACCESS: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Every filesystem right the kernel's version of Landlock knows of. Each version adds to those
+/// before it.
+fn handled_access(abi: i64) -> u64 {
+    match abi {
+        1 => (1 << 13) - 1,
+        2 => (1 << 14) - 1,
+        3 | 4 => (1 << 15) - 1,
+        _ => (1 << 16) - 1,
+    }
+}
+
+/// Directories most programs need to run, read-only.
+const SYSTEM_ROOTS: &[&str] = &["/usr", "/lib", "/lib64", "/bin", "/sbin", "/etc", "/proc"];
+
+/// Devices most programs expect to be able to read and write.
+const SYSTEM_DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+#[derive(Debug, Clone)]
+struct Root {
+    path: PathBuf,
+    writable: bool,
+}
+
+/// Limits and isolation for a stdio server's process, applied between forking and running the
+/// command. Linux only, and needs no privileges.
+///
+/// - Resource limits: CPU time, memory (address space), and open files.
+/// - Network isolation: the process runs in a new user and network namespace, with no network
+///   but an unconfigured loopback device. Our user and group are mapped to themselves.
+/// - Filesystem roots: a Landlock ruleset limits the process to the roots given, and what is
+///   beneath them. The command itself must be under one of them, as must the libraries it loads,
+///   so most sandboxes want [`Sandbox::with_system_roots`].
+///
+/// A process that dies of SIGXCPU or SIGKILL under a CPU time limit is reported as
+/// `Error::StdioProcessError`, as is one that dies of SIGSEGV or SIGABRT under a memory limit,
+/// which is how running out of address space usually ends. Either signal may have other causes,
+/// so the report says the limit was possibly the reason. One that can't be sandboxed, e.g. as the kernel lacks Landlock or
+/// doesn't allow unprivileged user namespaces, fails to start with `Error::StdioProcessError`.
+///
+/// Other violations can't be told apart from the process's own failures, so are reported as it
+/// exiting: a process that handles failed allocations and exits, one that runs out of open files
+/// (`EMFILE`), and access outside the roots, which fails inside the process with `EACCES`.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use mcp_client::transport::{Sandbox, StdioTransport};
+/// let transport = StdioTransport::builder("/usr/bin/mcp-server-files")
+///     .with_sandbox(
+///         Sandbox::new()
+///             .with_cpu_time_limit(Duration::from_secs(60))
+///             .with_memory_limit(512 * 1024 * 1024)
+///             .with_open_files_limit(256)
+///             .with_network_isolation()
+///             .with_system_roots()
+///             .with_writable_root("/srv/shared"),
+///     )
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    cpu_time: Option<Duration>,
+    memory: Option<u64>,
+    open_files: Option<u64>,
+    isolate_network: bool,
+    roots: Option<Vec<Root>>,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the CPU time the process may use, rounded up to whole seconds. It is sent SIGXCPU
+    /// when it runs out, and SIGKILL a second later.
+    pub fn with_cpu_time_limit(mut self, limit: Duration) -> Self {
+        self.cpu_time = Some(limit);
+        self
+    }
+
+    /// Limit the process's address space to `bytes`. Allocations beyond it fail.
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        self.memory = Some(bytes);
+        self
+    }
+
+    /// Limit how many files the process may have open at once.
+    pub fn with_open_files_limit(mut self, files: u64) -> Self {
+        self.open_files = Some(files);
+        self
+    }
+
+    /// Run the process in new user and network namespaces, cutting it off from the network.
+    pub fn with_network_isolation(mut self) -> Self {
+        self.isolate_network = true;
+        self
+    }
+
+    /// Let the process read and run anything under `path`. The first root limits the process to
+    /// its roots.
+    pub fn with_read_only_root(mut self, path: impl Into<PathBuf>) -> Self {
+        self.roots.get_or_insert_with(Vec::new).push(Root {
+            path: path.into(),
+            writable: false,
+        });
+        self
+    }
+
+    /// Let the process do anything under `path`. The first root limits the process to its roots.
+    pub fn with_writable_root(mut self, path: impl Into<PathBuf>) -> Self {
+        self.roots.get_or_insert_with(Vec::new).push(Root {
+            path: path.into(),
+            writable: true,
+        });
+        self
+    }
+
+    /// Add what most programs need to run as roots: `/usr`, `/lib`, `/lib64`, `/bin`, `/sbin`,
+    /// `/etc` and `/proc` read-only, and `/dev/null`, `/dev/zero`, `/dev/random` and
+    /// `/dev/urandom` writable. Those that don't exist are skipped.
+    pub fn with_system_roots(mut self) -> Self {
+        let roots = self.roots.get_or_insert_with(Vec::new);
+        for (paths, writable) in [(SYSTEM_ROOTS, false), (SYSTEM_DEVICES, true)] {
+            roots.extend(
+                paths
+                    .iter()
+                    .map(PathBuf::from)
+                    .filter(|path| path.exists())
+                    .map(|path| Root { path, writable }),
+            );
+        }
+        self
+    }
+
+    /// Do everything that can be done before forking, so the child only has to make system calls.
+    pub(crate) fn prepare(&self) -> Result<PreparedSandbox, Error> {
+        let error = |message: String| Error::StdioProcessError(format!("Sandbox: {message}"));
+        let ruleset = match &self.roots {
+            Some(roots) => Some(ruleset(roots).map_err(error)?),
+            None => None,
+        };
+        // SAFETY: getuid and getgid always succeed
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Ok(PreparedSandbox {
+            cpu_time: self.cpu_time.map(|limit| limit.as_secs_f64().ceil() as u64),
+            memory: self.memory,
+            open_files: self.open_files,
+            id_maps: self
+                .isolate_network
+                .then(|| (format!("{uid} {uid} 1"), format!("{gid} {gid} 1"))),
+            ruleset,
+        })
+    }
+
+    /// Why the process ended, if it was possibly for breaking the sandbox's limits. Only signals
+    /// tell, and only those a configured limit can raise: SIGXCPU and SIGKILL from the CPU time
+    /// limit, and SIGSEGV and SIGABRT from failed allocations under a memory limit. Anything may
+    /// send those signals too, so this is a guess.
+    pub(crate) fn violation(&self, status: &ExitStatus) -> Option<String> {
+        let signal = status.signal()?;
+        if let Some(limit) = self.cpu_time {
+            let seconds = limit.as_secs_f64().ceil();
+            let name = match signal {
+                libc::SIGXCPU => Some("SIGXCPU"),
+                libc::SIGKILL => Some("SIGKILL"),
+                _ => None,
+            };
+            if let Some(name) = name {
+                return Some(format!(
+                    "Process died of {name}, possibly for exceeding its CPU time limit of \
+                     {seconds}s"
+                ));
+            }
+        }
+        let name = match signal {
+            libc::SIGSEGV => "SIGSEGV",
+            libc::SIGABRT => "SIGABRT",
+            _ => return None,
+        };
+        self.memory.map(|bytes| {
+            format!(
+                "Process died of {name}, possibly for exceeding its memory limit of {bytes} bytes"
+            )
+        })
+    }
+}
+
+/// A Landlock ruleset allowing access to `roots`, and nothing else.
+fn ruleset(roots: &[Root]) -> Result<OwnedFd, String> {
+    // SAFETY: asking for the ABI version takes no attributes
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    if abi < 0 {
+        return Err(format!(
+            "Landlock isn't available: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    let handled = handled_access(abi);
+
+    let attr = RulesetAttr {
+        handled_access_fs: handled,
+    };
+    // SAFETY: attr outlives the call, and its size is passed along with it
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr,
+            std::mem::size_of::<RulesetAttr>(),
+            0,
+        )
+    };
+    if fd < 0 {
+        return Err(format!(
+            "Failed to create a Landlock ruleset: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    // SAFETY: the kernel just gave us this descriptor, which nothing else owns
+    let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+    for root in roots {
+        let path = root.path.display();
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(&root.path)
+            .map_err(|e| format!("Failed to open root {path}: {e}"))?;
+        let is_dir = file
+            .metadata()
+            .map_err(|e| format!("Failed to open root {path}: {e}"))?
+            .is_dir();
+        let mut allowed = if root.writable {
+            handled
+        } else {
+            READ_ONLY_ACCESS
+        };
+        if !is_dir {
+            allowed &= FILE_ACCESS;
+        }
+        let rule = PathBeneathAttr {
+            allowed_access: allowed & handled,
+            parent_fd: file.as_raw_fd(),
+        };
+        // SAFETY: rule outlives the call, and both descriptors are open
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                LANDLOCK_RULE_PATH_BENEATH,
+                &rule,
+                0,
+            )
+        };
+        if result < 0 {
+            return Err(format!(
+                "Failed to add root {path}: {}",
+                io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(ruleset)
+}
+
+/// A `Sandbox` ready to apply in the child process.
+pub(crate) struct PreparedSandbox {
+    cpu_time: Option<u64>,
+    memory: Option<u64>,
+    open_files: Option<u64>,
+    /// The contents of `uid_map` and `gid_map`, when isolating the network
+    id_maps: Option<(String, String)>,
+    ruleset: Option<OwnedFd>,
+}
+
+impl PreparedSandbox {
+    /// Apply the sandbox to the current process. Runs between fork and exec, so it must only make
+    /// async-signal-safe calls: no allocating, and no locks.
+    pub(crate) fn apply(&self) -> io::Result<()> {
+        if let Some(seconds) = self.cpu_time {
+            // The soft limit sends SIGXCPU, which `violation` recognizes, before the hard one
+            // sends SIGKILL
+            set_limit(libc::RLIMIT_CPU, seconds, seconds + 1)?;
+        }
+        if let Some(bytes) = self.memory {
+            set_limit(libc::RLIMIT_AS, bytes, bytes)?;
+        }
+        if let Some(files) = self.open_files {
+            set_limit(libc::RLIMIT_NOFILE, files, files)?;
+        }
+
+        if let Some((uid_map, gid_map)) = &self.id_maps {
+            // SAFETY: unshare has no memory safety requirements
+            if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Unprivileged processes may only map their group once setgroups is denied
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", uid_map.as_bytes())?;
+            write_file(c"/proc/self/gid_map", gid_map.as_bytes())?;
+        }
+
+        if let Some(ruleset) = &self.ruleset {
+            // SAFETY: neither call has memory safety requirements
+            unsafe {
+                // Landlock needs this from unprivileged processes, and it keeps setuid programs
+                // from gaining privileges outside the sandbox
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_env = "gnu")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type Resource = libc::c_int;
+
+fn set_limit(resource: Resource, soft: u64, hard: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    // SAFETY: limit outlives the call
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+    // SAFETY: path is NUL-terminated, contents outlives the write, and fd is closed once
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        let error = io::Error::last_os_error();
+        libc::close(fd);
+        if written != contents.len() as isize {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{StdioTransport, Transport, TransportHandle};
+    use mcp_core::protocol::{JsonRpcRequest, JsonRpcResponse, MessageId};
+    use mcp_core::transport::SendableMessage;
+    use serde_json::Value;
+
+    fn ping() -> SendableMessage {
+        SendableMessage::Request(JsonRpcRequest::new(
+            MessageId::Num(1),
+            "ping".to_string(),
+            None,
+        ))
+    }
+
+    /// Run `script` in `sandbox`, and answer our request with the JSON it prints.
+    async fn run(script: &str, sandbox: Sandbox) -> Result<Value, Error> {
+        let script = format!(
+            r#"read line; printf '{{"jsonrpc":"2.0","id":1,"result":%s}}\n' "$({script})""#
+        );
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", &script])
+            .with_sandbox(sandbox)
+            .build();
+        let handle = transport.start().await?;
+        match handle.send(ping()).await? {
+            Some(JsonRpcResponse::Success { result, .. }) => Ok(result),
+            response => panic!("Expected a successful response, got {response:?}"),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-sandbox-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_resource_limits() {
+        let sandbox = Sandbox::new()
+            .with_open_files_limit(64)
+            .with_memory_limit(1 << 30);
+        let result = run(r#"echo "[$(ulimit -n), $(ulimit -v)]""#, sandbox)
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!([64, 1 << 20]));
+    }
+
+    #[tokio::test]
+    async fn test_cpu_time_limit_is_reported() {
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", "while :; do :; done"])
+            .with_sandbox(Sandbox::new().with_cpu_time_limit(Duration::from_secs(1)))
+            .build();
+        let handle = transport.start().await.unwrap();
+        let result = handle.send(ping()).await;
+        assert!(
+            matches!(&result, Err(Error::StdioProcessError(message)) if message.contains("CPU time limit of 1s")),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit_crash_is_reported() {
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", "kill -SEGV $$"])
+            .with_sandbox(Sandbox::new().with_memory_limit(1 << 30))
+            .build();
+        let handle = transport.start().await.unwrap();
+        let result = handle.send(ping()).await;
+        assert!(
+            matches!(&result, Err(Error::StdioProcessError(message)) if message.contains("SIGSEGV, possibly for exceeding its memory limit")),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_violations() {
+        let sandbox = Sandbox::new().with_cpu_time_limit(Duration::from_millis(1500));
+        let killed = |signal| sandbox.violation(&ExitStatus::from_raw(signal));
+        assert!(killed(libc::SIGXCPU)
+            .unwrap()
+            .contains("CPU time limit of 2s"));
+        assert!(killed(libc::SIGKILL).unwrap().contains("CPU time limit"));
+        assert_eq!(killed(libc::SIGSEGV), None);
+        // Exiting with a status is the process's own doing
+        assert_eq!(sandbox.violation(&ExitStatus::from_raw(1 << 8)), None);
+
+        let sandbox = Sandbox::new().with_memory_limit(1024);
+        let killed = |signal| sandbox.violation(&ExitStatus::from_raw(signal));
+        assert!(killed(libc::SIGSEGV)
+            .unwrap()
+            .contains("memory limit of 1024 bytes"));
+        assert!(killed(libc::SIGABRT).is_some());
+        // Running out of address space doesn't raise these
+        assert_eq!(killed(libc::SIGKILL), None);
+        assert_eq!(killed(libc::SIGBUS), None);
+        assert_eq!(killed(libc::SIGTERM), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs unprivileged user namespaces"]
+    async fn test_network_isolation() {
+        let ours = std::fs::read_link("/proc/self/ns/net").unwrap();
+        let sandbox = Sandbox::new().with_network_isolation();
+        let result = run(
+            r#"echo "[\"$(readlink /proc/self/ns/net)\", $(id -u)]""#,
+            sandbox,
+        )
+        .await
+        .unwrap();
+        assert_ne!(result[0], ours.to_str().unwrap());
+        // SAFETY: getuid always succeeds
+        assert_eq!(result[1], unsafe { libc::getuid() });
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Landlock"]
+    async fn test_filesystem_roots() {
+        let inside = temp_dir("inside");
+        let outside = temp_dir("outside");
+        std::fs::write(outside.join("secret"), "secret").unwrap();
+
+        let sandbox = Sandbox::new()
+            .with_system_roots()
+            .with_writable_root(&inside);
+        let script = format!(
+            r#"echo written > {}/out; cat {}/secret >/dev/null 2>&1; echo "$?""#,
+            inside.display(),
+            outside.display()
+        );
+        let result = run(&script, sandbox).await.unwrap();
+        assert_ne!(result, 0, "read outside the roots");
+        assert_eq!(
+            std::fs::read_to_string(inside.join("out")).unwrap(),
+            "written\n"
+        );
+
+        let _ = std::fs::remove_dir_all(inside);
+        let _ = std::fs::remove_dir_all(outside);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Landlock"]
+    async fn test_invalid_root_fails_to_start() {
+        let sandbox = Sandbox::new().with_read_only_root("/nonexistent/mcp-sandbox-root");
+        let result = run("echo 0", sandbox).await;
+        assert!(
+            matches!(&result, Err(Error::StdioProcessError(message)) if message.contains("/nonexistent/mcp-sandbox-root")),
+            "{result:?}"
+        );
+    }
+}
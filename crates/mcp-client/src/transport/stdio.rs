@@ -1,86 +1,369 @@
-use mcp_core::protocol::JsonRpcResponse;
+use mcp_core::protocol::{
+    CancelledParams, JsonRpcNotification, JsonRpcResponse, ServerNotification,
+};
 use mcp_core::transport::SendableMessage;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
 
 use async_trait::async_trait;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::{mpsc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
 
-use super::{send_message, Error, PendingRequests, Transport, TransportHandle, TransportMessage};
+use super::{
+    handle_incoming, parse_message, send_batch_message, send_message, Error, OutgoingMessage,
+    PendingRequests, Transport, TransportHandle,
+};
+use crate::handler::{Connected, Dispatcher};
+
+#[cfg(target_os = "linux")]
+use super::sandbox::Sandbox;
+
+/// How long `close` waits for the process to exit after closing its stdin, and again after
+/// sending it SIGTERM, before escalating.
+const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sent to the process as `close` starts, after a `notifications/cancelled` for each request it
+/// hasn't answered.
+///
+/// This is a custom extension of this crate, not a method of the MCP specification: the spec
+/// shuts a stdio server down by closing its stdin, and has no shutdown message. Servers that don't
+/// know it ignore it, as they must any unknown notification, and stop once their stdin is closed.
+pub const SHUTDOWN_NOTIFICATION: &str = "notifications/shutdown";
+
+/// The longest line of stderr passed on whole. Longer lines are split.
+const MAX_STDERR_LINE: usize = 16 * 1024;
+
+/// How many lines of stderr are kept to report when the process dies.
+const DEFAULT_STDERR_TAIL_LINES: usize = 50;
+
+/// The size of the buffers for the process's stdin and stdout, unless set on the builder.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// How long to keep reading the process's stdout and stderr once it has exited. Anything the
+/// process spawned may still hold the pipes open.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Called with each line the process writes to stderr.
+pub type StderrHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The last lines written to stderr, oldest first.
+struct StderrTail {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl StderrTail {
+    fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    fn contents(&self) -> String {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// How the process ended, published by the actor once it has stopped.
+#[derive(Debug, Clone)]
+struct ProcessExit {
+    status: Option<ExitStatus>,
+    stderr: String,
+    /// Set if the process was stopped for breaking its sandbox's limits
+    violation: Option<String>,
+}
+
+impl ProcessExit {
+    fn error(&self) -> Error {
+        match &self.violation {
+            Some(violation) => Error::StdioProcessError(format!(
+                "{violation}. Last stderr output:\n{}",
+                self.stderr
+            )),
+            None => Error::StdioProcessExited {
+                status: self.status,
+                stderr: self.stderr.clone(),
+            },
+        }
+    }
+}
 
 /// A `StdioTransport` uses a child process's stdin/stdout as a communication channel.
 ///
 /// It uses channels for message passing and handles responses asynchronously through a background task.
 ///
-/// StdioActor needs to be given a `mpsc::Receiver<TransportMessage>` which will receive messages
+/// StdioActor needs to be given a `mpsc::Receiver<OutgoingMessage>` which will receive messages
 /// to be sent to the MCPServer. `pending_requests` is a store of message IDs for which we're waiting
-/// a response, and a corresponding channel to send the response on. There is a channel for errors
-/// to be communicated. Finally, there are handles to the child process's stdin, stdout, and stderr.
+/// a response, and a corresponding channel to send the response on. Finally, there are handles to
+/// the child process's stdin, stdout, and stderr.
+///
+/// Requests and notifications from the server go to `dispatcher`, and replies to requests are sent
+/// back through `replies`, a weak sender so the actor still stops once every handle is dropped.
+/// The dispatcher's subscriptions end with the actor, as it drops `connected`.
+///
+/// Stderr is read continuously, each line going to `stderr_handler` and the last few into a tail
+/// buffer. The actor stops the process when `close_receiver` fires (or the process stops talking
+/// to us), and publishes how it ended on `exit_sender`.
 pub struct StdioActor {
-    receiver: mpsc::Receiver<TransportMessage>,
+    receiver: mpsc::Receiver<OutgoingMessage>,
     pending_requests: Arc<PendingRequests>,
-    _process: Child, // we store the process to keep it alive
-    error_sender: mpsc::Sender<Error>,
+    process: Child,
     stdin: ChildStdin,
     stdout: ChildStdout,
     stderr: ChildStderr,
+    stderr_handler: StderrHandler,
+    stderr_tail_lines: usize,
+    stdin_buffer_size: usize,
+    stdout_buffer_size: usize,
+    dispatcher: Dispatcher,
+    connected: Connected,
+    replies: mpsc::WeakSender<OutgoingMessage>,
+    close_receiver: oneshot::Receiver<()>,
+    close_timeout: Duration,
+    exit_sender: watch::Sender<Option<ProcessExit>>,
+    #[cfg(target_os = "linux")]
+    sandbox: Option<Sandbox>,
 }
 
 impl StdioActor {
-    pub async fn run(mut self) {
+    pub async fn run(self) {
         use tokio::pin;
 
-        let incoming = Self::handle_incoming_messages(self.stdout, self.pending_requests.clone());
-        let outgoing = Self::handle_outgoing_messages(
-            self.receiver,
-            self.stdin,
-            self.pending_requests.clone(),
+        let Self {
+            receiver,
+            pending_requests,
+            mut process,
+            stdin,
+            stdout,
+            stderr,
+            stderr_handler,
+            stderr_tail_lines,
+            stdin_buffer_size,
+            stdout_buffer_size,
+            dispatcher,
+            connected,
+            replies,
+            close_receiver,
+            close_timeout,
+            exit_sender,
+            #[cfg(target_os = "linux")]
+            sandbox,
+        } = self;
+
+        // Read stderr from the start, so a chatty process can't fill the pipe and block
+        let tail = Arc::new(std::sync::Mutex::new(StderrTail::new(stderr_tail_lines)));
+        let mut stderr_task = tokio::spawn(Self::handle_stderr(
+            stderr,
+            stderr_handler,
+            Arc::clone(&tail),
+        ));
+
+        let incoming = Self::handle_incoming_messages(
+            BufReader::with_capacity(stdout_buffer_size, stdout),
+            pending_requests.clone(),
+            dispatcher,
+            replies,
         );
+        let mut stdin = BufWriter::with_capacity(stdin_buffer_size, stdin);
+        // Boxed rather than pinned on the stack, so it can be dropped to release stdin
+        let mut outgoing = Box::pin(Self::handle_outgoing_messages(
+            receiver,
+            &mut stdin,
+            pending_requests.clone(),
+        ));
 
         // take ownership of futures for tokio::select
         pin!(incoming);
-        pin!(outgoing);
 
         // Keep the process alive (the incoming and outgoing handlers). The select! will return only
-        // if one of the futures returns (due to an unrecoverable error) or the process exits.
-        tokio::select! {
+        // if one of the futures returns (due to an unrecoverable error), the process exits, or we
+        // are asked to close.
+        let (closing, incoming_done) = tokio::select! {
             result = &mut incoming => {
                 tracing::debug!("Stdin handler completed: {:?}", result);
+                (false, true)
             }
             result = &mut outgoing => {
                 tracing::debug!("Stdout handler completed: {:?}", result);
+                (false, false)
             }
             // capture the status so we don't need to wait for a timeout
-            status = self._process.wait() => {
+            status = process.wait() => {
                 tracing::debug!("Process exited with status: {:?}", status);
+                (false, false)
             }
+            Ok(()) = close_receiver => (true, false),
+        };
+
+        drop(outgoing);
+        if closing {
+            // Tell the process we're going, then fail everything waiting on it, as nothing more
+            // will be sent or answered
+            if tokio::time::timeout(
+                close_timeout,
+                Self::send_shutdown_notice(&mut stdin, &pending_requests),
+            )
+            .await
+            .is_err()
+            {
+                tracing::debug!("Process isn't reading its stdin, skipping the shutdown notice");
+            }
+            pending_requests.fail_all(|| Error::NotConnected).await;
+        } else if !incoming_done {
+            // The process may have answered just before exiting
+            let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, &mut incoming).await;
         }
 
-        // Then always try to read stderr before cleaning up
-        let mut stderr_buffer = Vec::new();
-        if let Ok(bytes) = self.stderr.read_to_end(&mut stderr_buffer).await {
-            let err_msg = if bytes > 0 {
-                String::from_utf8_lossy(&stderr_buffer).to_string()
-            } else {
-                "Process ended unexpectedly".to_string()
+        // Closing the process's stdin is the first step in stopping it
+        drop(stdin);
+        let status = match Self::terminate(&mut process, close_timeout).await {
+            Ok(status) => {
+                tracing::debug!("Process stopped with status: {}", status);
+                Some(status)
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to stop process");
+                None
+            }
+        };
+
+        // Pick up whatever the process wrote to stderr on its way out
+        if tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, &mut stderr_task)
+            .await
+            .is_err()
+        {
+            stderr_task.abort();
+        }
+
+        #[cfg(target_os = "linux")]
+        // Signals we sent while closing are no violation
+        let violation = status
+            .as_ref()
+            .filter(|_| !closing)
+            .and_then(|status| sandbox.as_ref()?.violation(status));
+        #[cfg(not(target_os = "linux"))]
+        let violation = None;
+        let exit = ProcessExit {
+            status,
+            stderr: tail.lock().unwrap().contents(),
+            violation,
+        };
+        // Exiting only matters as an error if it cut requests short
+        if !closing && !pending_requests.ids().await.is_empty() {
+            tracing::error!("{}", exit.error());
+        } else {
+            tracing::debug!("{}", exit.error());
+        }
+
+        // Publish how the process ended before failing anything waiting on it, so callers who
+        // see their request fail can find out why
+        exit_sender.send_replace(Some(exit.clone()));
+        pending_requests.fail_all(|| exit.error()).await;
+        drop(connected);
+    }
+
+    // Forward each line the process writes to stderr, keeping the last few. Lines longer than
+    // MAX_STDERR_LINE are split, so a process writing without newlines can't use up our memory.
+    async fn handle_stderr(
+        stderr: ChildStderr,
+        handler: StderrHandler,
+        tail: Arc<std::sync::Mutex<StderrTail>>,
+    ) {
+        let emit = |line: &[u8]| {
+            let line = String::from_utf8_lossy(line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            handler(line);
+            tail.lock().unwrap().push(line.to_string());
+        };
+        let mut reader = BufReader::new(stderr);
+        let mut line = Vec::new();
+        loop {
+            let available = match reader.fill_buf().await {
+                Ok([]) => break,
+                Ok(available) => available,
+                Err(e) => {
+                    tracing::error!(error = ?e, "Error reading stderr");
+                    break;
+                }
+            };
+            let room = MAX_STDERR_LINE - line.len();
+            let (used, complete) = match available.iter().take(room).position(|&b| b == b'\n') {
+                Some(newline) => (newline + 1, true),
+                None => {
+                    let used = available.len().min(room);
+                    (used, used == room)
+                }
             };
+            line.extend_from_slice(&available[..used]);
+            reader.consume(used);
+            if complete {
+                emit(&line);
+                line.clear();
+            }
+        }
+        // The last line may not end in a newline
+        if !line.is_empty() {
+            emit(&line);
+        }
+    }
 
-            tracing::info!("Process stderr: {}", err_msg);
-            let _ = self
-                .error_sender
-                .send(Error::StdioProcessError(err_msg))
-                .await;
+    /// Stop the process, whose stdin has already been closed: give it `timeout` to exit on its
+    /// own, then send SIGTERM to its process group, and after another `timeout` SIGKILL.
+    async fn terminate(process: &mut Child, timeout: Duration) -> std::io::Result<ExitStatus> {
+        if let Ok(status) = tokio::time::timeout(timeout, process.wait()).await {
+            return status;
+        }
+
+        // The process was spawned as the leader of its own process group, so signal the whole
+        // group to also stop anything it spawned
+        #[cfg(unix)]
+        if let Some(pid) = process.id() {
+            tracing::debug!(
+                pid,
+                "Process still running after closing stdin, sending SIGTERM"
+            );
+            // SAFETY: killpg has no memory safety requirements
+            unsafe { libc::killpg(pid as libc::pid_t, libc::SIGTERM) };
+            if let Ok(status) = tokio::time::timeout(timeout, process.wait()).await {
+                return status;
+            }
+            tracing::debug!(pid, "Process still running after SIGTERM, sending SIGKILL");
+            // SAFETY: as above
+            unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) };
         }
 
-        // Clean up
-        self.pending_requests.clear().await;
+        process.start_kill()?;
+        process.wait().await
     }
 
     // Receive messages from the MCP server
-    async fn handle_incoming_messages(stdout: ChildStdout, pending_requests: Arc<PendingRequests>) {
-        let mut reader = BufReader::new(stdout);
+    async fn handle_incoming_messages(
+        mut reader: BufReader<ChildStdout>,
+        pending_requests: Arc<PendingRequests>,
+        dispatcher: Dispatcher,
+        replies: mpsc::WeakSender<OutgoingMessage>,
+    ) {
         let mut line = String::new();
         loop {
             match reader.read_line(&mut line).await {
@@ -89,20 +372,8 @@ impl StdioActor {
                     break;
                 } // EOF
                 Ok(_) => {
-                    // TODO: Support notifications
-                    // We take a more opinionated approach, only supporting server responding to
-                    // requests, and not server-initiated requests (as the protocol technically allows).
-                    if let Ok(response) = serde_json::from_str::<JsonRpcResponse>(&line) {
-                        tracing::debug!(
-                            message = ?response,
-                            "Received incoming message"
-                        );
-
-                        let id = match &response {
-                            JsonRpcResponse::Success { id, .. } => id.clone(),
-                            JsonRpcResponse::Error { id, .. } => id.clone(),
-                        };
-                        pending_requests.respond(&id, Ok(response)).await;
+                    if let Some(message) = parse_message(&line) {
+                        handle_incoming(message, &pending_requests, &dispatcher, &replies).await;
                     } else {
                         // TODO: remove after testing, or move to trace level
                         tracing::error!(message = ?line, "Received invalid message");
@@ -117,41 +388,57 @@ impl StdioActor {
         }
     }
 
+    /// Cancel each request the process hasn't answered, then send `SHUTDOWN_NOTIFICATION`.
+    async fn send_shutdown_notice(
+        stdin: &mut BufWriter<ChildStdin>,
+        pending_requests: &PendingRequests,
+    ) {
+        let cancellations = pending_requests.ids().await.into_iter().map(|id| {
+            let params = CancelledParams {
+                request_id: id,
+                reason: Some("The client is closing the connection".to_string()),
+            };
+            JsonRpcNotification::new(
+                ServerNotification::CANCELLED.to_string(),
+                serde_json::to_value(params).ok(),
+            )
+        });
+        let shutdown = JsonRpcNotification::new(SHUTDOWN_NOTIFICATION.to_string(), None);
+        for notification in cancellations.chain([shutdown]) {
+            let Ok(mut line) = serde_json::to_string(&notification) else {
+                continue;
+            };
+            line.push('\n');
+            if let Err(e) = stdin.write_all(line.as_bytes()).await {
+                tracing::debug!(error = ?e, "Failed to send shutdown notice");
+                return;
+            }
+        }
+        if let Err(e) = stdin.flush().await {
+            tracing::debug!(error = ?e, "Failed to send shutdown notice");
+        }
+    }
+
     // Send messages to the MCP server
     async fn handle_outgoing_messages(
-        mut receiver: mpsc::Receiver<TransportMessage>,
-        mut stdin: ChildStdin,
+        mut receiver: mpsc::Receiver<OutgoingMessage>,
+        stdin: &mut BufWriter<ChildStdin>,
         pending_requests: Arc<PendingRequests>,
     ) {
         // Receive submitted messages on the channel and transmit them to the MCP server over the
         // child process's stdin.
-        while let Some(mut transport_msg) = receiver.recv().await {
-            let message_str = match serde_json::to_string(&transport_msg.message) {
-                Ok(s) => s,
-                Err(e) => {
-                    // If we can't serialize the message, send an error response on the response channel.
-                    if let Some(tx) = transport_msg.response_tx.take() {
-                        let _ = tx.send(Err(Error::Serialization(e)));
-                    }
-                    continue;
-                }
+        while let Some(outgoing) = receiver.recv().await {
+            let Some(message_str) = outgoing.prepare(&pending_requests).await else {
+                continue;
             };
 
-            tracing::debug!(message = ?transport_msg.message, "Sending outgoing message");
+            tracing::debug!(message = %message_str, "Sending outgoing message");
 
-            // If the message requires a response, insert it into the pending requests map.
-            if let Some(response_tx) = transport_msg.response_tx.take() {
-                if let SendableMessage::Request(request) = &transport_msg.message {
-                    pending_requests
-                        .insert(request.id.clone(), response_tx)
-                        .await;
-                }
+            if let Err(e) = stdin.write_all(message_str.as_bytes()).await {
+                tracing::error!(error = ?e, "Error writing message to child process");
+                break;
             }
-
-            if let Err(e) = stdin
-                .write_all(format!("{}\n", message_str).as_bytes())
-                .await
-            {
+            if let Err(e) = stdin.write_all(b"\n").await {
                 tracing::error!(error = ?e, "Error writing message to child process");
                 break;
             }
@@ -166,52 +453,149 @@ impl StdioActor {
 
 #[derive(Clone)]
 pub struct StdioTransportHandle {
-    sender: mpsc::Sender<TransportMessage>,
-    error_receiver: Arc<Mutex<mpsc::Receiver<Error>>>,
+    sender: mpsc::Sender<OutgoingMessage>,
+    dispatcher: Dispatcher,
+    exit_receiver: watch::Receiver<Option<ProcessExit>>,
+    closed: Arc<AtomicBool>,
 }
 
 #[async_trait::async_trait]
 impl TransportHandle for StdioTransportHandle {
     async fn send(&self, message: SendableMessage) -> Result<Option<JsonRpcResponse>, Error> {
+        self.check_connected()?;
         let result = send_message(&self.sender, message).await;
-        // Check for any pending errors even if send is successful
-        self.check_for_errors().await?;
-        result
+        self.explain_error(result).await
+    }
+
+    async fn send_batch(
+        &self,
+        messages: Vec<SendableMessage>,
+    ) -> Result<Vec<JsonRpcResponse>, Error> {
+        self.check_connected()?;
+        let result = send_batch_message(&self.sender, messages).await;
+        self.explain_error(result).await
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
     }
 }
 
 impl StdioTransportHandle {
-    /// Check if there are any process errors
+    /// Check if the process has exited, returning an error with its exit status and the end of
+    /// its stderr output if so.
     pub async fn check_for_errors(&self) -> Result<(), Error> {
-        match self.error_receiver.lock().await.try_recv() {
-            Ok(error) => {
-                tracing::debug!("Found error: {:?}", error);
+        self.check_connected()?;
+        match self.exit_receiver.borrow().as_ref() {
+            Some(exit) => Err(exit.error()),
+            None => Ok(()),
+        }
+    }
+
+    fn check_connected(&self) -> Result<(), Error> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::NotConnected);
+        }
+        Ok(())
+    }
+
+    /// A closed channel means the actor stopped, which it only does once the process is gone.
+    /// Wait for it to say how the process ended, and return that instead.
+    async fn explain_error<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        match result {
+            Err(Error::ChannelClosed) => {
+                self.check_connected()?;
+                let mut exit_receiver = self.exit_receiver.clone();
+                let error = match exit_receiver.wait_for(Option::is_some).await {
+                    Ok(exit) => exit
+                        .as_ref()
+                        .map_or(Error::ChannelClosed, ProcessExit::error),
+                    Err(_) => Error::ChannelClosed,
+                };
                 Err(error)
             }
-            Err(_) => Ok(()),
+            result => result,
         }
     }
 }
 
+/// The parts of a started transport `close` needs to stop it.
+struct StdioConnection {
+    close_sender: Option<oneshot::Sender<()>>,
+    exit_receiver: watch::Receiver<Option<ProcessExit>>,
+    closed: Arc<AtomicBool>,
+}
+
 pub struct StdioTransport {
     command: String,
     args: Vec<String>,
     env: HashMap<String, String>,
+    /// The names of the variables the process inherits, or `None` to inherit all of them
+    inherited_env: Option<Vec<String>>,
+    current_dir: Option<PathBuf>,
+    stdin_buffer_size: usize,
+    stdout_buffer_size: usize,
+    #[cfg(target_os = "linux")]
+    sandbox: Option<Sandbox>,
+    close_timeout: Duration,
+    stderr_handler: StderrHandler,
+    stderr_tail_lines: usize,
+    dispatcher: Dispatcher,
+    connection: Mutex<Option<StdioConnection>>,
 }
 
 impl StdioTransport {
-    /// Create a new `StdioTransport`. The command and args are passed directly to `Command::new`,
-    /// and used to spawn a new process which runs an MCP server.
+    /// Create a new `StdioTransport`, which runs `command` with `args` as an MCP server. The
+    /// process inherits our environment, with `env` added, and our working directory. Use
+    /// [`StdioTransport::builder`] to control either.
     pub fn new<S: Into<String>>(
         command: S,
         args: Vec<String>,
         env: HashMap<String, String>,
     ) -> Self {
-        Self {
-            command: command.into(),
-            args,
-            env,
-        }
+        Self::builder(command)
+            .with_args(args)
+            .with_envs(env)
+            .build()
+    }
+
+    pub fn builder<S: Into<String>>(command: S) -> StdioTransportBuilder {
+        StdioTransportBuilder::new(command)
+    }
+
+    /// The dispatcher for requests and notifications from the server. Register handlers on it to
+    /// answer the server's requests.
+    pub fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
+    /// Stop the process and wait for it to exit, returning its exit status.
+    ///
+    /// Shutting down happens in stages: the process is sent a `notifications/cancelled` for each
+    /// request it hasn't answered and a final [`SHUTDOWN_NOTIFICATION`], pending requests are
+    /// failed, the process's stdin is closed and it is given the close timeout to exit. If it
+    /// doesn't, SIGTERM is sent to its process group, and if it still hasn't exited after another
+    /// timeout, SIGKILL. Handles to the transport fail with `Error::NotConnected` from the start.
+    ///
+    /// Closing again returns the same exit status. If the process already exited on its own, its
+    /// status is returned straight away.
+    pub async fn close_with_status(&self) -> Result<ExitStatus, Error> {
+        let mut exit_receiver = {
+            let mut connection = self.connection.lock().await;
+            let connection = connection.as_mut().ok_or(Error::NotConnected)?;
+            connection.closed.store(true, Ordering::SeqCst);
+            if let Some(close_sender) = connection.close_sender.take() {
+                // Fails only if the actor already stopped, in which case it has published the
+                // exit status
+                let _ = close_sender.send(());
+            }
+            connection.exit_receiver.clone()
+        };
+
+        let exit = exit_receiver.wait_for(Option::is_some).await;
+        exit.ok()
+            .and_then(|exit| exit.as_ref().and_then(|exit| exit.status))
+            .ok_or_else(|| Error::StdioProcessError("Failed to stop process".into()))
     }
 
     /// Spawn the MCP server as a new process. This method returns handles to communciate with the
@@ -224,7 +608,33 @@ impl StdioTransport {
     ///
     /// [stdio in the spec]: https://spec.modelcontextprotocol.io/specification/2024-11-05/basic/transports/#stdio
     async fn spawn_process(&self) -> Result<(Child, ChildStdin, ChildStdout, ChildStderr), Error> {
-        let mut command = Command::new(&self.command);
+        // Look the command up on the PATH the process gets, falling back to ours as
+        // `Command::new` does
+        let path = self
+            .env
+            .get("PATH")
+            .map(OsString::from)
+            .or_else(|| std::env::var_os("PATH"));
+        let program = resolve_command(&self.command, path, self.current_dir.as_deref())?;
+
+        let mut command = Command::new(&program);
+        if let Some(inherited) = &self.inherited_env {
+            command.env_clear();
+            for name in inherited {
+                if let Some(value) = std::env::var_os(name) {
+                    command.env(name, value);
+                }
+            }
+        }
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(sandbox) = &self.sandbox {
+            let sandbox = sandbox.prepare()?;
+            // SAFETY: applying the sandbox only makes async-signal-safe system calls
+            unsafe { command.pre_exec(move || sandbox.apply()) };
+        }
         command
             .envs(&self.env)
             .args(&self.args)
@@ -241,9 +651,17 @@ impl StdioTransport {
         #[cfg(windows)]
         command.creation_flags(0x08000000); // CREATE_NO_WINDOW flag
 
-        let mut process = command
-            .spawn()
-            .map_err(|e| Error::StdioProcessError(e.to_string()))?;
+        let mut process = command.spawn().map_err(|e| {
+            let mut message = format!("Failed to run {}", program.display());
+            if let Some(dir) = &self.current_dir {
+                message.push_str(&format!(" in {}", dir.display()));
+            }
+            #[cfg(target_os = "linux")]
+            if self.sandbox.is_some() {
+                message.push_str(" sandboxed");
+            }
+            Error::StdioProcessError(format!("{message}: {e}"))
+        })?;
 
         let stdin = process
             .stdin
@@ -273,28 +691,590 @@ impl Transport for StdioTransport {
     async fn start(&self) -> Result<Self::Handle, Error> {
         let (process, stdin, stdout, stderr) = self.spawn_process().await?;
         let (message_tx, message_rx) = mpsc::channel(32);
-        let (error_tx, error_rx) = mpsc::channel(1);
+        let (close_tx, close_rx) = oneshot::channel();
+        let (exit_tx, exit_rx) = watch::channel(None);
+        let closed = Arc::new(AtomicBool::new(false));
 
         let actor = StdioActor {
             receiver: message_rx,
             pending_requests: Arc::new(PendingRequests::new()),
-            _process: process,
-            error_sender: error_tx,
+            process,
             stdin,
             stdout,
             stderr,
+            stderr_handler: Arc::clone(&self.stderr_handler),
+            stderr_tail_lines: self.stderr_tail_lines,
+            stdin_buffer_size: self.stdin_buffer_size,
+            stdout_buffer_size: self.stdout_buffer_size,
+            dispatcher: self.dispatcher.clone(),
+            connected: self.dispatcher.connect(),
+            replies: message_tx.downgrade(),
+            close_receiver: close_rx,
+            close_timeout: self.close_timeout,
+            exit_sender: exit_tx,
+            #[cfg(target_os = "linux")]
+            sandbox: self.sandbox.clone(),
         };
 
         tokio::spawn(actor.run());
 
+        *self.connection.lock().await = Some(StdioConnection {
+            close_sender: Some(close_tx),
+            exit_receiver: exit_rx.clone(),
+            closed: closed.clone(),
+        });
+
         let handle = StdioTransportHandle {
             sender: message_tx,
-            error_receiver: Arc::new(Mutex::new(error_rx)),
+            dispatcher: self.dispatcher.clone(),
+            exit_receiver: exit_rx,
+            closed,
         };
         Ok(handle)
     }
 
+    /// Stop the process. See [`StdioTransport::close_with_status`].
     async fn close(&self) -> Result<(), Error> {
-        Ok(())
+        match self.close_with_status().await {
+            Ok(status) => {
+                tracing::debug!("MCP server process exited with status: {}", status);
+                Ok(())
+            }
+            // Never started, so there's nothing to close
+            Err(Error::NotConnected) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Builds a [`StdioTransport`], controlling what the process inherits from us.
+///
+/// By default the process inherits our whole environment, which may include secrets. For a
+/// third-party server, clear it and pass on only what the server needs:
+///
+/// ```no_run
+/// # use mcp_client::transport::StdioTransport;
+/// let transport = StdioTransport::builder("uvx")
+///     .with_args(["mcp-server-git"])
+///     .with_env_clear()
+///     .with_inherited_env(["PATH", "HOME"])
+///     .with_env("GIT_AUTHOR_NAME", "mcp")
+///     .with_current_dir("/srv/repos")
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct StdioTransportBuilder {
+    command: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    inherited_env: Option<Vec<String>>,
+    current_dir: Option<PathBuf>,
+    stdin_buffer_size: usize,
+    stdout_buffer_size: usize,
+    #[cfg(target_os = "linux")]
+    sandbox: Option<Sandbox>,
+    close_timeout: Duration,
+    stderr_handler: StderrHandler,
+    stderr_tail_lines: usize,
+}
+
+impl StdioTransportBuilder {
+    pub fn new<S: Into<String>>(command: S) -> Self {
+        Self {
+            command: command.into(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            inherited_env: None,
+            current_dir: None,
+            stdin_buffer_size: DEFAULT_BUFFER_SIZE,
+            stdout_buffer_size: DEFAULT_BUFFER_SIZE,
+            #[cfg(target_os = "linux")]
+            sandbox: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
+            stderr_handler: Arc::new(|line| tracing::info!("MCP server stderr: {}", line)),
+            stderr_tail_lines: DEFAULT_STDERR_TAIL_LINES,
+        }
+    }
+
+    pub fn with_arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set a variable for the process, on top of what it inherits.
+    pub fn with_env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_envs<I, K, V>(mut self, env: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env.extend(
+            env.into_iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        self
+    }
+
+    /// Inherit none of our environment, only the variables set with `with_env` and those named
+    /// with `with_inherited_env`.
+    pub fn with_env_clear(mut self) -> Self {
+        self.inherited_env = Some(Vec::new());
+        self
+    }
+
+    /// Inherit only the named variables from our environment, and any named before. Those we
+    /// don't have are left unset.
+    pub fn with_inherited_env<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.inherited_env
+            .get_or_insert_with(Vec::new)
+            .extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Run the process in `dir`. A command given as a relative path is found from there too.
+    pub fn with_current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// The size of the buffer messages are written to the process's stdin through. Each message
+    /// is flushed once written. Defaults to [`DEFAULT_BUFFER_SIZE`].
+    pub fn with_stdin_buffer_size(mut self, size: usize) -> Self {
+        self.stdin_buffer_size = size;
+        self
+    }
+
+    /// The size of the buffer the process's stdout is read through. Defaults to
+    /// [`DEFAULT_BUFFER_SIZE`].
+    pub fn with_stdout_buffer_size(mut self, size: usize) -> Self {
+        self.stdout_buffer_size = size;
+        self
+    }
+
+    /// Apply `sandbox` to the process before it runs the command.
+    #[cfg(target_os = "linux")]
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Set how long `close` waits for the process at each stage: after closing its stdin, and
+    /// after sending SIGTERM.
+    pub fn with_close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    /// Call `handler` with each line the process writes to stderr, instead of logging it.
+    pub fn with_stderr_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.stderr_handler = Arc::new(handler);
+        self
+    }
+
+    /// Set how many of the last lines of stderr are included in the error when the process dies.
+    pub fn with_stderr_tail_lines(mut self, lines: usize) -> Self {
+        self.stderr_tail_lines = lines;
+        self
+    }
+
+    pub fn build(self) -> StdioTransport {
+        StdioTransport {
+            command: self.command,
+            args: self.args,
+            env: self.env,
+            inherited_env: self.inherited_env,
+            current_dir: self.current_dir,
+            stdin_buffer_size: self.stdin_buffer_size,
+            stdout_buffer_size: self.stdout_buffer_size,
+            #[cfg(target_os = "linux")]
+            sandbox: self.sandbox,
+            close_timeout: self.close_timeout,
+            stderr_handler: self.stderr_handler,
+            stderr_tail_lines: self.stderr_tail_lines,
+            dispatcher: Dispatcher::new(),
+            connection: Mutex::new(None),
+        }
+    }
+}
+
+/// Find the executable `command` names. A command with a path separator is taken relative to
+/// `current_dir`, and anything else is looked for in the directories on `path`.
+fn resolve_command(
+    command: &str,
+    path: Option<OsString>,
+    current_dir: Option<&Path>,
+) -> Result<PathBuf, Error> {
+    let not_found = |searched: String| Error::CommandNotFound {
+        command: command.to_string(),
+        searched,
+    };
+
+    if command.contains(std::path::MAIN_SEPARATOR) || command.contains('/') {
+        let program = match current_dir {
+            Some(dir) => dir.join(command),
+            None => PathBuf::from(command),
+        };
+        return executable(&program).ok_or_else(|| not_found(program.display().to_string()));
+    }
+
+    let path = path.unwrap_or_default();
+    std::env::split_paths(&path)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find_map(|dir| executable(&dir.join(command)))
+        .ok_or_else(|| not_found(format!("PATH={}", path.to_string_lossy())))
+}
+
+/// `program`, or on Windows `program` with one of the `PATHEXT` extensions, if it is an
+/// executable file.
+///
+/// On Windows a file is only run if its extension is one of `PATHEXT`, as that's what
+/// `CreateProcess` can start. npm, for one, installs an extensionless `npx` shell script next to
+/// `npx.cmd`, which must not win.
+fn executable(program: &Path) -> Option<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(program).ok()?;
+        (metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .then(|| program.to_path_buf())
+    }
+
+    #[cfg(windows)]
+    {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+        pathext_candidates(program, &extensions)
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+/// The files `program` may be on Windows, in order: itself if it already has one of the
+/// `extensions`, and otherwise `program` with each of them appended.
+#[cfg(any(windows, test))]
+fn pathext_candidates(program: &Path, extensions: &str) -> Vec<PathBuf> {
+    let extensions = extensions
+        .split(';')
+        .filter(|extension| !extension.is_empty());
+    let has_extension = program.extension().is_some_and(|own| {
+        extensions.clone().any(|extension| {
+            extension
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(&own.to_string_lossy())
+        })
+    });
+    if has_extension {
+        return vec![program.to_path_buf()];
+    }
+    extensions
+        .map(|extension| {
+            let mut candidate = program.as_os_str().to_owned();
+            candidate.push(extension);
+            PathBuf::from(candidate)
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::handler::RequestHandler;
+    use mcp_core::protocol::{ErrorData, JsonRpcRequest, MessageId, METHOD_NOT_FOUND};
+    use serde_json::{json, Value};
+    use std::os::unix::process::ExitStatusExt;
+
+    fn ping() -> SendableMessage {
+        SendableMessage::Request(JsonRpcRequest::new(
+            MessageId::Num(1),
+            "ping".to_string(),
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_pathext_candidates() {
+        let candidates = |program: &str| pathext_candidates(Path::new(program), ".COM;.EXE;.CMD;");
+        // The extensionless shim npm puts next to `npx.cmd` is never a candidate
+        assert_eq!(
+            candidates(r"C:\nodejs\npx"),
+            [
+                r"C:\nodejs\npx.COM",
+                r"C:\nodejs\npx.EXE",
+                r"C:\nodejs\npx.CMD"
+            ]
+            .map(PathBuf::from)
+        );
+        assert_eq!(candidates("npx.cmd"), [PathBuf::from("npx.cmd")]);
+        assert_eq!(
+            candidates("python3.11"),
+            ["python3.11.COM", "python3.11.EXE", "python3.11.CMD"].map(PathBuf::from)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_process_exiting_on_stdin_close() {
+        let transport = StdioTransport::new("cat", vec![], HashMap::new());
+        let handle = transport.start().await.unwrap();
+
+        let status = transport.close_with_status().await.unwrap();
+        assert!(status.success());
+        assert!(matches!(
+            handle.send(ping()).await,
+            Err(Error::NotConnected)
+        ));
+
+        // Closing again reports the same status
+        assert_eq!(transport.close_with_status().await.unwrap(), status);
+    }
+
+    #[tokio::test]
+    async fn test_close_escalates_to_sigkill() {
+        // Ignores SIGTERM and never reads stdin
+        let script = "trap '' TERM; while true; do sleep 0.05; done";
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", script])
+            .with_close_timeout(Duration::from_millis(200))
+            .build();
+        let handle = transport.start().await.unwrap();
+
+        // A request the process will never answer fails once we close
+        let pending = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send(ping()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let status = transport.close_with_status().await.unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        assert!(matches!(pending.await.unwrap(), Err(Error::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn test_close_sends_shutdown_notice() {
+        // Echoes what it receives to stderr, and exits once stdin is closed
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", "cat >&2"])
+            .with_stderr_handler({
+                let received = Arc::clone(&received);
+                move |line| received.lock().unwrap().push(line.to_string())
+            })
+            .build();
+        let handle = transport.start().await.unwrap();
+        let pending = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send(ping()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(transport.close_with_status().await.unwrap().success());
+        assert!(matches!(pending.await.unwrap(), Err(Error::NotConnected)));
+        let received: Vec<Value> = received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            received[1..],
+            [
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/cancelled",
+                    "params": {"requestId": 1, "reason": "The client is closing the connection"},
+                }),
+                json!({"jsonrpc": "2.0", "method": SHUTDOWN_NOTIFICATION}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_crash_fails_pending_requests_with_stderr_tail() {
+        let script = "read line; echo starting >&2; echo boom >&2; exit 3";
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", script])
+            .with_stderr_tail_lines(1)
+            .build();
+        let handle = transport.start().await.unwrap();
+
+        let result = handle.send(ping()).await;
+        let Err(Error::StdioProcessExited { status, stderr }) = result else {
+            panic!("Expected the process to have exited, got {result:?}");
+        };
+        assert_eq!(status.unwrap().code(), Some(3));
+        assert_eq!(stderr, "boom");
+
+        // Later calls see the same error, without racing the actor
+        assert!(matches!(
+            handle.check_for_errors().await,
+            Err(Error::StdioProcessExited { .. })
+        ));
+        assert!(matches!(
+            handle.send(ping()).await,
+            Err(Error::StdioProcessExited { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stderr_is_read_while_running() {
+        // Writes more to stderr than fits in a pipe before answering
+        let script = r#"i=0; while [ $i -lt 2000 ]; do echo "log line $i with some padding to fill the pipe" >&2; i=$((i+1)); done
+read line; echo '{"jsonrpc":"2.0","id":1,"result":{}}'"#;
+        let lines = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", script])
+            .with_stderr_handler({
+                let lines = Arc::clone(&lines);
+                move |_| {
+                    lines.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build();
+        let handle = transport.start().await.unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(10), handle.send(ping()))
+            .await
+            .expect("process blocked on stderr")
+            .unwrap();
+        assert!(matches!(response, Some(JsonRpcResponse::Success { .. })));
+        // The last lines may still be in the pipe when the response arrives
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while lines.load(Ordering::SeqCst) < 2000 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("not all stderr lines were handled");
+    }
+
+    #[tokio::test]
+    async fn test_long_stderr_lines_are_split() {
+        let script = "head -c 100000 /dev/zero | tr '\\0' x >&2; echo >&2; echo end >&2";
+        let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", script])
+            .with_stderr_handler({
+                let lines = Arc::clone(&lines);
+                move |line| lines.lock().unwrap().push(line.len())
+            })
+            .build();
+        transport.start().await.unwrap();
+        transport.close_with_status().await.unwrap();
+
+        let lines = lines.lock().unwrap();
+        assert!(lines.iter().all(|&len| len <= MAX_STDERR_LINE), "{lines:?}");
+        assert_eq!(lines[..lines.len() - 1].iter().sum::<usize>(), 100000);
+        assert_eq!(lines.last(), Some(&3));
+    }
+
+    #[tokio::test]
+    async fn test_environment_and_current_dir() {
+        let script = r#"read line
+printf '{"jsonrpc":"2.0","id":1,"result":{"home":"%s","path":"%s","extra":"%s","dir":"%s"}}\n' "$HOME" "$PATH" "$EXTRA" "$(pwd -P)""#;
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let transport = StdioTransport::builder("sh")
+            .with_args(["-c", script])
+            .with_env_clear()
+            .with_inherited_env(["PATH", "MCP_STDIO_TEST_UNSET"])
+            .with_env("EXTRA", "extra")
+            .with_current_dir(&dir)
+            .with_stdout_buffer_size(16)
+            .build();
+        let handle = transport.start().await.unwrap();
+
+        let Some(JsonRpcResponse::Success { result, .. }) = handle.send(ping()).await.unwrap()
+        else {
+            panic!("Expected a successful response");
+        };
+        assert_eq!(result["home"], "");
+        assert_eq!(result["path"], std::env::var("PATH").unwrap());
+        assert_eq!(result["extra"], "extra");
+        assert_eq!(result["dir"], dir.to_str().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_missing_command() {
+        let transport =
+            StdioTransport::new("mcp-stdio-test-no-such-command", vec![], HashMap::new());
+        let error = transport.start().await.err().unwrap();
+        assert!(
+            matches!(&error, Error::CommandNotFound { command, searched }
+                if command == "mcp-stdio-test-no-such-command" && searched.starts_with("PATH=")),
+            "{error}"
+        );
+
+        // Relative paths are found from the working directory
+        let transport = StdioTransport::builder("./sh")
+            .with_current_dir("/bin")
+            .build();
+        assert!(transport.start().await.is_ok());
+        transport.close().await.unwrap();
+        let transport = StdioTransport::builder("./sh")
+            .with_current_dir("/nonexistent")
+            .build();
+        assert!(matches!(
+            transport.start().await,
+            Err(Error::CommandNotFound { searched, .. }) if searched == "/nonexistent/./sh"
+        ));
+    }
+
+    struct Roots;
+
+    #[async_trait]
+    impl RequestHandler for Roots {
+        async fn handle(&self, _params: Option<Value>) -> Result<Value, ErrorData> {
+            Ok(json!({ "roots": [] }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_requests_and_notifications() {
+        // Sends a notification and two requests, then answers our request with everything it read
+        let script = r#"echo '{"jsonrpc":"2.0","method":"notifications/message","params":{"data":"hi"}}'
+echo '{"jsonrpc":"2.0","id":"roots","method":"roots/list"}'
+echo '{"jsonrpc":"2.0","id":"other","method":"unknown/method"}'
+read a; read b; read c
+printf '{"jsonrpc":"2.0","id":1,"result":[%s,%s,%s]}\n' "$a" "$b" "$c""#;
+        let transport = StdioTransport::new("sh", vec!["-c".into(), script.into()], HashMap::new());
+        transport.dispatcher().register("roots/list", Roots);
+        let mut notifications = transport.dispatcher().subscribe();
+        let handle = transport.start().await.unwrap();
+
+        let notification = notifications.recv().await.unwrap();
+        assert_eq!(notification.method(), "notifications/message");
+
+        let Some(JsonRpcResponse::Success { result, .. }) = handle.send(ping()).await.unwrap()
+        else {
+            panic!("Expected a successful response");
+        };
+        let received = result.as_array().unwrap();
+        let reply = |id: &str| {
+            received
+                .iter()
+                .find(|message| message["id"] == id)
+                .unwrap_or_else(|| panic!("No reply to {id}"))
+        };
+        assert_eq!(reply("roots")["result"], json!({ "roots": [] }));
+        assert_eq!(reply("other")["error"]["code"], METHOD_NOT_FOUND);
     }
 }
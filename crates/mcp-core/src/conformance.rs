@@ -0,0 +1,158 @@
+//! Round-trips example messages in the shapes the MCP schema gives, from `fixtures/`, through the
+//! types in this crate. A fixture that doesn't come back out the same means a type's serde
+//! attributes have drifted from the spec.
+//!
+//! Spec revision: the fixtures follow the message definitions of the 2024-11-05 revision
+//! (https://spec.modelcontextprotocol.io/specification/2024-11-05/), plus the 2025-06-18 revision
+//! for elicitation. Each is one of that revision's examples, or built field by field from its
+//! `schema.json` definition where the revision gives no example. A field the schema has but no
+//! fixture uses goes unchecked. When a new revision is adopted, update the fixtures from its
+//! `schema.json` and this note with it. Requests are checked both as a `JsonRpcMessage` and by
+//! their `params`.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::protocol::{
+    CallToolResult, CancelledParams, CreateElicitationRequestParams, CreateElicitationResult,
+    ErrorData, GetPromptResult, InitializeRequestParams, InitializeResult, JsonRpcMessage,
+    ListPromptsResult, ListResourcesResult, ListToolsResult, LoggingMessageParams,
+    PaginatedRequestParams, ProgressParams, ReadResourceResult, ResourceUpdatedParams,
+    SubscribeRequestParams,
+};
+use crate::tool::ToolCall;
+
+/// Round-trips a fixture's `result`, `params` or `error` through the type it should parse as.
+type Check = fn(&Value) -> Result<Value, serde_json::Error>;
+
+struct Fixture {
+    name: &'static str,
+    contents: &'static str,
+    check: Option<(&'static str, Check)>,
+}
+
+macro_rules! fixture {
+    ($name:literal) => {
+        Fixture {
+            name: $name,
+            contents: include_str!(concat!("../fixtures/", $name, ".json")),
+            check: None,
+        }
+    };
+    ($name:literal, $member:literal: $ty:ty) => {
+        Fixture {
+            name: $name,
+            contents: include_str!(concat!("../fixtures/", $name, ".json")),
+            check: Some(($member, round_trip::<$ty> as Check)),
+        }
+    };
+}
+
+const FIXTURES: &[Fixture] = &[
+    fixture!("initialize_request", "params": InitializeRequestParams),
+    fixture!("list_tools_request", "params": PaginatedRequestParams),
+    fixture!("list_resources_request", "params": PaginatedRequestParams),
+    fixture!("list_prompts_request", "params": PaginatedRequestParams),
+    fixture!("resources_subscribe_request", "params": SubscribeRequestParams),
+    fixture!("initialize_result", "result": InitializeResult),
+    fixture!("list_tools_result", "result": ListToolsResult),
+    fixture!("call_tool_result", "result": CallToolResult),
+    fixture!("call_tool_error_result", "result": CallToolResult),
+    fixture!("list_resources_result", "result": ListResourcesResult),
+    fixture!("read_resource_result", "result": ReadResourceResult),
+    fixture!("list_prompts_result", "result": ListPromptsResult),
+    fixture!("get_prompt_result", "result": GetPromptResult),
+    fixture!("error_response", "error": ErrorData),
+    fixture!("tools_call_request", "params": ToolCall),
+    fixture!("elicitation_request", "params": CreateElicitationRequestParams),
+    fixture!("elicitation_result", "result": CreateElicitationResult),
+    fixture!("progress_notification", "params": ProgressParams),
+    fixture!("logging_notification", "params": LoggingMessageParams),
+    fixture!("resource_updated_notification", "params": ResourceUpdatedParams),
+    fixture!("cancelled_notification", "params": CancelledParams),
+    fixture!("tools_list_changed_notification"),
+    fixture!("batch"),
+];
+
+/// Parse `value` as `T` and write it back out, as it would go over the wire.
+fn round_trip<T: Serialize + DeserializeOwned>(value: &Value) -> Result<Value, serde_json::Error> {
+    let typed: T = serde_json::from_value(value.clone())?;
+    serde_json::from_str(&serde_json::to_string(&typed)?)
+}
+
+/// Whether `a` and `b` are the same JSON. Numbers are compared by value, as `50` and `50.0` are
+/// the same number to JSON even though they aren't the same `Value`.
+fn same_json(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_json(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same_json(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+#[test]
+fn test_fixtures_round_trip() {
+    let mut failures = Vec::new();
+    for Fixture {
+        name,
+        contents,
+        check,
+    } in FIXTURES
+    {
+        let fixture: Value = serde_json::from_str(contents).unwrap();
+
+        let message = round_trip::<JsonRpcMessage>(&fixture);
+        match message {
+            Ok(message) if same_json(&message, &fixture) => {}
+            Ok(message) => failures.push(format!("{name}: message came back as {message}")),
+            Err(e) => failures.push(format!("{name}: message didn't parse: {e}")),
+        }
+
+        if let Some((member, check)) = check {
+            let expected = &fixture[member];
+            match check(expected) {
+                Ok(typed) if same_json(&typed, expected) => {}
+                Ok(typed) => failures.push(format!("{name}: {member} came back as {typed}")),
+                Err(e) => failures.push(format!("{name}: {member} didn't parse: {e}")),
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_fixtures_parse_as_the_expected_message_kind() {
+    let kind = |name: &str| {
+        let fixture = FIXTURES.iter().find(|f| f.name == name).unwrap();
+        serde_json::from_str::<JsonRpcMessage>(fixture.contents).unwrap()
+    };
+    for request in [
+        "initialize_request",
+        "list_tools_request",
+        "list_resources_request",
+        "list_prompts_request",
+        "resources_subscribe_request",
+        "tools_call_request",
+    ] {
+        assert!(
+            matches!(kind(request), JsonRpcMessage::Request(_)),
+            "{request}"
+        );
+    }
+    assert!(matches!(
+        kind("error_response"),
+        JsonRpcMessage::Response(_)
+    ));
+    assert!(matches!(
+        kind("progress_notification"),
+        JsonRpcMessage::Notification(_)
+    ));
+    assert!(matches!(kind("batch"), JsonRpcMessage::Batch(batch) if batch.len() == 3));
+}
@@ -102,7 +102,7 @@ impl Content {
         Content::Resource(EmbeddedResource {
             resource: ResourceContents::TextResourceContents {
                 uri: uri.into(),
-                mime_type: Some("text".to_string()),
+                mime_type: Some("text/plain".to_string()),
                 text: content.into(),
             },
             annotations: None,
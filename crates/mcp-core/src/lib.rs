@@ -10,4 +10,8 @@ pub use resource::{Resource, ResourceContents};
 pub mod protocol;
 pub use handler::{ToolError, ToolResult};
 pub mod prompt;
+pub mod schema;
 pub mod transport;
+
+#[cfg(test)]
+mod conformance;
@@ -88,6 +88,39 @@ impl JsonRpcResponse {
     }
 }
 
+/// Any JSON-RPC message, as read off the wire.
+///
+/// Variants are tried in order: a request carries both `id` and `method`, a response carries `id`
+/// and either `result` or `error`, a notification carries only `method`, and a batch is a JSON
+/// array of any of these.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum JsonRpcMessage {
+    Request(JsonRpcRequest),
+    Response(JsonRpcResponse),
+    Notification(JsonRpcNotification),
+    /// Several messages sent together. Batches may not be nested.
+    Batch(Vec<JsonRpcMessage>),
+}
+
+impl From<JsonRpcRequest> for JsonRpcMessage {
+    fn from(request: JsonRpcRequest) -> Self {
+        JsonRpcMessage::Request(request)
+    }
+}
+
+impl From<JsonRpcResponse> for JsonRpcMessage {
+    fn from(response: JsonRpcResponse) -> Self {
+        JsonRpcMessage::Response(response)
+    }
+}
+
+impl From<JsonRpcNotification> for JsonRpcMessage {
+    fn from(notification: JsonRpcNotification) -> Self {
+        JsonRpcMessage::Notification(notification)
+    }
+}
+
 // Standard JSON-RPC error codes
 pub const PARSE_ERROR: i32 = -32700;
 pub const INVALID_REQUEST: i32 = -32600;
@@ -110,6 +143,14 @@ pub struct ErrorData {
     pub data: Option<Value>,
 }
 
+/// The newest protocol revision this crate implements. Servers answer with it when the client
+/// asks for a revision they don't support.
+pub const LATEST_PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// The protocol revisions servers can agree to, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] =
+    &[LATEST_PROTOCOL_VERSION, "2025-03-26", "2024-11-05"];
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct InitializeResult {
@@ -140,22 +181,105 @@ pub struct ServerCapabilities {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PromptsCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub list_changed: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ResourcesCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub subscribe: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub list_changed: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolsCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_changed: Option<bool>,
+}
+
+/// Present if the client supports servers eliciting structured input from the user.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ElicitationCapability {}
+
+/// What the client supports, sent in its `initialize` request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ClientCapabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roots: Option<RootsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingCapability>,
+    /// Set if the client can handle `elicitation/create` requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elicitation: Option<ElicitationCapability>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RootsCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub list_changed: Option<bool>,
 }
 
+/// Present if the client supports servers sampling its model.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SamplingCapability {}
+
+/// Parameters of the client's `initialize` request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeRequestParams {
+    pub protocol_version: String,
+    pub capabilities: ClientCapabilities,
+    pub client_info: Implementation,
+}
+
+/// Parameters of the `*/list` requests, which page through their results.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct PaginatedRequestParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// Parameters of `resources/subscribe` and `resources/unsubscribe`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SubscribeRequestParams {
+    pub uri: String,
+}
+
+/// Parameters of an `elicitation/create` request, sent by the server to ask the user for input.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateElicitationRequestParams {
+    /// The message to present to the user
+    pub message: String,
+    /// A restricted JSON Schema (flat object of primitive properties) describing the requested data
+    pub requested_schema: Value,
+}
+
+/// How the user responded to an elicitation request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitationAction {
+    /// The user submitted the form; `content` holds the data
+    Accept,
+    /// The user explicitly declined to provide the data
+    Decline,
+    /// The user dismissed the request without making a choice
+    Cancel,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CreateElicitationResult {
+    pub action: ElicitationAction,
+    /// The submitted data, present when `action` is `accept`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ListResourcesResult {
@@ -186,8 +310,11 @@ pub struct CallToolResult {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct ListPromptsResult {
     pub prompts: Vec<Prompt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -199,3 +326,121 @@ pub struct GetPromptResult {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EmptyResult {}
+
+/// Severity of a log message, following syslog (RFC 5424).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LoggingLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+/// Parameters of a `notifications/message` notification, a log message from the server.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LoggingMessageParams {
+    pub level: LoggingLevel,
+    /// The name of the logger issuing the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    /// The data to be logged, such as a string message or an object
+    pub data: Value,
+}
+
+/// Parameters of a `notifications/progress` notification, reporting progress of a long-running
+/// request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    /// The token given in the original request's `_meta.progressToken`
+    pub progress_token: MessageId,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Parameters of a `notifications/resources/updated` notification.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResourceUpdatedParams {
+    /// The URI of the resource that changed
+    pub uri: String,
+}
+
+/// Parameters of a `notifications/cancelled` notification.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelledParams {
+    /// The ID of the request being cancelled
+    pub request_id: MessageId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A notification sent by the server, parsed according to its method.
+///
+/// Notifications with a method not listed here, or whose params don't match the expected shape,
+/// are kept as they were received in `Other`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerNotification {
+    ToolListChanged,
+    PromptListChanged,
+    ResourceListChanged,
+    ResourceUpdated(ResourceUpdatedParams),
+    Progress(ProgressParams),
+    LoggingMessage(LoggingMessageParams),
+    Cancelled(CancelledParams),
+    Other(JsonRpcNotification),
+}
+
+impl ServerNotification {
+    pub const TOOL_LIST_CHANGED: &'static str = "notifications/tools/list_changed";
+    pub const PROMPT_LIST_CHANGED: &'static str = "notifications/prompts/list_changed";
+    pub const RESOURCE_LIST_CHANGED: &'static str = "notifications/resources/list_changed";
+    pub const RESOURCE_UPDATED: &'static str = "notifications/resources/updated";
+    pub const PROGRESS: &'static str = "notifications/progress";
+    pub const LOGGING_MESSAGE: &'static str = "notifications/message";
+    pub const CANCELLED: &'static str = "notifications/cancelled";
+
+    /// The method of the notification.
+    pub fn method(&self) -> &str {
+        match self {
+            ServerNotification::ToolListChanged => Self::TOOL_LIST_CHANGED,
+            ServerNotification::PromptListChanged => Self::PROMPT_LIST_CHANGED,
+            ServerNotification::ResourceListChanged => Self::RESOURCE_LIST_CHANGED,
+            ServerNotification::ResourceUpdated(_) => Self::RESOURCE_UPDATED,
+            ServerNotification::Progress(_) => Self::PROGRESS,
+            ServerNotification::LoggingMessage(_) => Self::LOGGING_MESSAGE,
+            ServerNotification::Cancelled(_) => Self::CANCELLED,
+            ServerNotification::Other(notification) => &notification.method,
+        }
+    }
+}
+
+impl From<JsonRpcNotification> for ServerNotification {
+    fn from(notification: JsonRpcNotification) -> Self {
+        fn params<T: serde::de::DeserializeOwned>(notification: &JsonRpcNotification) -> Option<T> {
+            serde_json::from_value(notification.params.clone().unwrap_or_default()).ok()
+        }
+
+        let parsed = match notification.method.as_str() {
+            Self::TOOL_LIST_CHANGED => Some(ServerNotification::ToolListChanged),
+            Self::PROMPT_LIST_CHANGED => Some(ServerNotification::PromptListChanged),
+            Self::RESOURCE_LIST_CHANGED => Some(ServerNotification::ResourceListChanged),
+            Self::RESOURCE_UPDATED => {
+                params(&notification).map(ServerNotification::ResourceUpdated)
+            }
+            Self::PROGRESS => params(&notification).map(ServerNotification::Progress),
+            Self::LOGGING_MESSAGE => params(&notification).map(ServerNotification::LoggingMessage),
+            Self::CANCELLED => params(&notification).map(ServerNotification::Cancelled),
+            _ => None,
+        };
+        parsed.unwrap_or(ServerNotification::Other(notification))
+    }
+}
@@ -19,15 +19,15 @@ pub struct Resource {
     /// Optional description of the resource
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    /// MIME type of the resource content ("text" or "blob")
-    #[serde(default = "default_mime_type")]
-    pub mime_type: String,
+    /// MIME type of the resource content, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub annotations: Option<Annotations>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(rename_all = "camelCase", untagged)]
+#[serde(rename_all_fields = "camelCase", untagged)]
 pub enum ResourceContents {
     TextResourceContents {
         uri: String,
@@ -43,10 +43,6 @@ pub enum ResourceContents {
     },
 }
 
-fn default_mime_type() -> String {
-    "text".to_string()
-}
-
 impl Resource {
     /// Creates a new Resource from a URI with explicit mime type
     pub fn new<S: AsRef<str>>(
@@ -63,17 +59,11 @@ impl Resource {
             Some(n) => n,
             None => url
                 .path_segments()
-                .and_then(|segments| segments.last())
+                .and_then(|mut segments| segments.next_back())
                 .unwrap_or("unnamed")
                 .to_string(),
         };
 
-        // Use provided mime_type or default
-        let mime_type = match mime_type {
-            Some(t) if t == "text" || t == "blob" => t,
-            _ => default_mime_type(),
-        };
-
         Ok(Self {
             uri: uri.to_string(),
             name,
@@ -93,12 +83,6 @@ impl Resource {
         let uri_string = uri.into();
         Url::parse(&uri_string).map_err(|e| anyhow!("Invalid URI: {}", e))?;
 
-        // Use provided mime_type or default
-        let mime_type = match mime_type {
-            Some(t) if t == "text" || t == "blob" => t,
-            _ => default_mime_type(),
-        };
-
         Ok(Self {
             uri: uri_string,
             name: name.into(),
@@ -157,11 +141,7 @@ impl Resource {
 
     /// Sets the MIME type of the resource
     pub fn with_mime_type<S: Into<String>>(mut self, mime_type: S) -> Self {
-        let mime_type = mime_type.into();
-        match mime_type.as_str() {
-            "text" | "blob" => self.mime_type = mime_type,
-            _ => self.mime_type = default_mime_type(),
-        }
+        self.mime_type = Some(mime_type.into());
         self
     }
 }
@@ -181,10 +161,10 @@ mod tests {
             .map_err(|_| anyhow!("Invalid file path"))?
             .to_string();
 
-        let resource = Resource::new(&uri, Some("text".to_string()), None)?;
+        let resource = Resource::new(&uri, Some("text/plain".to_string()), None)?;
         assert!(resource.uri.starts_with("file:///"));
         assert_eq!(resource.priority(), Some(0.0));
-        assert_eq!(resource.mime_type, "text");
+        assert_eq!(resource.mime_type.as_deref(), Some("text/plain"));
         assert_eq!(resource.scheme()?, "file");
 
         Ok(())
@@ -198,34 +178,31 @@ mod tests {
             uri.clone(),
             "test.txt".to_string(),
             0.5,
-            Some("text".to_string()),
+            Some("text/plain".to_string()),
         )?;
 
         assert_eq!(resource.uri, uri);
         assert_eq!(resource.name, "test.txt");
         assert_eq!(resource.priority(), Some(0.5));
-        assert_eq!(resource.mime_type, "text");
+        assert_eq!(resource.mime_type.as_deref(), Some("text/plain"));
         assert_eq!(resource.scheme()?, "str");
 
         Ok(())
     }
 
     #[test]
-    fn test_mime_type_validation() -> Result<()> {
-        // Test valid mime types
-        let resource = Resource::new("file:///test.txt", Some("text".to_string()), None)?;
-        assert_eq!(resource.mime_type, "text");
-
-        let resource = Resource::new("file:///test.bin", Some("blob".to_string()), None)?;
-        assert_eq!(resource.mime_type, "blob");
-
-        // Test invalid mime type defaults to "text"
-        let resource = Resource::new("file:///test.txt", Some("invalid".to_string()), None)?;
-        assert_eq!(resource.mime_type, "text");
+    fn test_mime_type() -> Result<()> {
+        let resource = Resource::new("file:///test.md", Some("text/markdown".to_string()), None)?;
+        assert_eq!(resource.mime_type.as_deref(), Some("text/markdown"));
 
-        // Test None defaults to "text"
+        // An unknown type is left out rather than guessed, on the wire too
         let resource = Resource::new("file:///test.txt", None, None)?;
-        assert_eq!(resource.mime_type, "text");
+        assert_eq!(resource.mime_type, None);
+        assert!(serde_json::to_value(&resource)?.get("mimeType").is_none());
+
+        let resource: Resource =
+            serde_json::from_value(serde_json::json!({"uri": "file:///a", "name": "a"}))?;
+        assert_eq!(resource.mime_type, None);
 
         Ok(())
     }
@@ -241,14 +218,13 @@ mod tests {
 
     #[test]
     fn test_with_mime_type() -> Result<()> {
-        let resource =
-            Resource::with_uri("file:///test.txt", "test.txt", 0.0, None)?.with_mime_type("blob");
-
-        assert_eq!(resource.mime_type, "blob");
+        let resource = Resource::with_uri("file:///test.txt", "test.txt", 0.0, None)?
+            .with_mime_type("application/octet-stream");
 
-        // Test invalid mime type defaults to "text"
-        let resource = resource.with_mime_type("invalid");
-        assert_eq!(resource.mime_type, "text");
+        assert_eq!(
+            resource.mime_type.as_deref(),
+            Some("application/octet-stream")
+        );
         Ok(())
     }
 
@@ -0,0 +1,411 @@
+//! Validating values, like tool arguments, against the JSON Schemas that describe them.
+
+use serde_json::{Map, Value};
+
+/// How many `$ref`s may be followed without descending into the value, so a schema that refers
+/// to itself can't loop forever.
+const MAX_REF_DEPTH: usize = 32;
+
+/// A way a value fails to match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// JSON pointer to the part of the value that failed, empty for the value itself
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "(root): {}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Check `instance` against `schema`, returning every way it fails to match.
+///
+/// This covers the keywords that schemas generated with `schemars` use: `type`, `enum`, `const`,
+/// `properties`, `required`, `additionalProperties`, `items`, the length and range bounds,
+/// `allOf`/`anyOf`/`oneOf`/`not`, and `$ref`s within the schema. Other keywords, like `pattern`
+/// and `format`, are ignored.
+pub fn validate(schema: &Value, instance: &Value) -> Result<(), Vec<ValidationError>> {
+    let mut validator = Validator {
+        root: schema,
+        errors: Vec::new(),
+    };
+    validator.check(schema, instance, "", 0);
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.errors)
+    }
+}
+
+/// The errors as a single line, for putting in an error message.
+pub fn describe(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.errors.push(ValidationError {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Whether `instance` matches `schema`, without recording why not.
+    fn matches(&self, schema: &Value, instance: &Value, depth: usize) -> bool {
+        let mut validator = Validator {
+            root: self.root,
+            errors: Vec::new(),
+        };
+        validator.check(schema, instance, "", depth);
+        validator.errors.is_empty()
+    }
+
+    fn check(&mut self, schema: &'a Value, instance: &Value, path: &str, depth: usize) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return self.error(path, "no value is allowed here"),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer));
+            match target {
+                Some(_) if depth >= MAX_REF_DEPTH => {
+                    return self.error(path, format!("schema reference {reference} is circular"))
+                }
+                Some(target) => self.check(target, instance, path, depth + 1),
+                None => {
+                    return self.error(path, format!("cannot resolve schema reference {reference}"))
+                }
+            }
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|name| is_type(instance, name)) {
+                // The other keywords don't mean much for a value of the wrong type
+                return self.error(
+                    path,
+                    format!(
+                        "expected {}, got {}",
+                        types.join(" or "),
+                        type_name(instance)
+                    ),
+                );
+            }
+        }
+
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            if !values.contains(instance) {
+                let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+                self.error(
+                    path,
+                    format!("expected one of {}, got {instance}", values.join(", ")),
+                );
+            }
+        }
+        if let Some(value) = schema.get("const") {
+            if value != instance {
+                self.error(path, format!("expected {value}, got {instance}"));
+            }
+        }
+
+        match instance {
+            Value::Object(object) => self.check_object(schema, object, path),
+            Value::Array(items) => self.check_array(schema, items, path),
+            Value::String(string) => {
+                let length = string.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if length < min {
+                        self.error(path, format!("expected at least {min} characters"));
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if length > max {
+                        self.error(path, format!("expected at most {max} characters"));
+                    }
+                }
+            }
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum").filter(|min| number < *min) {
+                    self.error(path, format!("expected a number no less than {min}"));
+                }
+                if let Some(max) = bound("maximum").filter(|max| number > *max) {
+                    self.error(path, format!("expected a number no greater than {max}"));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|min| number <= *min) {
+                    self.error(path, format!("expected a number greater than {min}"));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|max| number >= *max) {
+                    self.error(path, format!("expected a number less than {max}"));
+                }
+            }
+            Value::Bool(_) | Value::Null => {}
+        }
+
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            for schema in schemas {
+                self.check(schema, instance, path, depth);
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("anyOf") {
+            if !schemas
+                .iter()
+                .any(|schema| self.matches(schema, instance, depth))
+            {
+                self.error(path, "does not match any of the allowed schemas");
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+            let matching = schemas
+                .iter()
+                .filter(|schema| self.matches(schema, instance, depth))
+                .count();
+            match matching {
+                0 => self.error(path, "does not match any of the allowed schemas"),
+                1 => {}
+                _ => self.error(path, "matches more than one of the allowed schemas"),
+            }
+        }
+        if let Some(schema) = schema.get("not") {
+            if self.matches(schema, instance, depth) {
+                self.error(path, "matches a schema it must not");
+            }
+        }
+    }
+
+    fn check_object(
+        &mut self,
+        schema: &'a Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    self.error(path, format!("missing required property `{name}`"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        for (name, value) in object {
+            let child = format!("{path}/{}", escape(name));
+            match (
+                properties.and_then(|properties| properties.get(name)),
+                additional,
+            ) {
+                (Some(property), _) => self.check(property, value, &child, 0),
+                (None, Some(Value::Bool(false))) => {
+                    self.error(path, format!("unexpected property `{name}`"))
+                }
+                (None, Some(additional)) => self.check(additional, value, &child, 0),
+                (None, None) => {}
+            }
+        }
+    }
+
+    fn check_array(&mut self, schema: &'a Map<String, Value>, items: &[Value], path: &str) {
+        let length = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if length < min {
+                self.error(path, format!("expected at least {min} items"));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if length > max {
+                self.error(path, format!("expected at most {max} items"));
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicate = items
+                .iter()
+                .enumerate()
+                .any(|(i, item)| items[..i].contains(item));
+            if duplicate {
+                self.error(path, "expected unique items");
+            }
+        }
+
+        match schema.get("items") {
+            // One schema for every item
+            Some(item @ (Value::Object(_) | Value::Bool(_))) => {
+                for (index, value) in items.iter().enumerate() {
+                    self.check(item, value, &format!("{path}/{index}"), 0);
+                }
+            }
+            // A schema for each position, then `additionalItems` for the rest
+            Some(Value::Array(positions)) => {
+                let additional = schema.get("additionalItems");
+                for (index, value) in items.iter().enumerate() {
+                    let child = format!("{path}/{index}");
+                    match (positions.get(index), additional) {
+                        (Some(item), _) | (None, Some(item)) => self.check(item, value, &child, 0),
+                        (None, None) => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => match instance {
+            Value::Number(number) => {
+                number.is_i64()
+                    || number.is_u64()
+                    || number.as_f64().is_some_and(|n| n.fract() == 0.0)
+            }
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(number) if is_type(instance, "integer") && !number.is_f64() => "integer",
+        Value::Number(_) => "number",
+    }
+}
+
+/// Escape a property name for use in a JSON pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schemars::JsonSchema;
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct Order {
+        item: String,
+        quantity: u32,
+        note: Option<String>,
+        lines: Vec<Line>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct Line {
+        sku: String,
+        kind: Kind,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    enum Kind {
+        Physical,
+        Digital,
+    }
+
+    fn paths(result: Result<(), Vec<ValidationError>>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_generated_schema() {
+        let schema = serde_json::to_value(schemars::schema_for!(Order)).unwrap();
+
+        let valid = json!({
+            "item": "book",
+            "quantity": 2,
+            "note": null,
+            "lines": [{ "sku": "b-1", "kind": "Physical" }],
+        });
+        assert_eq!(validate(&schema, &valid), Ok(()));
+
+        let invalid = json!({
+            "item": "book",
+            "quantity": -1,
+            "lines": [{ "sku": 7, "kind": "Virtual" }, {}],
+        });
+        assert_eq!(
+            paths(validate(&schema, &invalid)),
+            vec![
+                "/lines/0/kind: expected one of \"Physical\", \"Digital\", got \"Virtual\"",
+                "/lines/0/sku: expected string, got integer",
+                "/lines/1: missing required property `kind`",
+                "/lines/1: missing required property `sku`",
+                "/quantity: expected a number no less than 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_keywords() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "tags": { "type": "array", "items": { "type": "string" }, "maxItems": 2 },
+                "name": { "type": "string", "minLength": 1 },
+                "id": { "oneOf": [{ "type": "integer" }, { "type": "string" }] },
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+        });
+        assert_eq!(
+            paths(validate(
+                &schema,
+                &json!({ "tags": ["a", 1, "c"], "name": "", "id": 1.5, "extra": true })
+            )),
+            vec![
+                "(root): unexpected property `extra`",
+                "/id: does not match any of the allowed schemas",
+                "/name: expected at least 1 characters",
+                "/tags: expected at most 2 items",
+                "/tags/1: expected string, got integer",
+            ]
+        );
+        assert_eq!(
+            paths(validate(&schema, &json!(null))),
+            vec!["(root): expected object, got null"]
+        );
+    }
+}
@@ -1,6 +1,9 @@
+use std::task::{Context, Poll};
+
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
 
-use crate::protocol::{JsonRpcNotification, JsonRpcRequest};
+use crate::protocol::{JsonRpcMessage, JsonRpcNotification, JsonRpcRequest};
 
 /// This trait represents messages that can be sent over the transport.
 /// By using this trait, we can use the type system to ensure we don't initiate communication with
@@ -23,3 +26,63 @@ impl From<JsonRpcNotification> for SendableMessage {
         SendableMessage::Notification(notification)
     }
 }
+
+impl From<SendableMessage> for JsonRpcMessage {
+    fn from(message: SendableMessage) -> Self {
+        match message {
+            SendableMessage::Request(request) => JsonRpcMessage::Request(request),
+            SendableMessage::Notification(notification) => {
+                JsonRpcMessage::Notification(notification)
+            }
+        }
+    }
+}
+
+/// How many messages each direction of a [`duplex`] holds before sending waits for the other end
+/// to catch up.
+pub const DUPLEX_CAPACITY: usize = 32;
+
+/// One end of an in-process connection, made by [`duplex`]. Messages sent on one end are
+/// received on the other as they are, without being written out as bytes.
+///
+/// The client and server crates each have a transport built on an end, so a client can talk to a
+/// server running in the same process.
+#[derive(Debug)]
+pub struct Duplex {
+    sender: mpsc::Sender<JsonRpcMessage>,
+    receiver: mpsc::Receiver<JsonRpcMessage>,
+}
+
+/// Make both ends of an in-process connection. Each direction holds up to [`DUPLEX_CAPACITY`]
+/// messages, so a fast sender waits for a slow receiver rather than queueing without bound.
+pub fn duplex() -> (Duplex, Duplex) {
+    let (a_sender, b_receiver) = mpsc::channel(DUPLEX_CAPACITY);
+    let (b_sender, a_receiver) = mpsc::channel(DUPLEX_CAPACITY);
+    (
+        Duplex {
+            sender: a_sender,
+            receiver: a_receiver,
+        },
+        Duplex {
+            sender: b_sender,
+            receiver: b_receiver,
+        },
+    )
+}
+
+impl Duplex {
+    /// Send a message to the other end, waiting while its queue is full. Fails, giving the
+    /// message back, once that end is dropped.
+    pub async fn send(&self, message: JsonRpcMessage) -> Result<(), JsonRpcMessage> {
+        self.sender.send(message).await.map_err(|e| e.0)
+    }
+
+    /// Wait for the next message from the other end. Returns `None` once that end is dropped.
+    pub async fn recv(&mut self) -> Option<JsonRpcMessage> {
+        self.receiver.recv().await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<JsonRpcMessage>> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,828 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use mcp_client::{Catalog, Error as ClientError, McpClientPool, McpClientTrait, NamePrefix};
+use mcp_core::{
+    handler::{PromptError, ResourceError, ToolError},
+    prompt::Prompt,
+    protocol::{
+        CallToolResult, ErrorData, JsonRpcRequest, JsonRpcResponse, ServerCapabilities,
+        ServerNotification,
+    },
+    Content, Resource, ResourceContents, Tool,
+};
+use mcp_server::{router::CapabilitiesBuilder, Peer, Router, RouterError};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+/// What an entry in the gateway's catalog is, for filtering and renaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Tool,
+    Resource,
+    Prompt,
+}
+
+type Filter = Arc<dyn Fn(Kind, &str) -> bool + Send + Sync>;
+
+/// Build a `Gateway` from the clients of the servers it re-exports.
+pub struct GatewayBuilder {
+    name: String,
+    instructions: String,
+    pool: McpClientPool,
+    filter: Option<Filter>,
+    renames: HashMap<(Kind, String), String>,
+}
+
+impl GatewayBuilder {
+    pub fn new(name: impl Into<String>, instructions: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            instructions: instructions.into(),
+            pool: McpClientPool::new(),
+            filter: None,
+            renames: HashMap::new(),
+        }
+    }
+
+    /// Re-export the server `client` is connected to. The client should already be initialized,
+    /// and `name` not taken by another client.
+    pub fn with_client(
+        mut self,
+        name: impl Into<String>,
+        client: impl McpClientTrait + 'static,
+    ) -> Result<Self, ClientError> {
+        self.pool = self.pool.with_client(name, client)?;
+        Ok(self)
+    }
+
+    /// When tool and prompt names get the downstream server's name in front, see `NamePrefix`.
+    pub fn with_prefix(mut self, prefix: NamePrefix) -> Self {
+        self.pool = self.pool.with_prefix(prefix);
+        self
+    }
+
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        self.pool = self.pool.with_separator(separator);
+        self
+    }
+
+    /// Only re-export the entries `filter` accepts. It's given tool and prompt names after
+    /// prefixing but before renaming, and resource URIs.
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(Kind, &str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Re-export the tool or prompt called `from` (after prefixing) as `to`. If something else is
+    /// re-exported as `to` already, the rename is refused and `from` left out.
+    pub fn with_rename(
+        mut self,
+        kind: Kind,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Self {
+        self.renames.insert((kind, from.into()), to.into());
+        self
+    }
+
+    /// List the downstream servers, and start watching them for changes to what they offer.
+    pub async fn build(self) -> Gateway {
+        let inner = Arc::new(Inner {
+            pool: self.pool,
+            filter: self.filter,
+            renames: self.renames,
+            state: Mutex::new(State::default()),
+            peer: Peer::new(),
+        });
+        // Subscribe before listing, so changes made meanwhile aren't missed
+        let changes = changes(&inner.pool);
+        inner.refresh().await;
+        let watcher = tokio::spawn(watch(Arc::downgrade(&inner), changes));
+
+        Gateway {
+            name: self.name,
+            instructions: self.instructions,
+            inner,
+            _watcher: Arc::new(Watcher(watcher)),
+        }
+    }
+}
+
+/// A `Router` that serves what other MCP servers offer, reached through their clients, as if it
+/// were its own.
+///
+/// Calls are forwarded to the server an entry came from and its results passed back unchanged.
+/// When a downstream server says its tools, resources or prompts changed, the gateway lists it
+/// again and tells its own client. Pass `peer()` to `Server::with_peer` so those notifications can
+/// be sent.
+#[derive(Clone)]
+pub struct Gateway {
+    name: String,
+    instructions: String,
+    inner: Arc<Inner>,
+    _watcher: Arc<Watcher>,
+}
+
+struct Inner {
+    pool: McpClientPool,
+    filter: Option<Filter>,
+    renames: HashMap<(Kind, String), String>,
+    state: Mutex<State>,
+    peer: Peer,
+}
+
+/// The catalog as re-exported, with the names tools and prompts have in the pool.
+#[derive(Default)]
+struct State {
+    tools: Vec<Tool>,
+    resources: Vec<Resource>,
+    prompts: Vec<Prompt>,
+    tool_names: HashMap<String, String>,
+    prompt_names: HashMap<String, String>,
+}
+
+/// Stops watching the downstream servers once the last clone of the gateway is gone.
+struct Watcher(JoinHandle<()>);
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl Gateway {
+    /// The handle used to tell the client about changes downstream.
+    pub fn peer(&self) -> Peer {
+        self.inner.peer.clone()
+    }
+
+    /// List the downstream servers again.
+    pub async fn refresh(&self) {
+        self.inner.refresh().await;
+    }
+
+    async fn call(&self, name: &str, arguments: Value) -> Result<CallToolResult, ClientError> {
+        let name = self.inner.name_in_pool(Kind::Tool, name)?;
+        self.inner.pool.call_tool(&name, arguments).await
+    }
+
+    async fn read(&self, uri: &str) -> Result<Value, ClientError> {
+        if !self.inner.accepts(Kind::Resource, uri) {
+            return Err(unknown(Kind::Resource, uri));
+        }
+        let result = self.inner.pool.read_resource(uri).await?;
+        Ok(serde_json::to_value(result)?)
+    }
+
+    async fn prompt(&self, name: &str, arguments: Value) -> Result<Value, ClientError> {
+        let name = self.inner.name_in_pool(Kind::Prompt, name)?;
+        let result = self.inner.pool.get_prompt(&name, arguments).await?;
+        Ok(serde_json::to_value(result)?)
+    }
+}
+
+impl Inner {
+    async fn refresh(&self) {
+        let Catalog {
+            tools,
+            resources,
+            prompts,
+            errors,
+        } = self.pool.catalog().await;
+        for (server, error) in errors {
+            tracing::warn!(server, %error, "Leaving out server that could not be listed");
+        }
+
+        let mut state = State::default();
+        (state.tools, state.tool_names) = self.expose(Kind::Tool, tools, |tool| &mut tool.name);
+        (state.prompts, state.prompt_names) =
+            self.expose(Kind::Prompt, prompts, |prompt| &mut prompt.name);
+        state.resources = resources
+            .into_iter()
+            .filter(|resource| self.accepts(Kind::Resource, &resource.uri))
+            .collect();
+
+        *self.state.lock().unwrap() = state;
+    }
+
+    /// The tools or prompts the filter accepts, renamed, with the names the pool knows them by.
+    ///
+    /// A rename to a name that something else is exposed under is refused, and what was renamed
+    /// left out, so a call never reaches a different tool than the one listed.
+    fn expose<T>(
+        &self,
+        kind: Kind,
+        items: Vec<T>,
+        name: impl Fn(&mut T) -> &mut String,
+    ) -> (Vec<T>, HashMap<String, String>) {
+        let items: Vec<(T, String, String)> = items
+            .into_iter()
+            .filter_map(|mut item| {
+                let name_in_pool = name(&mut item).clone();
+                let exposed = self.rename(kind, &name_in_pool)?;
+                Some((item, name_in_pool, exposed))
+            })
+            .collect();
+        let kept: HashSet<String> = items
+            .iter()
+            .filter(|(_, name_in_pool, exposed)| name_in_pool == exposed)
+            .map(|(_, _, exposed)| exposed.clone())
+            .collect();
+
+        let mut exposed_items = Vec::new();
+        let mut names = HashMap::new();
+        for (mut item, name_in_pool, exposed) in items {
+            let renamed = name_in_pool != exposed;
+            if renamed && (kept.contains(&exposed) || names.contains_key(&exposed)) {
+                tracing::warn!(
+                    ?kind,
+                    name = %name_in_pool,
+                    to = %exposed,
+                    "Leaving out an entry renamed to a name that is already taken"
+                );
+                continue;
+            }
+            *name(&mut item) = exposed.clone();
+            names.insert(exposed, name_in_pool);
+            exposed_items.push(item);
+        }
+        (exposed_items, names)
+    }
+
+    fn accepts(&self, kind: Kind, name: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(kind, name))
+    }
+
+    /// The name to re-export `name` from the pool under, or `None` if it's filtered out.
+    fn rename(&self, kind: Kind, name: &str) -> Option<String> {
+        if !self.accepts(kind, name) {
+            return None;
+        }
+        let renamed = self.renames.get(&(kind, name.to_string()));
+        Some(renamed.cloned().unwrap_or_else(|| name.to_string()))
+    }
+
+    /// The name the pool knows a re-exported tool or prompt by.
+    fn name_in_pool(&self, kind: Kind, name: &str) -> Result<String, ClientError> {
+        let state = self.state.lock().unwrap();
+        let names = match kind {
+            Kind::Tool => &state.tool_names,
+            _ => &state.prompt_names,
+        };
+        names.get(name).cloned().ok_or_else(|| unknown(kind, name))
+    }
+}
+
+type Changes = futures::stream::SelectAll<BoxStream<'static, ServerNotification>>;
+
+/// The notifications of the downstream servers saying what they offer changed.
+fn changes(pool: &McpClientPool) -> Changes {
+    futures::stream::select_all(pool.clients().map(|(_, client)| {
+        client
+            .subscribe()
+            .with_methods([
+                ServerNotification::TOOL_LIST_CHANGED,
+                ServerNotification::RESOURCE_LIST_CHANGED,
+                ServerNotification::PROMPT_LIST_CHANGED,
+            ])
+            .into_stream()
+            .boxed()
+    }))
+}
+
+/// Relist the downstream servers whenever one says what it offers changed, and pass the news on.
+async fn watch(inner: std::sync::Weak<Inner>, mut changes: Changes) {
+    while let Some(notification) = changes.next().await {
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let method = notification.method().to_string();
+        tracing::debug!(%method, "Downstream server changed");
+        inner.refresh().await;
+        // Nobody to tell is fine
+        let _ = inner.peer.send_notification(&method, None);
+    }
+}
+
+fn unknown(kind: Kind, name: &str) -> ClientError {
+    let kind = match kind {
+        Kind::Tool => "tool",
+        Kind::Resource => "resource",
+        Kind::Prompt => "prompt",
+    };
+    ClientError::UnknownTarget {
+        kind,
+        name: name.to_string(),
+    }
+}
+
+/// How a failed call shows up to the gateway's client.
+fn router_error(error: ClientError) -> RouterError {
+    match error {
+        ClientError::UnknownTarget { kind: "tool", name } => RouterError::ToolNotFound(name),
+        ClientError::UnknownTarget {
+            kind: "resource",
+            name,
+        } => RouterError::ResourceNotFound(name),
+        ClientError::UnknownTarget { name, .. } => {
+            RouterError::PromptNotFound(format!("Prompt '{name}' not found"))
+        }
+        // Keep what the downstream server said, so the client sees the same error
+        ClientError::RpcError {
+            code,
+            message,
+            data,
+        } => RouterError::Rpc(ErrorData {
+            code,
+            message,
+            data,
+        }),
+        error => RouterError::Internal(error.to_string()),
+    }
+}
+
+fn params(req: &JsonRpcRequest, key: &str) -> Result<(String, Value), RouterError> {
+    let params = req
+        .params
+        .as_ref()
+        .ok_or_else(|| RouterError::InvalidParams("Missing parameters".into()))?;
+    let name = params
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RouterError::InvalidParams(format!("Missing {key}")))?;
+    let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+    Ok((name.to_string(), arguments))
+}
+
+impl Router for Gateway {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn instructions(&self) -> String {
+        self.instructions.clone()
+    }
+
+    /// What any downstream server offers, taking those whose capabilities aren't known to offer
+    /// everything. Changes are always announced, as the gateway passes them on.
+    fn capabilities(&self) -> ServerCapabilities {
+        let offered = |offers: fn(&ServerCapabilities) -> bool| {
+            self.inner
+                .pool
+                .clients()
+                .any(|(_, client)| client.server_capabilities().is_none_or(offers))
+        };
+        let mut capabilities = CapabilitiesBuilder::new();
+        if offered(|capabilities| capabilities.tools.is_some()) {
+            capabilities = capabilities.with_tools(true);
+        }
+        if offered(|capabilities| capabilities.resources.is_some()) {
+            capabilities = capabilities.with_resources(false, true);
+        }
+        if offered(|capabilities| capabilities.prompts.is_some()) {
+            capabilities = capabilities.with_prompts(true);
+        }
+        capabilities.build()
+    }
+
+    fn list_tools(&self) -> Vec<Tool> {
+        self.inner.state.lock().unwrap().tools.clone()
+    }
+
+    fn tool_input_schema(&self, name: &str) -> Option<Value> {
+        let state = self.inner.state.lock().unwrap();
+        let tool = state.tools.iter().find(|tool| tool.name == name)?;
+        Some(tool.input_schema.clone())
+    }
+
+    fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: Value,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<Content>, ToolError>> + '_>> {
+        let tool_name = tool_name.to_string();
+        Box::pin(async move {
+            let result = self
+                .call(&tool_name, arguments)
+                .await
+                .map_err(|e| match e {
+                    ClientError::UnknownTarget { .. } => ToolError::NotFound(tool_name),
+                    e => ToolError::ExecutionError(e.to_string()),
+                })?;
+            if result.is_error == Some(true) {
+                let text: Vec<&str> = result.content.iter().filter_map(Content::as_text).collect();
+                return Err(ToolError::ExecutionError(text.join("\n")));
+            }
+            Ok(result.content)
+        })
+    }
+
+    fn list_resources(&self) -> Vec<Resource> {
+        self.inner.state.lock().unwrap().resources.clone()
+    }
+
+    fn read_resource(
+        &self,
+        uri: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ResourceError>> + 'static>> {
+        let gateway = self.clone();
+        let uri = uri.to_string();
+        Box::pin(async move {
+            let result = gateway.read(&uri).await.map_err(|e| match e {
+                ClientError::UnknownTarget { .. } => ResourceError::NotFound(uri),
+                e => ResourceError::ExecutionError(e.to_string()),
+            })?;
+            let contents: Vec<ResourceContents> =
+                serde_json::from_value(result["contents"].clone())
+                    .map_err(|e| ResourceError::ExecutionError(e.to_string()))?;
+            Ok(contents
+                .into_iter()
+                .map(|contents| match contents {
+                    ResourceContents::TextResourceContents { text, .. } => text,
+                    ResourceContents::BlobResourceContents { blob, .. } => blob,
+                })
+                .collect())
+        })
+    }
+
+    fn list_prompts(&self) -> Vec<Prompt> {
+        self.inner.state.lock().unwrap().prompts.clone()
+    }
+
+    fn get_prompt(
+        &self,
+        prompt_name: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<String, PromptError>> + 'static>> {
+        let gateway = self.clone();
+        let prompt_name = prompt_name.to_string();
+        Box::pin(async move {
+            let result = gateway
+                .prompt(&prompt_name, serde_json::json!({}))
+                .await
+                .map_err(|e| match e {
+                    ClientError::UnknownTarget { .. } => PromptError::NotFound(prompt_name),
+                    e => PromptError::InternalError(e.to_string()),
+                })?;
+            Ok(result["messages"][0]["content"]["text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string())
+        })
+    }
+
+    // The default handlers go through the methods above, which only carry text. Forward these
+    // requests whole instead, so results arrive as the downstream server sent them.
+
+    async fn handle_tools_call(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse, RouterError> {
+        let (name, arguments) = params(&req, "name")?;
+        let result = self.call(&name, arguments).await.map_err(router_error)?;
+        let result = serde_json::to_value(result)
+            .map_err(|e| RouterError::Internal(format!("JSON serialization error: {}", e)))?;
+        Ok(JsonRpcResponse::success(req.id, result))
+    }
+
+    async fn handle_resources_read(
+        &self,
+        req: JsonRpcRequest,
+    ) -> Result<JsonRpcResponse, RouterError> {
+        let (uri, _) = params(&req, "uri")?;
+        let result = self.read(&uri).await.map_err(router_error)?;
+        Ok(JsonRpcResponse::success(req.id, result))
+    }
+
+    async fn handle_prompts_get(
+        &self,
+        req: JsonRpcRequest,
+    ) -> Result<JsonRpcResponse, RouterError> {
+        let (name, arguments) = params(&req, "name")?;
+        let result = self.prompt(&name, arguments).await.map_err(router_error)?;
+        Ok(JsonRpcResponse::success(req.id, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_client::{
+        ClientCapabilities, ClientInfo, Dispatcher, MemoryTransport, Notifications, Transport,
+    };
+    use mcp_core::prompt::{PromptMessage, PromptMessageRole};
+    use mcp_core::protocol::{
+        GetPromptResult, InitializeResult, JsonRpcMessage, JsonRpcNotification, ListPromptsResult,
+        ListResourcesResult, ListToolsResult, MessageId, ReadResourceResult,
+    };
+    use mcp_server::router::RouterService;
+    use mcp_server::Server;
+    use serde_json::json;
+
+    /// A client for a server with the given tools and a `greet` prompt. Tools answer with the
+    /// server and tool name; `fail` answers with an error result, and `reject` with a JSON-RPC
+    /// error. Notifications come from `dispatcher`.
+    struct FakeClient {
+        server: &'static str,
+        tools: Arc<Mutex<Vec<&'static str>>>,
+        dispatcher: Dispatcher,
+        capabilities: Option<ServerCapabilities>,
+    }
+
+    impl FakeClient {
+        fn new(server: &'static str, tools: Vec<&'static str>) -> Self {
+            Self {
+                server,
+                tools: Arc::new(Mutex::new(tools)),
+                dispatcher: Dispatcher::new(),
+                capabilities: None,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl McpClientTrait for FakeClient {
+        async fn initialize(
+            &mut self,
+            _info: ClientInfo,
+            _capabilities: ClientCapabilities,
+        ) -> Result<InitializeResult, ClientError> {
+            unimplemented!()
+        }
+
+        async fn list_resources(
+            &self,
+            _next_cursor: Option<String>,
+        ) -> Result<ListResourcesResult, ClientError> {
+            let uri = format!("file:///{}.txt", self.server);
+            Ok(ListResourcesResult {
+                resources: vec![Resource::new(uri, None, None).unwrap()],
+                next_cursor: None,
+            })
+        }
+
+        async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, ClientError> {
+            Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: uri.to_string(),
+                    mime_type: None,
+                    text: self.server.to_string(),
+                }],
+            })
+        }
+
+        async fn list_tools(
+            &self,
+            _next_cursor: Option<String>,
+        ) -> Result<ListToolsResult, ClientError> {
+            Ok(ListToolsResult {
+                tools: self
+                    .tools
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|name| Tool::new(*name, "", json!({})))
+                    .collect(),
+                next_cursor: None,
+            })
+        }
+
+        async fn call_tool(
+            &self,
+            name: &str,
+            _arguments: Value,
+        ) -> Result<CallToolResult, ClientError> {
+            if name == "reject" {
+                return Err(ClientError::RpcError {
+                    code: -32001,
+                    message: "Quota exceeded".to_string(),
+                    data: Some(json!({ "retryAfter": 60 })),
+                });
+            }
+            Ok(CallToolResult {
+                content: vec![Content::text(format!("{}:{name}", self.server))],
+                is_error: (name == "fail").then_some(true),
+            })
+        }
+
+        async fn list_prompts(
+            &self,
+            _next_cursor: Option<String>,
+        ) -> Result<ListPromptsResult, ClientError> {
+            Ok(ListPromptsResult {
+                prompts: vec![Prompt::new("greet", None::<String>, None)],
+                next_cursor: None,
+            })
+        }
+
+        async fn get_prompt(
+            &self,
+            _name: &str,
+            arguments: Value,
+        ) -> Result<GetPromptResult, ClientError> {
+            Ok(GetPromptResult {
+                description: None,
+                messages: vec![PromptMessage::new_text(
+                    PromptMessageRole::User,
+                    format!("Hello {} from {}", arguments["name"], self.server),
+                )],
+            })
+        }
+
+        fn server_capabilities(&self) -> Option<&ServerCapabilities> {
+            self.capabilities.as_ref()
+        }
+
+        fn subscribe(&self) -> Notifications {
+            self.dispatcher.subscribe()
+        }
+    }
+
+    async fn gateway() -> Gateway {
+        GatewayBuilder::new("gateway", "")
+            .with_client("git", FakeClient::new("git", vec!["status", "log", "fail"]))
+            .and_then(|builder| {
+                builder.with_client("svn", FakeClient::new("svn", vec!["status", "blame"]))
+            })
+            .unwrap()
+            .with_filter(|kind, name| kind != Kind::Tool || name != "blame")
+            .with_rename(Kind::Tool, "git__status", "status")
+            .build()
+            .await
+    }
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest::new(MessageId::Num(1), method.to_string(), Some(params))
+    }
+
+    fn result(response: JsonRpcResponse) -> Value {
+        match response {
+            JsonRpcResponse::Success { result, .. } => result,
+            JsonRpcResponse::Error { error, .. } => panic!("Unexpected error: {error:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reexports_filtered_and_renamed_catalog() {
+        let gateway = gateway().await;
+        let tools: Vec<String> = gateway.list_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(tools, vec!["status", "log", "fail", "svn__status"]);
+        let prompts: Vec<String> = gateway.list_prompts().into_iter().map(|p| p.name).collect();
+        assert_eq!(prompts, vec!["git__greet", "svn__greet"]);
+        assert_eq!(gateway.list_resources().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_forwards_requests() {
+        let gateway = gateway().await;
+        let call = |name: &str| request("tools/call", json!({ "name": name, "arguments": {} }));
+
+        let response = gateway.handle_tools_call(call("status")).await.unwrap();
+        assert_eq!(result(response)["content"][0]["text"], "git:status");
+        let response = gateway
+            .handle_tools_call(call("svn__status"))
+            .await
+            .unwrap();
+        assert_eq!(result(response)["content"][0]["text"], "svn:status");
+        let response = gateway.handle_tools_call(call("fail")).await.unwrap();
+        assert_eq!(result(response)["isError"], true);
+        for hidden in ["blame", "git__status"] {
+            assert!(matches!(
+                gateway.handle_tools_call(call(hidden)).await,
+                Err(RouterError::ToolNotFound(name)) if name == hidden
+            ));
+        }
+
+        let response = gateway
+            .handle_prompts_get(request(
+                "prompts/get",
+                json!({ "name": "svn__greet", "arguments": { "name": "Ada" } }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            result(response)["messages"][0]["content"]["text"],
+            "Hello \"Ada\" from svn"
+        );
+
+        let response = gateway
+            .handle_resources_read(request(
+                "resources/read",
+                json!({ "uri": "file:///svn.txt" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(result(response)["contents"][0]["text"], "svn");
+    }
+
+    #[tokio::test]
+    async fn test_downstream_errors_pass_through() {
+        let gateway = GatewayBuilder::new("gateway", "")
+            .with_client("git", FakeClient::new("git", vec!["reject"]))
+            .unwrap()
+            .build()
+            .await;
+        let error = gateway
+            .handle_tools_call(request("tools/call", json!({ "name": "reject" })))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            ErrorData::from(error),
+            ErrorData {
+                code: -32001,
+                message: "Quota exceeded".to_string(),
+                data: Some(json!({ "retryAfter": 60 })),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_follow_downstream() {
+        let only_tools = FakeClient {
+            capabilities: Some(CapabilitiesBuilder::new().with_tools(false).build()),
+            ..FakeClient::new("git", vec!["status"])
+        };
+        let gateway = GatewayBuilder::new("gateway", "")
+            .with_client("git", only_tools)
+            .unwrap()
+            .build()
+            .await;
+        let capabilities = gateway.capabilities();
+        assert!(capabilities.tools.is_some());
+        assert!(capabilities.resources.is_none());
+        assert!(capabilities.prompts.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rename_to_a_taken_name_is_refused() {
+        let gateway = GatewayBuilder::new("gateway", "")
+            .with_client("git", FakeClient::new("git", vec!["status", "log", "fail"]))
+            .and_then(|builder| {
+                builder.with_client("svn", FakeClient::new("svn", vec!["status", "blame"]))
+            })
+            .unwrap()
+            .with_rename(Kind::Tool, "log", "fail")
+            .with_rename(Kind::Tool, "git__status", "status")
+            .with_rename(Kind::Tool, "svn__status", "status")
+            .build()
+            .await;
+        let tools: Vec<String> = gateway.list_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(tools, vec!["status", "fail", "blame"]);
+
+        let response = gateway
+            .handle_tools_call(request("tools/call", json!({ "name": "fail" })))
+            .await
+            .unwrap();
+        assert_eq!(result(response)["content"][0]["text"], "git:fail");
+    }
+
+    #[tokio::test]
+    async fn test_downstream_changes_refresh_and_reach_the_client() {
+        // The downstream server is played over an in-memory transport, so its notification
+        // goes through a real dispatcher
+        let (client_end, downstream) = mcp_core::transport::duplex();
+        let transport = MemoryTransport::new(client_end);
+        let _handle = transport.start().await.unwrap();
+        let client = FakeClient {
+            dispatcher: transport.dispatcher().clone(),
+            ..FakeClient::new("git", vec!["status"])
+        };
+        let tools = Arc::clone(&client.tools);
+        let gateway = GatewayBuilder::new("gateway", "")
+            .with_client("git", client)
+            .unwrap()
+            .build()
+            .await;
+
+        // The gateway is served to its own client, which hears of the change through the peer
+        let (server_end, mut upstream) = mcp_core::transport::duplex();
+        let server = Server::new(RouterService::new(gateway.clone())).with_peer(gateway.peer());
+        let serving = server.run(mcp_server::MemoryTransport::new(server_end));
+
+        tools.lock().unwrap().push("log");
+        downstream
+            .send(
+                JsonRpcNotification::new(ServerNotification::TOOL_LIST_CHANGED.to_string(), None)
+                    .into(),
+            )
+            .await
+            .unwrap();
+
+        let message = tokio::select! {
+            result = serving => panic!("The server stopped: {result:?}"),
+            message = upstream.recv() => message,
+        };
+        let Some(JsonRpcMessage::Notification(notification)) = message else {
+            panic!("Expected a notification");
+        };
+        assert_eq!(notification.method, ServerNotification::TOOL_LIST_CHANGED);
+        let tools: Vec<String> = gateway.list_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(tools, vec!["status", "log"]);
+    }
+}
@@ -11,6 +11,26 @@ struct MacroArgs {
     name: Option<String>,
     description: Option<String>,
     param_descriptions: HashMap<String, String>,
+    /// In milliseconds
+    timeout: Option<u64>,
+    max_concurrency: Option<usize>,
+}
+
+const DURATION_EXPECTED: &str = "expected a duration such as \"500ms\", \"30s\", \"5m\" or \"1h\"";
+
+/// Parse a duration such as `500ms`, `30s`, `5m` or `1h` into milliseconds.
+fn parse_duration(duration: &str) -> Option<u64> {
+    let split = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = duration.split_at(split);
+    let value: u64 = value.parse().ok()?;
+    let factor = match unit.trim() {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    value.checked_mul(factor)
 }
 
 impl Parse for MacroArgs {
@@ -18,6 +38,8 @@ impl Parse for MacroArgs {
         let mut name = None;
         let mut description = None;
         let mut param_descriptions = HashMap::new();
+        let mut timeout = None;
+        let mut max_concurrency = None;
 
         let meta_list: Punctuated<Meta, Token![,]> = Punctuated::parse_terminated(input)?;
 
@@ -25,16 +47,49 @@ impl Parse for MacroArgs {
             match meta {
                 Meta::NameValue(nv) => {
                     let ident = nv.path.get_ident().unwrap().to_string();
-                    if let Expr::Lit(ExprLit {
-                        lit: Lit::Str(lit_str),
-                        ..
-                    }) = nv.value
-                    {
-                        match ident.as_str() {
-                            "name" => name = Some(lit_str.value()),
-                            "description" => description = Some(lit_str.value()),
-                            _ => {}
+                    let lit = match &nv.value {
+                        Expr::Lit(ExprLit { lit, .. }) => Some(lit),
+                        _ => None,
+                    };
+                    match (ident.as_str(), lit) {
+                        ("name" | "description", Some(Lit::Str(lit_str))) => {
+                            if ident == "name" {
+                                name = Some(lit_str.value());
+                            } else {
+                                description = Some(lit_str.value());
+                            }
+                        }
+                        ("name" | "description", _) => {
+                            return Err(syn::Error::new_spanned(
+                                &nv.value,
+                                format!("{ident} must be a string"),
+                            ));
                         }
+                        ("timeout", Some(Lit::Str(lit_str))) => {
+                            timeout = Some(parse_duration(&lit_str.value()).ok_or_else(|| {
+                                syn::Error::new_spanned(lit_str, DURATION_EXPECTED)
+                            })?);
+                        }
+                        ("timeout", _) => {
+                            return Err(syn::Error::new_spanned(&nv.value, DURATION_EXPECTED));
+                        }
+                        ("max_concurrency", Some(Lit::Int(lit_int))) => {
+                            let max = lit_int.base10_parse::<usize>()?;
+                            if max == 0 {
+                                return Err(syn::Error::new_spanned(
+                                    lit_int,
+                                    "max_concurrency must be at least 1",
+                                ));
+                            }
+                            max_concurrency = Some(max);
+                        }
+                        ("max_concurrency", _) => {
+                            return Err(syn::Error::new_spanned(
+                                &nv.value,
+                                "max_concurrency must be an integer such as 2",
+                            ));
+                        }
+                        _ => {}
                     }
                 }
                 Meta::List(list) if list.path.is_ident("params") => {
@@ -62,6 +117,8 @@ impl Parse for MacroArgs {
             name,
             description,
             param_descriptions,
+            timeout,
+            max_concurrency,
         })
     }
 }
@@ -96,6 +153,21 @@ pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
     let tool_name = args.name.unwrap_or(fn_name_str);
     let tool_description = args.description.unwrap_or_default();
 
+    // Only tools with limits override the default `limits`, which has none
+    let limits = (args.timeout.is_some() || args.max_concurrency.is_some()).then(|| {
+        let timeout = args.timeout.map(|millis| {
+            quote! { .with_timeout(std::time::Duration::from_millis(#millis)) }
+        });
+        let max_concurrency = args
+            .max_concurrency
+            .map(|max| quote! { .with_max_concurrency(#max) });
+        quote! {
+            fn limits(&self) -> mcp_server::server::ToolLimits {
+                mcp_server::server::ToolLimits::new() #timeout #max_concurrency
+            }
+        }
+    });
+
     // Extract parameter names, types, and descriptions
     let mut ctx_params = Vec::new();
     let mut param_defs = Vec::new();
@@ -104,7 +176,7 @@ pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
     for arg in input_fn.sig.inputs.iter() {
         if let FnArg::Typed(PatType { pat, ty, .. }) = arg {
             if let Pat::Ident(param_ident) = &**pat {
-                if is_injected_type(&**ty) {
+                if is_injected_type(ty) {
                     ctx_params.push(param_ident);
                     continue;
                 }
@@ -157,6 +229,8 @@ pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
                     .expect("Failed to generate schema")
             }
 
+            #limits
+
             async fn call(&self, context: &mcp_server::context::Context, params: serde_json::Value) -> Result<serde_json::Value, mcp_core::handler::ToolError> {
                 let params: #params_struct_name = serde_json::from_value(params)
                     .map_err(|e| mcp_core::handler::ToolError::InvalidParameters(e.to_string()))?;
@@ -173,3 +247,30 @@ pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> syn::Result<MacroArgs> {
+        syn::parse_str(args)
+    }
+
+    #[test]
+    fn test_limits_need_the_right_literal() {
+        let args = parse(r#"name = "add", timeout = "30s", max_concurrency = 2"#).unwrap();
+        assert_eq!(args.name.as_deref(), Some("add"));
+        assert_eq!(args.timeout, Some(30_000));
+        assert_eq!(args.max_concurrency, Some(2));
+
+        for args in [
+            "timeout = 30",
+            r#"timeout = "30 parsecs""#,
+            r#"max_concurrency = "2""#,
+            "max_concurrency = 0",
+            "description = 1",
+        ] {
+            assert!(parse(args).is_err(), "{args} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,740 @@
+//! Bearer token authentication for servers reachable over HTTP.
+//!
+//! An [`Authenticator`] turns the token from a request's `Authorization` header into the
+//! [`Principal`] it was issued to. [`BearerAuth`] wraps one for a transport: it extracts the
+//! token, checks the scopes the server requires, and answers with a `401`/`403` response when that
+//! fails. As a [`tower::Layer`] it guards an HTTP service, which finds the [`Principal`] in the
+//! request's extensions. [`request_extensions`] carries it from there into the handling of the
+//! request's messages, and [`crate::Server::with_principal`] into a whole connection's. Either way
+//! tool handlers can take it as `Inject<Principal>`, see [`crate::context::Extensions`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::future::BoxFuture;
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{HeaderValue, Request, Response, StatusCode};
+use ring::signature;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+use tower_service::Service;
+
+use crate::context::{Extensions, Inject};
+
+/// Who a request was authenticated as.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    /// The user or client the token was issued to
+    pub subject: String,
+    pub scopes: Vec<String>,
+    /// Everything else known about the principal, e.g. the claims of a JWT
+    pub claims: Value,
+}
+
+impl Principal {
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            scopes: Vec::new(),
+            claims: Value::Null,
+        }
+    }
+
+    pub fn with_scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum AuthError {
+    #[error("No bearer token")]
+    MissingToken,
+
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+
+    #[error("Insufficient scope, requires: {}", .0.join(" "))]
+    InsufficientScope(Vec<String>),
+}
+
+/// Checks bearer tokens, e.g. against a list of known tokens, by verifying a JWT, or by asking an
+/// introspection endpoint.
+#[async_trait]
+pub trait Authenticator: Send + Sync + 'static {
+    /// The principal `token` was issued to. Fails with `AuthError::InvalidToken` if the token is
+    /// unknown, expired or otherwise not acceptable.
+    async fn authenticate(&self, token: &str) -> Result<Principal, AuthError>;
+}
+
+/// Accepts a fixed set of tokens, e.g. API keys from configuration.
+#[derive(Debug, Clone, Default)]
+pub struct StaticTokens {
+    tokens: HashMap<String, Principal>,
+}
+
+impl StaticTokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>, principal: Principal) -> Self {
+        self.tokens.insert(token.into(), principal);
+        self
+    }
+}
+
+#[async_trait]
+impl Authenticator for StaticTokens {
+    async fn authenticate(&self, token: &str) -> Result<Principal, AuthError> {
+        self.tokens
+            .get(token)
+            .cloned()
+            .ok_or_else(|| AuthError::InvalidToken("Unknown token".to_string()))
+    }
+}
+
+/// A public key from a JSON Web Key Set.
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    alg: Option<String>,
+    // RSA
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    // EC
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+/// Verifies JWT access tokens signed with one of the keys of a local JSON Web Key Set. Supports
+/// `RS256` and `ES256`.
+///
+/// The token must not have expired (`exp` is required) and must be valid already (`nbf`). The
+/// issuer and audience are checked if configured. The principal's subject is the `sub` claim, and
+/// its scopes come from `scope` (space separated) or `scp`.
+#[derive(Debug, Clone)]
+pub struct JwtAuthenticator {
+    keys: Vec<Jwk>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+}
+
+impl JwtAuthenticator {
+    /// Trust the keys of a JWKS document, i.e. `{"keys": [...]}`.
+    pub fn from_jwks(jwks: &str) -> Result<Self, AuthError> {
+        let jwks: Jwks = serde_json::from_str(jwks)
+            .map_err(|e| AuthError::InvalidToken(format!("Invalid JWKS: {e}")))?;
+        Ok(Self {
+            keys: jwks.keys,
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(60),
+        })
+    }
+
+    /// Only accept tokens with this `iss`.
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Only accept tokens for this audience, such as the server's URL.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// How far clocks may disagree when checking `exp` and `nbf`. Defaults to a minute.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    fn verify(&self, token: &str) -> Result<Value, AuthError> {
+        let invalid = |message: &str| AuthError::InvalidToken(message.to_string());
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| invalid("Bad encoding"))
+        };
+
+        let mut parts = token.split('.');
+        let (Some(header), Some(claims), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("Not a JWT"));
+        };
+        let signed = &token[..header.len() + 1 + claims.len()];
+        let header: JwtHeader =
+            serde_json::from_slice(&decode(header)?).map_err(|_| invalid("Bad header"))?;
+        let signature = decode(signature)?;
+
+        let kty = match header.alg.as_str() {
+            "RS256" => "RSA",
+            "ES256" => "EC",
+            _ => return Err(invalid("Unsupported algorithm")),
+        };
+        // Without a `kid`, any key for the algorithm may have signed the token
+        let mut keys = self
+            .keys
+            .iter()
+            .filter(|key| header.kid.is_none() || key.kid == header.kid)
+            .filter(|key| key.kty == kty && key.alg.as_ref().is_none_or(|alg| *alg == header.alg))
+            .peekable();
+        if keys.peek().is_none() {
+            return Err(invalid("Unknown key"));
+        }
+        let verified = keys.any(|key| verify_signature(key, signed.as_bytes(), &signature));
+        if !verified {
+            return Err(invalid("Bad signature"));
+        }
+
+        let claims: Value =
+            serde_json::from_slice(&decode(claims)?).map_err(|_| invalid("Bad claims"))?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let time = |claim: &str| claims[claim].as_u64().map(Duration::from_secs);
+        match time("exp") {
+            Some(exp) if exp + self.leeway > now => {}
+            Some(_) => return Err(invalid("Expired")),
+            None => return Err(invalid("No expiry")),
+        }
+        if time("nbf").is_some_and(|nbf| nbf > now + self.leeway) {
+            return Err(invalid("Not valid yet"));
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims["iss"].as_str() != Some(issuer) {
+                return Err(invalid("Wrong issuer"));
+            }
+        }
+        if let Some(audience) = &self.audience {
+            let matches = match &claims["aud"] {
+                Value::String(aud) => aud == audience,
+                Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+                _ => false,
+            };
+            if !matches {
+                return Err(invalid("Wrong audience"));
+            }
+        }
+        Ok(claims)
+    }
+}
+
+/// Whether `key` made `signature` of `signed`, with the algorithm for its type. Incomplete keys
+/// made nothing.
+fn verify_signature(key: &Jwk, signed: &[u8], signature: &[u8]) -> bool {
+    let decode = |part: &Option<String>| part.as_ref().and_then(|p| URL_SAFE_NO_PAD.decode(p).ok());
+    match key.kty.as_str() {
+        "RSA" => {
+            let (Some(n), Some(e)) = (decode(&key.n), decode(&key.e)) else {
+                return false;
+            };
+            signature::RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, signed, signature)
+                .is_ok()
+        }
+        "EC" if key.crv.as_deref() == Some("P-256") => {
+            let (Some(x), Some(y)) = (decode(&key.x), decode(&key.y)) else {
+                return false;
+            };
+            let mut point = vec![0x04];
+            point.extend(x);
+            point.extend(y);
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(signed, signature)
+                .is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl Authenticator for JwtAuthenticator {
+    async fn authenticate(&self, token: &str) -> Result<Principal, AuthError> {
+        let claims = self.verify(token)?;
+        let subject = claims["sub"]
+            .as_str()
+            .ok_or_else(|| AuthError::InvalidToken("No subject".to_string()))?
+            .to_string();
+        let scopes = match (&claims["scope"], &claims["scp"]) {
+            (Value::String(scope), _) => scope.split_whitespace().map(String::from).collect(),
+            (_, Value::Array(scp)) => scp
+                .iter()
+                .filter_map(|scope| scope.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(Principal {
+            subject,
+            scopes,
+            claims,
+        })
+    }
+}
+
+/// What to answer a request that failed authentication with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    /// `401` for a missing or invalid token, `403` for insufficient scope
+    pub status: u16,
+    /// The value of the `WWW-Authenticate` header
+    pub www_authenticate: String,
+    pub error: AuthError,
+}
+
+/// Authenticates the requests of an HTTP transport by their `Authorization: Bearer` header.
+///
+/// As a layer, it answers requests that fail with the [`Rejection`], and passes the others on with
+/// their [`Principal`] in the request's extensions:
+///
+/// ```no_run
+/// # use axum::routing::post;
+/// # use mcp_server::auth::{BearerAuth, Principal, StaticTokens};
+/// # async fn handle() {}
+/// let app: axum::Router = axum::Router::new()
+///     .route("/mcp", post(handle))
+///     .layer(BearerAuth::new(StaticTokens::new().with_token("secret", Principal::new("alice"))));
+/// ```
+///
+/// Handle the request's messages in [`crate::context::scope`] with [`request_extensions`], so tool
+/// handlers see the principal.
+#[derive(Clone)]
+pub struct BearerAuth {
+    authenticator: Arc<dyn Authenticator>,
+    required_scopes: Vec<String>,
+    resource_metadata: Option<String>,
+}
+
+impl BearerAuth {
+    pub fn new<A: Authenticator>(authenticator: A) -> Self {
+        Self {
+            authenticator: Arc::new(authenticator),
+            required_scopes: Vec::new(),
+            resource_metadata: None,
+        }
+    }
+
+    /// Refuse principals without all of these scopes, with `403 Forbidden`.
+    pub fn with_required_scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.required_scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Point clients to the server's protected resource metadata (RFC 9728) in rejections, so
+    /// they can discover where to get a token.
+    pub fn with_resource_metadata(mut self, url: impl Into<String>) -> Self {
+        self.resource_metadata = Some(url.into());
+        self
+    }
+
+    /// Authenticate a request by the value of its `Authorization` header.
+    pub async fn check(&self, authorization: Option<&str>) -> Result<Principal, Rejection> {
+        let token = authorization
+            .and_then(|header| header.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .filter(|token| !token.is_empty());
+        let Some(token) = token else {
+            return Err(self.reject(AuthError::MissingToken));
+        };
+
+        let principal = self
+            .authenticator
+            .authenticate(token)
+            .await
+            .map_err(|e| self.reject(e))?;
+        if !self
+            .required_scopes
+            .iter()
+            .all(|scope| principal.has_scope(scope))
+        {
+            return Err(self.reject(AuthError::InsufficientScope(self.required_scopes.clone())));
+        }
+        Ok(principal)
+    }
+
+    /// Describe the response for `error`, as RFC 6750 has it.
+    fn reject(&self, error: AuthError) -> Rejection {
+        let mut params = Vec::new();
+        let status = match &error {
+            // A request without credentials gets no error code
+            AuthError::MissingToken => 401,
+            AuthError::InvalidToken(description) => {
+                params.push(r#"error="invalid_token""#.to_string());
+                params.push(format!(
+                    r#"error_description="{}""#,
+                    description.replace('"', "'")
+                ));
+                401
+            }
+            AuthError::InsufficientScope(scopes) => {
+                params.push(r#"error="insufficient_scope""#.to_string());
+                params.push(format!(r#"scope="{}""#, scopes.join(" ")));
+                403
+            }
+        };
+        if let Some(url) = &self.resource_metadata {
+            params.push(format!(r#"resource_metadata="{url}""#));
+        }
+        let www_authenticate = match params.is_empty() {
+            true => "Bearer".to_string(),
+            false => format!("Bearer {}", params.join(", ")),
+        };
+        Rejection {
+            status,
+            www_authenticate,
+            error,
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for BearerAuth {
+    type Service = RequireBearer<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireBearer {
+            inner,
+            auth: self.clone(),
+        }
+    }
+}
+
+/// The service [`BearerAuth`] wraps an HTTP service in.
+#[derive(Clone)]
+pub struct RequireBearer<S> {
+    inner: S,
+    auth: BearerAuth,
+}
+
+impl<S, B, R> Service<Request<B>> for RequireBearer<S>
+where
+    S: Service<Request<B>, Response = Response<R>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+    R: Default,
+{
+    type Response = Response<R>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response<R>, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        // Call the service that was polled ready, and leave a fresh clone for the next request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let auth = self.auth.clone();
+        Box::pin(async move {
+            let authorization = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|header| header.to_str().ok());
+            match auth.check(authorization).await {
+                Ok(principal) => {
+                    request.extensions_mut().insert(principal);
+                    inner.call(request).await
+                }
+                Err(rejection) => Ok(rejection.into_response()),
+            }
+        })
+    }
+}
+
+/// The extensions to handle an HTTP request's messages with: the [`Principal`] [`BearerAuth`]
+/// put in the request's extensions, if any, as `Inject<Principal>`. Pass them to
+/// [`crate::context::scope`] along with each message the request carries.
+pub fn request_extensions(http: &http::Extensions) -> Extensions {
+    let mut extensions = Extensions::new();
+    if let Some(principal) = http.get::<Principal>() {
+        extensions.insert(Inject::new(principal.clone()));
+    }
+    extensions
+}
+
+impl Rejection {
+    /// The response to send, with an empty body.
+    pub fn into_response<R: Default>(self) -> Response<R> {
+        let mut response = Response::new(R::default());
+        *response.status_mut() =
+            StatusCode::from_u16(self.status).unwrap_or(StatusCode::UNAUTHORIZED);
+        if let Ok(value) = HeaderValue::from_str(&self.www_authenticate) {
+            response.headers_mut().insert(WWW_AUTHENTICATE, value);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{self, Context, FromContext};
+    use axum::body::Body;
+    use axum::routing::post;
+    use axum::{Extension, Router};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use serde_json::json;
+
+    fn bearer_auth() -> BearerAuth {
+        let tokens = StaticTokens::new()
+            .with_token("reader", Principal::new("alice").with_scopes(["read"]))
+            .with_token(
+                "writer",
+                Principal::new("bob").with_scopes(["read", "write"]),
+            );
+        BearerAuth::new(tokens)
+            .with_required_scopes(["write"])
+            .with_resource_metadata("https://mcp.example.com/.well-known/oauth-protected-resource")
+    }
+
+    #[tokio::test]
+    async fn test_bearer_auth_rejections() {
+        let auth = bearer_auth();
+
+        let missing = auth.check(None).await.unwrap_err();
+        assert_eq!(missing.status, 401);
+        assert_eq!(
+            missing.www_authenticate,
+            r#"Bearer resource_metadata="https://mcp.example.com/.well-known/oauth-protected-resource""#
+        );
+
+        let invalid = auth.check(Some("Bearer nope")).await.unwrap_err();
+        assert_eq!(invalid.status, 401);
+        assert!(invalid
+            .www_authenticate
+            .starts_with(r#"Bearer error="invalid_token", error_description="Unknown token""#));
+
+        let forbidden = auth.check(Some("Bearer reader")).await.unwrap_err();
+        assert_eq!(forbidden.status, 403);
+        assert_eq!(
+            forbidden.error,
+            AuthError::InsufficientScope(vec!["write".to_string()])
+        );
+        assert!(forbidden
+            .www_authenticate
+            .starts_with(r#"Bearer error="insufficient_scope", scope="write""#));
+
+        let principal = auth.check(Some("bearer writer")).await.unwrap();
+        assert_eq!(principal.subject, "bob");
+    }
+
+    #[tokio::test]
+    async fn test_bearer_auth_layer() {
+        async fn whoami(Extension(principal): Extension<Principal>) -> String {
+            principal.subject
+        }
+        let mut app = Router::new()
+            .route("/mcp", post(whoami))
+            .layer(bearer_auth());
+        let mut send = |authorization: Option<&str>| {
+            let mut request = Request::post("/mcp");
+            if let Some(authorization) = authorization {
+                request = request.header(AUTHORIZATION, authorization);
+            }
+            app.call(request.body(Body::empty()).unwrap())
+        };
+
+        let response = send(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer resource_metadata="https://mcp.example.com/.well-known/oauth-protected-resource""#
+        );
+
+        let response = send(Some("Bearer reader")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.headers()[WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap()
+            .starts_with(r#"Bearer error="insufficient_scope""#));
+
+        // The handler sees who the request was authenticated as
+        let response = send(Some("Bearer writer")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        assert_eq!(body, "bob");
+    }
+
+    /// A P-256 key pair, and its public key as a JWK.
+    fn es256_jwk(kid: &str) -> (EcdsaKeyPair, Value) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+        let point = key.public_key().as_ref();
+        let jwk = json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": kid,
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        });
+        (key, jwk)
+    }
+
+    /// A P-256 key pair, and a JWKS with its public key.
+    fn es256_key() -> (EcdsaKeyPair, String) {
+        let (key, jwk) = es256_jwk("key-1");
+        (key, json!({ "keys": [jwk] }).to_string())
+    }
+
+    fn sign(key: &EcdsaKeyPair, claims: Value) -> String {
+        sign_with_header(key, json!({"alg": "ES256", "kid": "key-1"}), claims)
+    }
+
+    fn sign_with_header(key: &EcdsaKeyPair, header: Value, claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(header.to_string());
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signed = format!("{header}.{claims}");
+        let signature = key.sign(&SystemRandom::new(), signed.as_bytes()).unwrap();
+        format!("{signed}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    #[tokio::test]
+    async fn test_jwt_authenticator() {
+        let (key, jwks) = es256_key();
+        let authenticator = JwtAuthenticator::from_jwks(&jwks)
+            .unwrap()
+            .with_issuer("https://auth.example.com")
+            .with_audience("https://mcp.example.com");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let claims = json!({
+            "sub": "alice",
+            "iss": "https://auth.example.com",
+            "aud": ["https://mcp.example.com"],
+            "exp": now + 300,
+            "scope": "read write",
+        });
+
+        let principal = authenticator
+            .authenticate(&sign(&key, claims.clone()))
+            .await
+            .unwrap();
+        assert_eq!(principal.subject, "alice");
+        assert_eq!(principal.scopes, ["read", "write"]);
+
+        let mut expired = claims.clone();
+        expired["exp"] = json!(now - 300);
+        let mut wrong_audience = claims.clone();
+        wrong_audience["aud"] = json!("https://other.example.com");
+        for claims in [expired, wrong_audience] {
+            let result = authenticator.authenticate(&sign(&key, claims)).await;
+            assert!(matches!(result, Err(AuthError::InvalidToken(_))));
+        }
+
+        // Tampering with the claims breaks the signature
+        let token = sign(&key, claims);
+        let mut parts: Vec<_> = token.split('.').map(String::from).collect();
+        parts[1] = URL_SAFE_NO_PAD.encode(json!({"sub": "mallory", "exp": now + 300}).to_string());
+        let result = authenticator.authenticate(&parts.join(".")).await;
+        assert_eq!(
+            result,
+            Err(AuthError::InvalidToken("Bad signature".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jwt_without_kid_tries_every_matching_key() {
+        let (_, first) = es256_jwk("key-1");
+        let (key, second) = es256_jwk("key-2");
+        let rsa = json!({"kty": "RSA", "kid": "key-3", "n": "AQAB", "e": "AQAB"});
+        let jwks = json!({ "keys": [rsa, first, second] }).to_string();
+        let authenticator = JwtAuthenticator::from_jwks(&jwks).unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let claims = json!({"sub": "alice", "exp": now + 300});
+
+        let token = sign_with_header(&key, json!({"alg": "ES256"}), claims.clone());
+        let principal = authenticator.authenticate(&token).await.unwrap();
+        assert_eq!(principal.subject, "alice");
+
+        // With a `kid`, only that key is tried
+        let token = sign_with_header(&key, json!({"alg": "ES256", "kid": "key-1"}), claims);
+        assert_eq!(
+            authenticator.authenticate(&token).await,
+            Err(AuthError::InvalidToken("Bad signature".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_principal_is_injected_from_request_scope() {
+        let mut extensions = Extensions::new();
+        extensions.insert(Inject::new(Principal::new("alice")));
+        let ctx = Context::default();
+
+        let principal = context::scope(extensions, || async {
+            Inject::<Principal>::from_context(&ctx)
+        })
+        .await;
+        assert_eq!(principal.subject, "alice");
+    }
+
+    #[tokio::test]
+    async fn test_principal_is_bridged_from_the_http_request() {
+        let mut request = Request::post("/mcp").body(Body::empty()).unwrap();
+        let extensions = request_extensions(request.extensions());
+        assert!(extensions.get::<Inject<Principal>>().is_none());
+
+        let ctx = Context::default();
+
+        request.extensions_mut().insert(Principal::new("alice"));
+        let extensions = request_extensions(request.extensions());
+        let principal = context::scope(extensions, || async {
+            Inject::<Principal>::from_context(&ctx)
+        })
+        .await;
+        assert_eq!(principal.subject, "alice");
+    }
+}
@@ -0,0 +1,313 @@
+//! Drives a whole session, from `initialize` to tool calls, over each server transport, and checks
+//! the server writes exactly the bytes recorded in `fixtures/session.jsonl`.
+//!
+//! Lines of the transcript starting with `> ` are sent by the client, and each request among them
+//! is followed by the server's response, starting with `< `.
+//!
+//! The same transcript is also replayed through the client's stdio and SSE transports, so both ends
+//! of each wire agree on it.
+
+use std::{convert::Infallible, future::Future, pin::Pin, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, Sse},
+    routing::{get, post},
+};
+use futures::{stream, Stream, StreamExt};
+use mcp_client::transport::{SseTransport, StdioTransport, Transport, TransportHandle};
+use mcp_core::{
+    handler::{PromptError, ResourceError, ToolError},
+    prompt::{Prompt, PromptArgument},
+    protocol::{JsonRpcMessage, ServerCapabilities},
+    transport::{duplex, SendableMessage},
+    Content, Resource, Tool,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines},
+    net::TcpListener,
+    sync::Mutex,
+};
+
+use crate::{
+    router::{CapabilitiesBuilder, RouterService},
+    ByteTransport, MemoryTransport, Router, Server,
+};
+
+const TRANSCRIPT: &str = include_str!("../fixtures/session.jsonl");
+
+/// One of everything, with nothing that changes from run to run.
+#[derive(Clone)]
+struct Fixed;
+
+impl Router for Fixed {
+    fn name(&self) -> String {
+        "conformance".to_string()
+    }
+
+    fn instructions(&self) -> String {
+        "Echoes messages back".to_string()
+    }
+
+    fn capabilities(&self) -> ServerCapabilities {
+        CapabilitiesBuilder::new()
+            .with_tools(false)
+            .with_resources(false, false)
+            .with_prompts(false)
+            .build()
+    }
+
+    fn list_tools(&self) -> Vec<Tool> {
+        vec![Tool::new(
+            "echo",
+            "Echo the message back",
+            json!({
+                "type": "object",
+                "properties": { "message": { "type": "string" } },
+                "required": ["message"],
+            }),
+        )]
+    }
+
+    fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: Value,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<Content>, ToolError>> + '_>> {
+        let tool_name = tool_name.to_string();
+        Box::pin(async move {
+            match (tool_name.as_str(), arguments["message"].as_str()) {
+                ("echo", Some(message)) => Ok(vec![Content::text(message)]),
+                ("echo", None) => Err(ToolError::InvalidParameters("Missing message".into())),
+                _ => Err(ToolError::NotFound(tool_name)),
+            }
+        })
+    }
+
+    fn list_resources(&self) -> Vec<Resource> {
+        vec![Resource {
+            uri: "memo://greeting".to_string(),
+            name: "greeting".to_string(),
+            description: Some("A greeting".to_string()),
+            mime_type: Some("text/plain".to_string()),
+            annotations: None,
+        }]
+    }
+
+    fn read_resource(
+        &self,
+        uri: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ResourceError>> + 'static>> {
+        let uri = uri.to_string();
+        Box::pin(async move {
+            match uri.as_str() {
+                "memo://greeting" => Ok("Hello!".to_string()),
+                _ => Err(ResourceError::NotFound(uri)),
+            }
+        })
+    }
+
+    fn list_prompts(&self) -> Vec<Prompt> {
+        vec![Prompt::new(
+            "greet",
+            Some("Greet someone"),
+            Some(vec![PromptArgument {
+                name: "name".to_string(),
+                description: Some("Who to greet".to_string()),
+                required: Some(true),
+            }]),
+        )]
+    }
+
+    fn get_prompt(
+        &self,
+        _prompt_name: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<String, PromptError>> + 'static>> {
+        Box::pin(async { Ok("Say hello to {name}".to_string()) })
+    }
+}
+
+/// The transcript as (what the client sends, what the server answers, if anything).
+fn exchanges() -> Vec<(&'static str, Option<&'static str>)> {
+    let mut exchanges: Vec<(&str, Option<&str>)> = Vec::new();
+    for line in TRANSCRIPT.lines().filter(|line| !line.is_empty()) {
+        if let Some(sent) = line.strip_prefix("> ") {
+            exchanges.push((sent, None));
+        } else if let Some(received) = line.strip_prefix("< ") {
+            let last = exchanges.last_mut().expect("A response before any request");
+            assert!(last.1.is_none(), "Two responses to {}", last.0);
+            last.1 = Some(received);
+        } else {
+            panic!("Unexpected transcript line: {line}");
+        }
+    }
+    exchanges
+}
+
+#[tokio::test]
+async fn test_session_over_byte_transport() {
+    let (client, server) = tokio::io::duplex(4096);
+    let (server_read, server_write) = tokio::io::split(server);
+    let (client_read, mut client_write) = tokio::io::split(client);
+
+    let server =
+        Server::new(RouterService::new(Fixed)).run(ByteTransport::new(server_read, server_write));
+    let client = async move {
+        let mut lines = BufReader::new(client_read).lines();
+        for (sent, expected) in exchanges() {
+            client_write
+                .write_all(format!("{sent}\n").as_bytes())
+                .await
+                .unwrap();
+            if let Some(expected) = expected {
+                let received = lines.next_line().await.unwrap().unwrap();
+                assert_eq!(received, expected, "Unexpected response to {sent}");
+            }
+        }
+        client_write.shutdown().await.unwrap();
+        assert!(lines.next_line().await.unwrap().is_none());
+    };
+
+    let (result, ()) = tokio::join!(server, client);
+    result.unwrap();
+}
+
+#[tokio::test]
+async fn test_session_over_memory_transport() {
+    let (mut client, server) = duplex();
+
+    let server = Server::new(RouterService::new(Fixed)).run(MemoryTransport::new(server));
+    let client = async move {
+        for (sent, expected) in exchanges() {
+            let message: JsonRpcMessage = serde_json::from_str(sent).unwrap();
+            client.send(message).await.unwrap();
+            if let Some(expected) = expected {
+                let received = client.recv().await.unwrap();
+                assert_eq!(
+                    serde_json::to_string(&received).unwrap(),
+                    expected,
+                    "Unexpected response to {sent}"
+                );
+            }
+        }
+    };
+
+    let (result, ()) = tokio::join!(server, client);
+    result.unwrap();
+}
+
+/// Replay the transcript through a client transport handle.
+async fn replay(handle: &impl TransportHandle) {
+    for (sent, expected) in exchanges() {
+        let message = match serde_json::from_str(sent).unwrap() {
+            JsonRpcMessage::Request(request) => SendableMessage::Request(request),
+            JsonRpcMessage::Notification(notification) => {
+                SendableMessage::Notification(notification)
+            }
+            message => panic!("The client doesn't send {message:?}"),
+        };
+        let received = handle.send(message).await.unwrap();
+        assert_eq!(
+            received
+                .map(|response| serde_json::to_string(&JsonRpcMessage::from(response)).unwrap())
+                .as_deref(),
+            expected,
+            "Unexpected response to {sent}"
+        );
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_session_over_client_stdio_transport() {
+    // The server runs in this process, so the child only relays its stdio to a socket we serve on.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let transport = StdioTransport::new(
+        "bash",
+        vec![
+            "-c".to_string(),
+            "exec 3<>/dev/tcp/127.0.0.1/$PORT; cat <&3 & cat >&3; kill $!".to_string(),
+        ],
+        [("PORT".to_string(), port.to_string())].into(),
+    );
+    let handle = transport.start().await.unwrap();
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let (read, write) = stream.into_split();
+    let server = Server::new(RouterService::new(Fixed)).run(ByteTransport::new(read, write));
+
+    tokio::select! {
+        result = server => panic!("The server stopped early: {result:?}"),
+        () = replay(&handle) => {}
+    }
+    transport.close().await.unwrap();
+}
+
+/// The server's end of the SSE bridge: POSTs are written to its input, and each line it writes
+/// goes out as a `message` event.
+#[derive(Clone)]
+struct Bridge {
+    input: Arc<Mutex<DuplexStream>>,
+    output: Arc<Mutex<Option<Lines<BufReader<DuplexStream>>>>>,
+}
+
+async fn events(
+    State(bridge): State<Bridge>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let lines = bridge
+        .output
+        .lock()
+        .await
+        .take()
+        .ok_or(StatusCode::CONFLICT)?;
+    let endpoint = Event::default().event("endpoint").data("/message");
+    let messages = stream::unfold(lines, |mut lines| async move {
+        let line = lines.next_line().await.ok()??;
+        Some((Event::default().event("message").data(line), lines))
+    });
+    Ok(Sse::new(
+        stream::once(async { endpoint }).chain(messages).map(Ok),
+    ))
+}
+
+async fn message(State(bridge): State<Bridge>, body: String) -> StatusCode {
+    let mut input = bridge.input.lock().await;
+    match input.write_all(format!("{body}\n").as_bytes()).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::GONE,
+    }
+}
+
+#[tokio::test]
+async fn test_session_over_client_sse_transport() {
+    let (input, server_read) = tokio::io::duplex(4096);
+    let (server_write, output) = tokio::io::duplex(4096);
+    let bridge = Bridge {
+        input: Arc::new(Mutex::new(input)),
+        output: Arc::new(Mutex::new(Some(BufReader::new(output).lines()))),
+    };
+    let app = axum::Router::new()
+        .route("/sse", get(events))
+        .route("/message", post(message))
+        .with_state(bridge);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let server =
+        Server::new(RouterService::new(Fixed)).run(ByteTransport::new(server_read, server_write));
+    let transport = SseTransport::new(format!("http://{addr}/sse"));
+    let client = async {
+        let handle = transport.start().await.unwrap();
+        replay(&handle).await;
+    };
+
+    tokio::select! {
+        result = server => panic!("The server stopped early: {result:?}"),
+        () = client => {}
+    }
+    transport.close().await.unwrap();
+}
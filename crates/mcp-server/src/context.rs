@@ -2,9 +2,16 @@ use serde::{de, Serialize};
 use std::{
     any::{Any, TypeId},
     collections::HashMap,
+    future::Future,
     ops::Deref,
+    rc::Rc,
     sync::Arc,
 };
+use tokio::task::futures::TaskLocalFuture;
+
+tokio::task_local! {
+    static REQUEST: Extensions;
+}
 
 /// Registry of types that may be injected in MCPServer tool handlers.
 ///
@@ -29,6 +36,43 @@ impl Context {
     }
 }
 
+/// Values that belong to the request being handled rather than to the server, such as the
+/// `Principal` it was authenticated as.
+///
+/// Tool handlers get them through `Inject<T>` parameters like server state, and they take
+/// precedence over state of the same type. Transports attach them to the handling of a request
+/// with [`scope`].
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Rc<dyn Any>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<T: 'static>(&mut self, value: Inject<T>) {
+        self.map.insert(TypeId::of::<Inject<T>>(), Rc::new(value));
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+}
+
+/// Handle a request with `extensions` available to its tool handlers. `start` creates the future
+/// that handles the request, e.g. by calling the service.
+pub fn scope<F: Future>(
+    extensions: Extensions,
+    start: impl FnOnce() -> F,
+) -> TaskLocalFuture<Extensions, F> {
+    let future = REQUEST.sync_scope(extensions.clone(), start);
+    REQUEST.scope(extensions, future)
+}
+
 /// A trait to go from a Context to a type T.
 ///
 /// Implementing this for a type allows it to be directly injected into tool
@@ -47,14 +91,13 @@ pub trait FromContext {
 /// # use mcp_server::context::Inject;
 /// # use mcp_macros::tool;
 /// # use mcp_core::ToolError;
-/// struct MyState { counter: i32 }
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// struct MyState { counter: AtomicI32 }
 ///
 /// #[tool]
-/// async fn my_tool(my_state: Inject<MyState>) -> Result<(), ToolError> {
-///    // MyState is injected from the MCPServer's context
-///   my_state.counter += 1;
-///   println!("{}", my_state.counter);
-///   Ok(())
+/// async fn my_tool(my_state: Inject<MyState>) -> Result<i32, ToolError> {
+///     // MyState is injected from the MCPServer's context
+///     Ok(my_state.counter.fetch_add(1, Ordering::SeqCst) + 1)
 /// }
 /// ```
 pub struct Inject<T: ?Sized>(Arc<T>);
@@ -109,11 +152,15 @@ where
     }
 }
 
-/// Implement ability to get a Inject<T> from the server's context
+/// Implement ability to get a Inject<T> from the request's extensions or the server's context
 impl<T: 'static> FromContext for Inject<T> {
     fn from_context(ctx: &Context) -> Self {
-        if let Some(obj) = ctx.get::<Inject<T>>() {
-            obj.clone()
+        let scoped = REQUEST
+            .try_with(|extensions| extensions.get::<Inject<T>>().cloned())
+            .ok()
+            .flatten();
+        if let Some(obj) = scoped.or_else(|| ctx.get::<Inject<T>>().cloned()) {
+            obj
         } else {
             panic!("Tried to inject an object not in the MCPServer's state!")
         }
@@ -1,3 +1,4 @@
+use mcp_core::protocol::{ErrorData, JsonRpcMessage, MessageId, INVALID_REQUEST};
 use thiserror::Error;
 
 pub type BoxError = Box<dyn std::error::Error + Sync + Send>;
@@ -18,6 +19,47 @@ pub enum TransportError {
 
     #[error("Invalid message format: {0}")]
     InvalidMessage(String),
+
+    /// A message, or an empty batch, the server answers with a single INVALID_REQUEST error.
+    #[error("Invalid request: {}", .0.reason)]
+    InvalidRequest(InvalidRequest),
+
+    /// A batch with invalid elements. The valid `messages` are still handled, and each of
+    /// `invalid` gets its own INVALID_REQUEST error in the batch's response.
+    #[error("Batch with {} invalid element(s)", invalid.len())]
+    InvalidBatch {
+        messages: Vec<JsonRpcMessage>,
+        invalid: Vec<InvalidRequest>,
+    },
+}
+
+/// A message that isn't valid JSON-RPC. `id` is the message's own, if it has one we can read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidRequest {
+    pub id: Option<MessageId>,
+    pub reason: String,
+}
+
+impl InvalidRequest {
+    /// The INVALID_REQUEST error answering the message, with an `id` of null if it has none.
+    pub fn response(&self) -> serde_json::Value {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": self.id,
+            "error": ErrorData {
+                code: INVALID_REQUEST,
+                message: self.reason.clone(),
+                data: None,
+            },
+        })
+    }
+}
+
+/// Why `MCPServerBuilder::build` refused to build a server.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    #[error("Tool {0} has a max_concurrency of 0, which would refuse every call")]
+    ZeroMaxConcurrency(String),
 }
 
 #[derive(Error, Debug)]
@@ -57,6 +99,58 @@ pub enum RouterError {
 
     #[error("This implementation doesn't support message type: {0}")]
     Unsupported(String),
+
+    /// An error to answer with as is, e.g. one passed on from another server.
+    #[error("Error {}: {}", .0.code, .0.message)]
+    Rpc(mcp_core::protocol::ErrorData),
+}
+
+/// Why a request was refused in the current session state.
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Server not initialized")]
+    NotInitialized,
+
+    #[error("Server already initialized")]
+    AlreadyInitialized,
+
+    #[error("Server is shutting down")]
+    ShuttingDown,
+}
+
+impl From<SessionError> for mcp_core::protocol::ErrorData {
+    fn from(err: SessionError) -> Self {
+        mcp_core::protocol::ErrorData {
+            code: mcp_core::protocol::INVALID_REQUEST,
+            message: err.to_string(),
+            data: None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PeerError {
+    #[error("Not connected to a client")]
+    NotConnected,
+
+    #[error("Connection closed before the client responded")]
+    ConnectionClosed,
+
+    #[error("Client did not declare the {0} capability")]
+    MissingCapability(&'static str),
+
+    #[error("Client returned an error: code={}, message={}", .0.code, .0.message)]
+    Rpc(mcp_core::protocol::ErrorData),
+
+    #[error("JSON serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// Lets tool handlers use `?` on peer calls
+impl From<PeerError> for mcp_core::ToolError {
+    fn from(err: PeerError) -> Self {
+        mcp_core::ToolError::ExecutionError(err.to_string())
+    }
 }
 
 impl From<RouterError> for mcp_core::protocol::ErrorData {
@@ -98,6 +192,7 @@ impl From<RouterError> for mcp_core::protocol::ErrorData {
                 message: msg,
                 data: None,
             },
+            RouterError::Rpc(error) => error,
         }
     }
 }
@@ -1,24 +1,44 @@
 use std::{
     pin::Pin,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::{Future, Stream};
 use mcp_core::{
-    protocol::{JsonRpcRequest, JsonRpcResponse},
-    transport::SendableMessage,
+    protocol::{JsonRpcMessage, JsonRpcRequest, JsonRpcResponse},
+    transport::{Duplex, SendableMessage},
 };
 use pin_project::pin_project;
+use serde::Serialize;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tower_service::Service;
 
+use crate::auth::Principal;
+use crate::context::{Extensions, Inject};
+
+// Lets `#[tool]`, which refers to `mcp_server`, be used in this crate's tests
+#[cfg(test)]
+extern crate self as mcp_server;
+
+pub mod auth;
+#[cfg(test)]
+mod conformance;
 pub mod context;
 mod errors;
-pub use errors::{BoxError, RouterError, ServerError, TransportError};
+pub use errors::{
+    BoxError, BuildError, InvalidRequest, PeerError, RouterError, ServerError, SessionError,
+    TransportError,
+};
+pub mod peer;
+pub use peer::Peer;
 pub mod router;
-pub use router::Router;
+pub use router::{ArgumentValidation, Router};
 pub mod server;
 pub use server::MCPServer;
+pub mod session;
+pub use session::{Session, SessionState};
 
 // TODO: Rethink the pins
 /// A transport layer that handles JSON-RPC messages over byte
@@ -31,6 +51,9 @@ pub struct ByteTransport<R, W> {
     reader: BufReader<R>,
     #[pin]
     writer: W,
+    // Bytes of the line currently being read. Kept across poll calls so a partially received line
+    // isn't lost when the read returns Pending.
+    buf: Vec<u8>,
 }
 
 impl<R, W> ByteTransport<R, W>
@@ -45,56 +68,94 @@ where
             // allows the buffer to have the capacity to read very large calls
             reader: BufReader::with_capacity(2 * 1024 * 1024, reader),
             writer,
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Parse a line's JSON as a single message or a batch of them. An empty batch, or a message that
+/// isn't valid JSON-RPC, is an `InvalidRequest`. A batch with invalid elements is an
+/// `InvalidBatch`, so its valid elements can still be handled.
+fn parse_message(value: serde_json::Value) -> Result<JsonRpcMessage, TransportError> {
+    match value {
+        serde_json::Value::Array(batch) if batch.is_empty() => {
+            Err(TransportError::InvalidRequest(InvalidRequest {
+                id: None,
+                reason: "Batch must not be empty".into(),
+            }))
+        }
+        serde_json::Value::Array(batch) => {
+            let (mut messages, mut invalid) = (Vec::new(), Vec::new());
+            for element in batch {
+                match parse_single(element) {
+                    Ok(message) => messages.push(message),
+                    Err(e) => invalid.push(e),
+                }
+            }
+            if invalid.is_empty() {
+                Ok(JsonRpcMessage::Batch(messages))
+            } else {
+                Err(TransportError::InvalidBatch { messages, invalid })
+            }
         }
+        value => parse_single(value).map_err(TransportError::InvalidRequest),
     }
 }
 
+/// Parse a message that isn't a batch, checking its jsonrpc version.
+fn parse_single(value: serde_json::Value) -> Result<JsonRpcMessage, InvalidRequest> {
+    let invalid = |reason: String| InvalidRequest {
+        id: value
+            .get("id")
+            .and_then(|id| serde_json::from_value(id.clone()).ok()),
+        reason,
+    };
+    match &value {
+        serde_json::Value::Object(obj) => {
+            if obj.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+                return Err(invalid("Missing or invalid jsonrpc version".into()));
+            }
+        }
+        serde_json::Value::Array(_) => return Err(invalid("Batches must not be nested".into())),
+        _ => {
+            return Err(invalid(
+                "Message must be a JSON object or batch array".into(),
+            ))
+        }
+    }
+    let invalid = invalid(String::new());
+    serde_json::from_value(value).map_err(|e| InvalidRequest {
+        reason: format!("Not a JSON-RPC message: {e}"),
+        ..invalid
+    })
+}
+
 // TODO: Assess this code.
 impl<R, W> Stream for ByteTransport<R, W>
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
 {
-    type Item = Result<SendableMessage, TransportError>;
+    type Item = Result<JsonRpcMessage, TransportError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut this = self.project();
-        let mut buf = Vec::new();
 
         let mut reader = this.reader.as_mut();
-        let mut read_future = Box::pin(reader.read_until(b'\n', &mut buf));
-        match read_future.as_mut().poll(cx) {
-            Poll::Ready(Ok(0)) => Poll::Ready(None), // EOF
+        let poll = Box::pin(reader.read_until(b'\n', this.buf))
+            .as_mut()
+            .poll(cx);
+        match poll {
+            Poll::Ready(Ok(0)) if this.buf.is_empty() => Poll::Ready(None), // EOF
             Poll::Ready(Ok(_)) => {
                 // Convert to UTF-8 string
-                let line = match String::from_utf8(buf) {
+                let line = match String::from_utf8(std::mem::take(this.buf)) {
                     Ok(s) => s,
                     Err(e) => return Poll::Ready(Some(Err(TransportError::Utf8(e)))),
                 };
                 // Parse JSON and validate message format
                 match serde_json::from_str::<serde_json::Value>(&line) {
-                    Ok(value) => {
-                        // Validate basic JSON-RPC structure
-                        if !value.is_object() {
-                            return Poll::Ready(Some(Err(TransportError::InvalidMessage(
-                                "Message must be a JSON object".into(),
-                            ))));
-                        }
-                        let obj = value.as_object().unwrap(); // Safe due to check above
-
-                        // Check jsonrpc version field
-                        if !obj.contains_key("jsonrpc") || obj["jsonrpc"] != "2.0" {
-                            return Poll::Ready(Some(Err(TransportError::InvalidMessage(
-                                "Missing or invalid jsonrpc version".into(),
-                            ))));
-                        }
-
-                        // Now try to parse as proper message
-                        match serde_json::from_value::<SendableMessage>(value) {
-                            Ok(msg) => Poll::Ready(Some(Ok(msg))),
-                            Err(e) => Poll::Ready(Some(Err(TransportError::Json(e)))),
-                        }
-                    }
+                    Ok(value) => Poll::Ready(Some(parse_message(value))),
                     Err(e) => Poll::Ready(Some(Err(TransportError::Json(e)))),
                 }
             }
@@ -109,7 +170,8 @@ where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
 {
-    pub async fn write_message(&mut self, msg: JsonRpcResponse) -> Result<(), std::io::Error> {
+    /// Write a single message (or any other serializable JSON-RPC payload) as one line.
+    pub async fn write_message<M: Serialize>(&mut self, msg: M) -> Result<(), std::io::Error> {
         let json = serde_json::to_string(&msg)?;
         Pin::new(&mut self.writer)
             .write_all(json.as_bytes())
@@ -118,11 +180,114 @@ where
         Pin::new(&mut self.writer).flush().await?;
         Ok(())
     }
+
+    /// Flush anything still buffered in the writer.
+    pub async fn flush(&mut self) -> Result<(), std::io::Error> {
+        Pin::new(&mut self.writer).flush().await
+    }
+}
+
+/// A connection the server reads messages from and writes its responses and requests to.
+pub trait ServerTransport: Stream<Item = Result<JsonRpcMessage, TransportError>> + Unpin {
+    /// Write a single message (or batch) to the client.
+    fn write_message<M: Serialize>(
+        &mut self,
+        msg: M,
+    ) -> impl Future<Output = Result<(), std::io::Error>>;
+
+    /// Flush anything still buffered for the client.
+    fn flush(&mut self) -> impl Future<Output = Result<(), std::io::Error>>;
+}
+
+impl<R, W> ServerTransport for ByteTransport<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    async fn write_message<M: Serialize>(&mut self, msg: M) -> Result<(), std::io::Error> {
+        ByteTransport::write_message(self, msg).await
+    }
+
+    async fn flush(&mut self) -> Result<(), std::io::Error> {
+        ByteTransport::flush(self).await
+    }
+}
+
+/// A transport to a client in the same process, over one end of `mcp_core::transport::duplex`.
+/// Messages are passed as they are rather than written out as lines.
+///
+/// ```
+/// # use mcp_server::{router::RouterService, MemoryTransport, Router, Server};
+/// # async fn serve(router: impl Router + Clone) {
+/// let (client_end, server_end) = mcp_core::transport::duplex();
+/// // Hand `client_end` to `mcp_client::MemoryTransport::new`
+/// # drop(client_end);
+/// Server::new(RouterService::new(router))
+///     .run(MemoryTransport::new(server_end))
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct MemoryTransport {
+    duplex: Duplex,
+}
+
+impl MemoryTransport {
+    pub fn new(duplex: Duplex) -> Self {
+        Self { duplex }
+    }
+}
+
+impl Stream for MemoryTransport {
+    type Item = Result<JsonRpcMessage, TransportError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.duplex.poll_recv(cx).map(|message| message.map(Ok))
+    }
+}
+
+impl ServerTransport for MemoryTransport {
+    async fn write_message<M: Serialize>(&mut self, msg: M) -> Result<(), std::io::Error> {
+        let message = serde_json::to_value(msg).and_then(serde_json::from_value)?;
+        self.duplex.send(message).await.map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Client end was dropped")
+        })
+    }
+
+    async fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// How long `Server::run_until` waits for in-flight requests by default once it stops reading.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why the server stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// The client closed its end of the transport.
+    Disconnected,
+    /// The shutdown future passed to `Server::run_until` completed.
+    Requested,
+}
+
+/// What happened to the work the server had in progress when it stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    pub reason: ShutdownReason,
+    /// Requests that finished (and were answered) after the server stopped reading. A batch counts
+    /// as one request.
+    pub completed: usize,
+    /// Requests still running when the shutdown timeout expired. They were dropped unanswered.
+    pub abandoned: usize,
 }
 
 /// The main server type that processes incoming requests
 pub struct Server<S> {
     service: S,
+    peer: Option<Peer>,
+    shutdown_timeout: Duration,
+    extensions: Extensions,
 }
 
 fn trace_log_request(request: &JsonRpcRequest) {
@@ -136,7 +301,7 @@ fn trace_log_request(request: &JsonRpcRequest) {
     );
 }
 
-fn trace_log_response(response: &Option<JsonRpcResponse>) {
+fn trace_log_response<M: Serialize>(response: &M) {
     let response_json = serde_json::to_string(&response)
         .unwrap_or_else(|_| "Failed to serialize response".to_string());
     tracing::debug!(
@@ -151,71 +316,680 @@ where
     S::Error: Into<BoxError>,
 {
     pub fn new(service: S) -> Self {
-        Self { service }
+        Self {
+            service,
+            peer: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            extensions: Extensions::new(),
+        }
     }
 
-    // TODO transport trait instead of byte transport if we implement others
-    pub async fn run<R, W>(self, mut transport: ByteTransport<R, W>) -> Result<(), ServerError>
+    /// Set how long in-flight requests may keep running once the server stops reading.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Connect `peer` to the client while the server runs, so requests sent through it (for
+    /// example by tool handlers) reach the client and its responses are routed back.
+    pub fn with_peer(mut self, peer: Peer) -> Self {
+        self.peer = Some(peer);
+        self
+    }
+
+    /// Handle every request of the connection as `principal`, e.g. when the transport was
+    /// authenticated once as it connected. Tool handlers can take it as `Inject<Principal>`.
+    pub fn with_principal(mut self, principal: Principal) -> Self {
+        self.extensions.insert(Inject::new(principal));
+        self
+    }
+
+    pub async fn run<T>(self, transport: T) -> Result<(), ServerError>
+    where
+        T: ServerTransport,
+    {
+        self.run_until(transport, futures::future::pending())
+            .await
+            .map(|_| ())
+    }
+
+    /// Run the server until the client disconnects or `shutdown` completes, whichever is first.
+    ///
+    /// Either way the server stops reading, gives in-flight requests up to the shutdown timeout to
+    /// finish and sends their responses, then flushes the writer.
+    ///
+    /// ```no_run
+    /// # use mcp_server::{ByteTransport, Server, BoxError};
+    /// # async fn serve<S>(server: Server<S>) -> Result<(), BoxError>
+    /// # where
+    /// #     S: tower_service::Service<
+    /// #         mcp_core::transport::SendableMessage,
+    /// #         Response = Option<mcp_core::protocol::JsonRpcResponse>,
+    /// #     >,
+    /// #     S::Error: Into<BoxError>,
+    /// # {
+    /// use tokio::signal::unix::{signal, SignalKind};
+    ///
+    /// let mut sigterm = signal(SignalKind::terminate())?;
+    /// let transport = ByteTransport::new(tokio::io::stdin(), tokio::io::stdout());
+    /// let summary = server
+    ///     .run_until(transport, async move {
+    ///         sigterm.recv().await;
+    ///     })
+    ///     .await?;
+    /// tracing::info!(?summary, "Server stopped");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_until<T, F>(
+        self,
+        mut transport: T,
+        shutdown: F,
+    ) -> Result<ShutdownSummary, ServerError>
     where
-        R: AsyncRead + Unpin,
-        W: AsyncWrite + Unpin,
+        T: ServerTransport,
+        F: Future<Output = ()>,
     {
-        use futures::StreamExt;
+        use futures::{
+            future::{join_all, Either, FutureExt},
+            stream::{FuturesUnordered, StreamExt},
+        };
         let mut service = self.service;
+        let peer = self.peer.unwrap_or_default();
+        let mut outgoing = peer.connect();
+        let session = Arc::new(Mutex::new(Session::new()));
+
+        // Requests are processed concurrently, so a handler waiting on the client (e.g. for an
+        // elicitation) doesn't block reading the client's response.
+        let mut in_flight = FuturesUnordered::new();
+
+        tokio::pin!(shutdown);
 
         tracing::info!("Server started");
-        while let Some(msg_result) = transport.next().await {
-            // TODO: This tracing is incorrect for async code.
-            let _span = tracing::span!(tracing::Level::INFO, "message_processing");
-            let _enter = _span.enter();
-            match msg_result {
-                Ok(SendableMessage::Request(request)) => {
-                    let id = request.id.clone();
-                    // TODO: Remove after testing
-                    trace_log_request(&request);
-
-                    // Process the request using our service. Respond with the response from
-                    // the service, or an error response if the call fails.
-                    let response = match service.call(SendableMessage::from(request)).await {
-                        Ok(resp) => resp,
+        let reason = loop {
+            tokio::select! {
+                _ = &mut shutdown => {
+                    tracing::info!("Shutdown requested");
+                    break ShutdownReason::Requested;
+                }
+                msg_result = transport.next() => {
+                    let Some(msg_result) = msg_result else {
+                        break ShutdownReason::Disconnected;
+                    };
+                    let (messages, invalid) = match msg_result {
+                        Ok(JsonRpcMessage::Batch(messages)) => (messages, Vec::new()),
+                        Err(TransportError::InvalidBatch { messages, invalid }) => {
+                            tracing::debug!(invalid = invalid.len(), "Batch with invalid elements");
+                            (messages, invalid)
+                        }
+                        Ok(message) => {
+                            if let Some(request) =
+                                Self::dispatch(&mut service, &peer, &session, &self.extensions, message)
+                            {
+                                in_flight.push(Either::Left(request.map(Reply::Single)));
+                            }
+                            continue;
+                        }
+                        Err(TransportError::InvalidRequest(invalid)) => {
+                            tracing::debug!(reason = %invalid.reason, "Invalid request");
+                            Self::write_reply(&mut transport, Reply::Invalid(invalid)).await?;
+                            continue;
+                        }
                         Err(e) => {
-                            let error_msg = e.into().to_string();
-                            tracing::debug!(error = %error_msg, "Request processing failed");
-                            Some(JsonRpcResponse::Error {
-                                jsonrpc: "2.0".to_string(),
-                                id,
-                                error: mcp_core::protocol::ErrorData {
-                                    code: mcp_core::protocol::INTERNAL_ERROR,
-                                    message: error_msg,
-                                    data: None,
-                                },
-                            })
+                            // Other transport errors are just logged. No response is sent to the
+                            // client.
+                            tracing::error!(error = ?e, "Transport error");
+                            continue;
                         }
                     };
+                    let requests: Vec<_> = messages
+                        .into_iter()
+                        .filter_map(|message| {
+                            Self::dispatch(&mut service, &peer, &session, &self.extensions, message)
+                        })
+                        .collect();
+                    // A batch of only notifications and responses gets no reply
+                    if !requests.is_empty() || !invalid.is_empty() {
+                        in_flight.push(Either::Right(
+                            join_all(requests).map(move |responses| Reply::Batch(responses, invalid)),
+                        ));
+                    }
+                }
+                Some(reply) = in_flight.next(), if !in_flight.is_empty() => {
+                    Self::write_reply(&mut transport, reply).await?;
+                }
+                Some(message) = outgoing.recv() => {
+                    transport
+                        .write_message(message)
+                        .await
+                        .map_err(|e| ServerError::Transport(TransportError::Io(e)))?;
+                }
+            }
+        };
 
-                    // TODO: Remove after testing
-                    trace_log_response(&response);
+        // We've stopped reading, so nothing sent to the client can be answered any more. Send the
+        // notifications already queued, fail anything waiting on the client, then let the
+        // remaining requests finish.
+        session.lock().unwrap().shut_down();
+        while let Ok(message) = outgoing.try_recv() {
+            if let SendableMessage::Notification(_) = message {
+                transport
+                    .write_message(message)
+                    .await
+                    .map_err(|e| ServerError::Transport(TransportError::Io(e)))?;
+            }
+        }
+        peer.disconnect();
+        let mut completed = 0;
+        // The deadline only bounds waiting for a reply: once one is being written it is finished,
+        // so the client never gets a truncated message.
+        let deadline = tokio::time::sleep(self.shutdown_timeout);
+        tokio::pin!(deadline);
+        loop {
+            let reply = tokio::select! {
+                reply = in_flight.next() => match reply {
+                    Some(reply) => reply,
+                    None => break,
+                },
+                _ = &mut deadline => {
+                    tracing::warn!(
+                        abandoned = in_flight.len(),
+                        "Shutdown timeout expired with requests still in flight"
+                    );
+                    break;
+                }
+            };
+            Self::write_reply(&mut transport, reply).await?;
+            completed += 1;
+        }
+        transport
+            .flush()
+            .await
+            .map_err(|e| ServerError::Transport(TransportError::Io(e)))?;
 
-                    // Send the message over the transport
-                    // TODO: Swap JsonRpcMessage for a transport-level abstraction
-                    if let Some(response) = response {
-                        transport
-                            .write_message(response)
-                            .await
-                            .map_err(|e| ServerError::Transport(TransportError::Io(e)))?;
-                    }
+        let summary = ShutdownSummary {
+            reason,
+            completed,
+            abandoned: in_flight.len(),
+        };
+        tracing::info!(?summary, "Server stopped");
+        Ok(summary)
+    }
+
+    /// Route a single incoming message. Requests (and notifications) are handed to the service,
+    /// and the future resolving to their response is returned. Requests the session doesn't allow
+    /// in its current state are answered with an error.
+    fn dispatch(
+        service: &mut S,
+        peer: &Peer,
+        session: &Arc<Mutex<Session>>,
+        extensions: &Extensions,
+        message: JsonRpcMessage,
+    ) -> Option<impl Future<Output = Option<JsonRpcResponse>>> {
+        use futures::future::{ready, Either, FutureExt};
+
+        let message = match message {
+            JsonRpcMessage::Request(request) => {
+                // TODO: Remove after testing
+                trace_log_request(&request);
+                let allowed = session.lock().unwrap().on_request(&request.method);
+                if let Err(e) = allowed {
+                    tracing::debug!(error = %e, method = %request.method, "Request refused");
+                    let response = JsonRpcResponse::error(request.id, e.into());
+                    return Some(Either::Left(ready(Some(response))));
                 }
-                Ok(SendableMessage::Notification(_)) => {
-                    // Ignore notifications for now
-                    continue;
+                if request.method == "initialize" {
+                    // Kept so the peer only sends the client requests it declared support for
+                    let capabilities = request
+                        .params
+                        .as_ref()
+                        .and_then(|params| params.get("capabilities"))
+                        .and_then(|capabilities| serde_json::from_value(capabilities.clone()).ok())
+                        .unwrap_or_default();
+                    peer.set_client_capabilities(capabilities);
                 }
-                Err(e) => {
-                    // Transport errors are just logged. No response is sent to the client.
-                    tracing::error!(error = ?e, "Transport error");
+                SendableMessage::from(request)
+            }
+            JsonRpcMessage::Response(response) => {
+                // Responses answer requests the server sent through its peer
+                peer.handle_response(response);
+                return None;
+            }
+            JsonRpcMessage::Notification(notification) => {
+                // The initialized notification is only passed on once, when it completes the
+                // handshake
+                if notification.method == "notifications/initialized"
+                    && !session
+                        .lock()
+                        .unwrap()
+                        .on_notification(&notification.method)
+                {
+                    return None;
+                }
+                SendableMessage::from(notification)
+            }
+            JsonRpcMessage::Batch(_) => {
+                tracing::error!("Ignoring nested batch");
+                return None;
+            }
+        };
+        // The session advances once initialize has succeeded
+        let initialize =
+            matches!(&message, SendableMessage::Request(request) if request.method == "initialize");
+        let session = session.clone();
+        let response = context::scope(extensions.clone(), || {
+            Self::process_message(service, message)
+        });
+        Some(Either::Right(response.map(move |response| {
+            if let (true, Some(response)) = (initialize, &response) {
+                session.lock().unwrap().on_initialize_response(response);
+            }
+            response
+        })))
+    }
+
+    /// Process the message using our service. Respond with the response from the service, or an
+    /// error response if a request fails. Notifications never get a response.
+    fn process_message(
+        service: &mut S,
+        message: SendableMessage,
+    ) -> impl Future<Output = Option<JsonRpcResponse>> {
+        let id = match &message {
+            SendableMessage::Request(request) => Some(request.id.clone()),
+            SendableMessage::Notification(_) => None,
+        };
+        let response = service.call(message);
+        async move {
+            match (response.await, id) {
+                (Ok(resp), _) => resp,
+                (Err(e), Some(id)) => {
+                    let error_msg = e.into().to_string();
+                    tracing::debug!(error = %error_msg, "Request processing failed");
+                    Some(JsonRpcResponse::Error {
+                        jsonrpc: "2.0".to_string(),
+                        id,
+                        error: mcp_core::protocol::ErrorData {
+                            code: mcp_core::protocol::INTERNAL_ERROR,
+                            message: error_msg,
+                            data: None,
+                        },
+                    })
+                }
+                (Err(e), None) => {
+                    tracing::debug!(error = %e.into(), "Notification processing failed");
+                    None
                 }
             }
         }
+    }
 
-        Ok(())
+    async fn write_reply<T: ServerTransport>(
+        transport: &mut T,
+        reply: Reply,
+    ) -> Result<(), ServerError> {
+        let result = match reply {
+            Reply::Single(response) => {
+                // TODO: Remove after testing
+                trace_log_response(&response);
+                match response {
+                    Some(response) => transport.write_message(response).await,
+                    None => Ok(()),
+                }
+            }
+            Reply::Invalid(invalid) => transport.write_message(invalid.response()).await,
+            Reply::Batch(responses, invalid) => {
+                // Responses are collected into a single array, followed by an error for each
+                // invalid element. Requests which produced no response are left out, and if
+                // nothing is left, nothing is sent.
+                let responses: Vec<_> = responses
+                    .into_iter()
+                    .flatten()
+                    .map(|response| serde_json::json!(response))
+                    .chain(invalid.iter().map(InvalidRequest::response))
+                    .collect();
+                trace_log_response(&responses);
+                if responses.is_empty() {
+                    Ok(())
+                } else {
+                    transport.write_message(responses).await
+                }
+            }
+        };
+
+        result.map_err(|e| ServerError::Transport(TransportError::Io(e)))
+    }
+}
+
+/// The reply to a single request, to a message that isn't valid, or to all the elements of a
+/// batch.
+enum Reply {
+    Single(Option<JsonRpcResponse>),
+    Invalid(InvalidRequest),
+    Batch(Vec<Option<JsonRpcResponse>>, Vec<InvalidRequest>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::MessageId;
+    use serde_json::{json, Value};
+    use tokio::io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    /// Responds to every request with its method name, after sleeping for `params.sleep_ms` if set
+    struct EchoService;
+
+    impl Service<SendableMessage> for EchoService {
+        type Response = Option<JsonRpcResponse>;
+        type Error = BoxError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: SendableMessage) -> Self::Future {
+            Box::pin(async move {
+                match req {
+                    SendableMessage::Request(req) => {
+                        if let Some(ms) = req.params.as_ref().and_then(|p| p["sleep_ms"].as_u64()) {
+                            tokio::time::sleep(Duration::from_millis(ms)).await;
+                        }
+                        Ok(Some(JsonRpcResponse::success(
+                            req.id,
+                            Value::String(req.method),
+                        )))
+                    }
+                    SendableMessage::Notification(_) => Ok(None),
+                }
+            })
+        }
+    }
+
+    /// Send initialize, and wait for its response.
+    async fn initialize(
+        client_write: &mut (impl AsyncWrite + Unpin),
+        lines: &mut tokio::io::Lines<impl tokio::io::AsyncBufRead + Unpin>,
+    ) {
+        client_write
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":0,\"method\":\"initialize\"}\n")
+            .await
+            .unwrap();
+        let response = lines.next_line().await.unwrap().unwrap();
+        assert!(response.contains("\"result\":\"initialize\""), "{response}");
+    }
+
+    async fn exchange(input: &str) -> Vec<Value> {
+        let (client, server) = duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (client_read, mut client_write) = tokio::io::split(client);
+
+        let server = Server::new(EchoService).run(ByteTransport::new(server_read, server_write));
+        let client = async move {
+            let mut lines = BufReader::new(client_read).lines();
+            initialize(&mut client_write, &mut lines).await;
+            client_write.write_all(input.as_bytes()).await.unwrap();
+            client_write.shutdown().await.unwrap();
+            let mut received = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                received.push(serde_json::from_str(&line).unwrap());
+            }
+            received
+        };
+
+        let (result, received) = tokio::join!(server, client);
+        result.unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn test_batch_responses_exclude_notifications() {
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "tools/list"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": "two", "method": "prompts/list"},
+        ]);
+        let received = exchange(&format!("{batch}\n")).await;
+
+        assert_eq!(received.len(), 1);
+        let responses: Vec<JsonRpcResponse> = serde_json::from_value(received[0].clone()).unwrap();
+        assert_eq!(
+            responses,
+            vec![
+                JsonRpcResponse::success(MessageId::Num(1), json!("tools/list")),
+                JsonRpcResponse::success(MessageId::Str("two".into()), json!("prompts/list")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_of_notifications_gets_no_reply() {
+        let batch = json!([{"jsonrpc": "2.0", "method": "notifications/initialized"}]);
+        let received = exchange(&format!("{batch}\n")).await;
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_second_initialize_is_refused() {
+        let received = exchange("{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\"}\n").await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0]["error"]["code"],
+            mcp_core::protocol::INVALID_REQUEST
+        );
+        assert_eq!(
+            received[0]["error"]["message"],
+            "Server already initialized"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_pipelined_after_initialize_are_refused() {
+        let (client, server) = duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (client_read, mut client_write) = tokio::io::split(client);
+
+        let server = Server::new(EchoService).run(ByteTransport::new(server_read, server_write));
+        let client = async move {
+            // Nothing waits for the first initialize to be answered
+            let initialize = json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"sleep_ms": 20}});
+            let list = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+            let again = json!({"jsonrpc": "2.0", "id": 2, "method": "initialize"});
+            client_write
+                .write_all(format!("{initialize}\n{list}\n{again}\n").as_bytes())
+                .await
+                .unwrap();
+            client_write.shutdown().await.unwrap();
+            let mut lines = BufReader::new(client_read).lines();
+            let mut received: Vec<Value> = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                received.push(serde_json::from_str(&line).unwrap());
+            }
+            received.sort_by_key(|response| response["id"].as_u64());
+            received
+        };
+
+        let (result, received) = tokio::join!(server, client);
+        result.unwrap();
+        assert_eq!(received[0]["result"], "initialize");
+        assert_eq!(received[1]["error"]["message"], "Server not initialized");
+        assert_eq!(
+            received[2]["error"]["message"],
+            "Server already initialized"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch_is_rejected() {
+        let received = exchange("[]\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n").await;
+        assert_eq!(
+            received,
+            vec![
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": {"code": -32600, "message": "Batch must not be empty"},
+                }),
+                json!({"jsonrpc": "2.0", "id": 1, "result": "ping"}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_batch_elements_get_their_own_errors() {
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "tools/list"},
+            {"jsonrpc": "1.0", "id": 2, "method": "tools/list"},
+            42,
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+        ]);
+        let received = exchange(&format!("{batch}\n")).await;
+        assert_eq!(
+            received,
+            vec![json!([
+                {"jsonrpc": "2.0", "id": 1, "result": "tools/list"},
+                {
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "error": {"code": -32600, "message": "Missing or invalid jsonrpc version"},
+                },
+                {
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": {
+                        "code": -32600,
+                        "message": "Message must be a JSON object or batch array",
+                    },
+                },
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_transport() {
+        let (mut client, server) = mcp_core::transport::duplex();
+        let server = Server::new(EchoService).run(MemoryTransport::new(server));
+        let client = async move {
+            for (id, method) in [(0, "initialize"), (1, "ping")] {
+                let request = JsonRpcRequest::new(MessageId::Num(id), method.to_string(), None);
+                client.send(request.into()).await.unwrap();
+                let Some(JsonRpcMessage::Response(response)) = client.recv().await else {
+                    panic!("Expected a response");
+                };
+                assert_eq!(
+                    response,
+                    JsonRpcResponse::success(MessageId::Num(id), json!(method))
+                );
+            }
+            // Dropping the client end stops the server
+        };
+
+        let (result, ()) = tokio::join!(server, client);
+        result.unwrap();
+    }
+
+    /// Start a request that sleeps for `sleep_ms`, then shut the server down while it runs.
+    async fn shutdown_during_request(
+        sleep_ms: u64,
+        timeout: Duration,
+    ) -> (ShutdownSummary, Vec<Value>) {
+        let (client, server) = duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (client_read, mut client_write) = tokio::io::split(client);
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = Server::new(EchoService)
+            .with_shutdown_timeout(timeout)
+            .run_until(ByteTransport::new(server_read, server_write), async {
+                let _ = shutdown_rx.await;
+            });
+        let client = async move {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "slow", "params": {"sleep_ms": sleep_ms}});
+            let mut lines = BufReader::new(client_read).lines();
+            initialize(&mut client_write, &mut lines).await;
+            client_write
+                .write_all(
+                    format!("{request}\n{{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}}\n")
+                        .as_bytes(),
+                )
+                .await
+                .unwrap();
+            lines.next_line().await.unwrap();
+            // The slow request is in flight once the ping sent after it has been answered
+            shutdown_tx.send(()).unwrap();
+            let mut received = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                received.push(serde_json::from_str(&line).unwrap());
+            }
+            // Keep the client's write half open, so the server can only stop through shutdown
+            drop(client_write);
+            received
+        };
+
+        let (summary, received) = tokio::join!(server, client);
+        (summary.unwrap(), received)
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_in_flight_requests() {
+        let (summary, received) = shutdown_during_request(50, Duration::from_secs(5)).await;
+        assert_eq!(
+            summary,
+            ShutdownSummary {
+                reason: ShutdownReason::Requested,
+                completed: 1,
+                abandoned: 0,
+            }
+        );
+        assert_eq!(
+            received,
+            vec![json!({"jsonrpc": "2.0", "id": 1, "result": "slow"})]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_sends_queued_notifications() {
+        let (client, server) = duplex(4096);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (client_read, _client_write) = tokio::io::split(client);
+
+        let peer = Peer::new();
+        let server = Server::new(EchoService).with_peer(peer.clone()).run_until(
+            ByteTransport::new(server_read, server_write),
+            async {
+                peer.send_notification("notifications/message", Some(json!({"data": "bye"})))
+                    .unwrap();
+            },
+        );
+        let client = async move {
+            let mut lines = BufReader::new(client_read).lines();
+            let mut received = Vec::<Value>::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                received.push(serde_json::from_str(&line).unwrap());
+            }
+            received
+        };
+
+        let (summary, received) = tokio::join!(server, client);
+        assert_eq!(summary.unwrap().reason, ShutdownReason::Requested);
+        assert_eq!(
+            received,
+            vec![
+                json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {"data": "bye"}})
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_abandons_requests_after_timeout() {
+        let (summary, received) = shutdown_during_request(10_000, Duration::from_millis(20)).await;
+        assert_eq!(
+            summary,
+            ShutdownSummary {
+                reason: ShutdownReason::Requested,
+                completed: 0,
+                abandoned: 1,
+            }
+        );
+        assert!(received.is_empty());
     }
 }
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use mcp_core::{
+    protocol::{
+        ClientCapabilities, CreateElicitationRequestParams, CreateElicitationResult,
+        JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, MessageId,
+    },
+    transport::SendableMessage,
+};
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::PeerError;
+
+/// A handle to the client on the other end of the connection, used to send server-initiated
+/// requests and notifications.
+///
+/// The peer is connected when `Server::run` starts and disconnected when it returns. Sending while
+/// disconnected fails with `PeerError::NotConnected`, and requests still awaiting a response when
+/// the connection ends fail with `PeerError::ConnectionClosed`. Dropping a request before its
+/// response arrives sends `notifications/cancelled` for it.
+///
+/// `MCPServer` registers its peer in the tool context, so tool handlers can take an
+/// `Inject<Peer>` parameter to talk back to the client mid-call.
+#[derive(Clone)]
+pub struct Peer {
+    inner: Arc<PeerInner>,
+}
+
+struct PeerInner {
+    next_id: AtomicU64,
+    outgoing: Mutex<Option<mpsc::UnboundedSender<SendableMessage>>>,
+    pending: Mutex<HashMap<MessageId, oneshot::Sender<JsonRpcResponse>>>,
+    client_capabilities: Mutex<Option<ClientCapabilities>>,
+}
+
+/// Removes a request from the pending ones when its caller stops waiting, and tells the client
+/// it was cancelled if no response had arrived yet.
+struct PendingGuard<'a> {
+    inner: &'a PeerInner,
+    id: MessageId,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        let pending = self.inner.pending.lock().unwrap().remove(&self.id);
+        if pending.is_none() {
+            return;
+        }
+        let outgoing = self.inner.outgoing.lock().unwrap();
+        if let Some(sender) = outgoing.as_ref() {
+            let params = json!({ "requestId": self.id, "reason": "The request was dropped" });
+            let notification =
+                JsonRpcNotification::new("notifications/cancelled".to_string(), Some(params));
+            let _ = sender.send(notification.into());
+        }
+    }
+}
+
+impl Default for Peer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Peer {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(PeerInner {
+                next_id: AtomicU64::new(1),
+                outgoing: Mutex::new(None),
+                pending: Mutex::new(HashMap::new()),
+                client_capabilities: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Send a request to the client and wait for its result.
+    pub async fn send_request(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<Value, PeerError> {
+        let id = MessageId::Num(self.inner.next_id.fetch_add(1, Ordering::SeqCst));
+        let (tx, rx) = oneshot::channel();
+        self.inner.pending.lock().unwrap().insert(id.clone(), tx);
+        let _guard = PendingGuard {
+            inner: &self.inner,
+            id: id.clone(),
+        };
+
+        let request = JsonRpcRequest::new(id, method.to_string(), params);
+        self.send(request.into())?;
+
+        match rx.await.map_err(|_| PeerError::ConnectionClosed)? {
+            JsonRpcResponse::Success { result, .. } => Ok(result),
+            JsonRpcResponse::Error { error, .. } => Err(PeerError::Rpc(error)),
+        }
+    }
+
+    /// Send a notification to the client. Notifications are queued and do not wait for delivery.
+    pub fn send_notification(&self, method: &str, params: Option<Value>) -> Result<(), PeerError> {
+        self.send(JsonRpcNotification::new(method.to_string(), params).into())
+    }
+
+    /// Ask the user, through the client, for structured input matching `requested_schema`.
+    ///
+    /// Returns once the user accepts, declines or cancels. Fails with
+    /// `PeerError::MissingCapability` unless the client declared the `elicitation` capability.
+    pub async fn elicit<S: Into<String>>(
+        &self,
+        message: S,
+        requested_schema: Value,
+    ) -> Result<CreateElicitationResult, PeerError> {
+        let supported = self
+            .client_capabilities()
+            .is_some_and(|capabilities| capabilities.elicitation.is_some());
+        if !supported {
+            return Err(PeerError::MissingCapability("elicitation"));
+        }
+        let params = CreateElicitationRequestParams {
+            message: message.into(),
+            requested_schema,
+        };
+        let result = self
+            .send_request("elicitation/create", Some(serde_json::to_value(params)?))
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// The capabilities the client declared in its `initialize` request, once it has sent one.
+    pub fn client_capabilities(&self) -> Option<ClientCapabilities> {
+        self.inner.client_capabilities.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_client_capabilities(&self, capabilities: ClientCapabilities) {
+        *self.inner.client_capabilities.lock().unwrap() = Some(capabilities);
+    }
+
+    fn send(&self, message: SendableMessage) -> Result<(), PeerError> {
+        let outgoing = self.inner.outgoing.lock().unwrap();
+        let sender = outgoing.as_ref().ok_or(PeerError::NotConnected)?;
+        sender.send(message).map_err(|_| PeerError::NotConnected)
+    }
+
+    /// Attach the peer to a connection. Messages sent through the peer are delivered on the
+    /// returned channel, to be written to the transport by the server.
+    pub(crate) fn connect(&self) -> mpsc::UnboundedReceiver<SendableMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.inner.outgoing.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    /// Detach the peer from its connection, failing any requests still awaiting a response.
+    pub(crate) fn disconnect(&self) {
+        self.inner.outgoing.lock().unwrap().take();
+        self.inner.pending.lock().unwrap().clear();
+    }
+
+    /// Deliver a response from the client to the request waiting on it.
+    pub(crate) fn handle_response(&self, response: JsonRpcResponse) {
+        let id = match &response {
+            JsonRpcResponse::Success { id, .. } => id,
+            JsonRpcResponse::Error { id, .. } => id,
+        };
+        match self.inner.pending.lock().unwrap().remove(id) {
+            Some(tx) => {
+                let _ = tx.send(response);
+            }
+            None => tracing::warn!(id = ?id, "Received response for unknown request"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::{ElicitationAction, ElicitationCapability, ErrorData, INVALID_PARAMS};
+
+    /// A peer connected to a client that supports elicitation.
+    fn eliciting_peer() -> (Peer, mpsc::UnboundedReceiver<SendableMessage>) {
+        let peer = Peer::new();
+        peer.set_client_capabilities(ClientCapabilities {
+            elicitation: Some(ElicitationCapability::default()),
+            ..Default::default()
+        });
+        let outgoing = peer.connect();
+        (peer, outgoing)
+    }
+
+    #[tokio::test]
+    async fn test_send_while_disconnected() {
+        let peer = Peer::new();
+        let result = peer.send_request("ping", None).await;
+        assert!(matches!(result, Err(PeerError::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn test_elicit_round_trip() {
+        let (peer, mut outgoing) = eliciting_peer();
+
+        let client = peer.clone();
+        let client = async move {
+            let Some(SendableMessage::Request(request)) = outgoing.recv().await else {
+                panic!("Expected a request");
+            };
+            assert_eq!(request.method, "elicitation/create");
+            assert_eq!(request.params.as_ref().unwrap()["message"], "Deploy?");
+            client.handle_response(JsonRpcResponse::success(
+                request.id,
+                json!({ "action": "accept", "content": { "confirm": true } }),
+            ));
+        };
+
+        let (result, _) = tokio::join!(peer.elicit("Deploy?", json!({ "type": "object" })), client);
+        let result = result.unwrap();
+        assert_eq!(result.action, ElicitationAction::Accept);
+        assert_eq!(result.content, Some(json!({ "confirm": true })));
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (peer, mut outgoing) = eliciting_peer();
+
+        let client = peer.clone();
+        let client = async move {
+            let Some(SendableMessage::Request(request)) = outgoing.recv().await else {
+                panic!("Expected a request");
+            };
+            client.handle_response(JsonRpcResponse::error(
+                request.id,
+                ErrorData {
+                    code: INVALID_PARAMS,
+                    message: "bad schema".into(),
+                    data: None,
+                },
+            ));
+        };
+
+        let (result, _) = tokio::join!(peer.elicit("Deploy?", json!({})), client);
+        assert!(matches!(result, Err(PeerError::Rpc(e)) if e.code == INVALID_PARAMS));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_fails_pending_requests() {
+        let peer = Peer::new();
+        let _outgoing = peer.connect();
+
+        let disconnect = async {
+            tokio::task::yield_now().await;
+            peer.disconnect();
+        };
+        let (result, _) = tokio::join!(peer.send_request("ping", None), disconnect);
+        assert!(matches!(result, Err(PeerError::ConnectionClosed)));
+    }
+
+    #[tokio::test]
+    async fn test_elicit_requires_the_capability() {
+        let peer = Peer::new();
+        let mut outgoing = peer.connect();
+        peer.set_client_capabilities(ClientCapabilities::default());
+
+        let result = peer.elicit("Deploy?", json!({})).await;
+        assert!(matches!(
+            result,
+            Err(PeerError::MissingCapability("elicitation"))
+        ));
+        assert!(outgoing.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_is_cancelled() {
+        let peer = Peer::new();
+        let mut outgoing = peer.connect();
+
+        let request = peer.send_request("ping", None);
+        // Give up on the request once it has been sent
+        tokio::select! {
+            biased;
+            _ = request => panic!("The request can't complete"),
+            _ = std::future::ready(()) => {}
+        }
+        let Some(SendableMessage::Request(request)) = outgoing.recv().await else {
+            panic!("Expected a request");
+        };
+        let Some(SendableMessage::Notification(cancelled)) = outgoing.recv().await else {
+            panic!("Expected a notification");
+        };
+        assert_eq!(cancelled.method, "notifications/cancelled");
+        assert_eq!(cancelled.params.unwrap()["requestId"], json!(request.id));
+        assert!(peer.inner.pending.lock().unwrap().is_empty());
+    }
+}
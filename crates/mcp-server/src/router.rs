@@ -14,8 +14,9 @@ use mcp_core::{
         CallToolResult, GetPromptResult, Implementation, InitializeResult, JsonRpcRequest,
         JsonRpcResponse, ListPromptsResult, ListResourcesResult, ListToolsResult,
         PromptsCapability, ReadResourceResult, ResourcesCapability, ServerCapabilities,
-        ToolsCapability,
+        ToolsCapability, LATEST_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
     },
+    schema::{describe, validate},
     transport::SendableMessage,
     ResourceContents,
 };
@@ -82,6 +83,18 @@ impl CapabilitiesBuilder {
     }
 }
 
+/// What the router does with tool arguments that don't match the tool's input schema.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArgumentValidation {
+    /// Pass the arguments to the tool unchecked
+    #[default]
+    Off,
+    /// Reject the call with an `INVALID_PARAMS` error
+    InvalidParams,
+    /// Answer with a tool result marked `is_error`, so the model can see what was wrong and retry
+    ToolError,
+}
+
 pub trait Router: 'static {
     fn name(&self) -> String;
     // in the protocol, instructions are optional but we make it required
@@ -101,13 +114,39 @@ pub trait Router: 'static {
     fn list_prompts(&self) -> Vec<Prompt>;
     fn get_prompt(&self, prompt_name: &str) -> PromptFuture;
 
+    /// Called once the client confirms initialization with `notifications/initialized`.
+    fn on_initialized(&self) {}
+
+    /// Whether `tools/call` checks arguments against the tool's `input_schema` before calling it.
+    fn argument_validation(&self) -> ArgumentValidation {
+        ArgumentValidation::Off
+    }
+
+    /// The `input_schema` of the tool called `name`, to validate its arguments against. Searches
+    /// `list_tools` by default; routers that can look a tool up directly should override it.
+    fn tool_input_schema(&self, name: &str) -> Option<Value> {
+        self.list_tools()
+            .into_iter()
+            .find(|tool| tool.name == name)
+            .map(|tool| tool.input_schema)
+    }
+
     fn handle_initialize(
         &self,
         req: JsonRpcRequest,
     ) -> impl Future<Output = Result<JsonRpcResponse, RouterError>> {
         async move {
+            // Agree to the client's revision if we support it, and offer our latest otherwise
+            let requested = req
+                .params
+                .as_ref()
+                .and_then(|params| params.get("protocolVersion"))
+                .and_then(Value::as_str);
+            let protocol_version = requested
+                .filter(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(version))
+                .unwrap_or(LATEST_PROTOCOL_VERSION);
             let result = InitializeResult {
-                protocol_version: "2024-11-05".to_string(),
+                protocol_version: protocol_version.to_string(),
                 capabilities: self.capabilities().clone(),
                 server_info: Implementation {
                     name: self.name(),
@@ -157,15 +196,45 @@ pub trait Router: 'static {
 
             let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
 
-            let result = match self.call_tool(name, arguments).await {
-                Ok(result) => CallToolResult {
-                    content: result,
-                    is_error: None,
-                },
-                Err(err) => CallToolResult {
-                    content: vec![Content::text(err.to_string())],
+            let invalid = match self.argument_validation() {
+                ArgumentValidation::Off => None,
+                mode => self
+                    .tool_input_schema(name)
+                    .and_then(|schema| {
+                        // Leaving out the arguments is the same as passing none
+                        let empty = Value::Object(Default::default());
+                        let arguments = if arguments.is_null() {
+                            &empty
+                        } else {
+                            &arguments
+                        };
+                        validate(&schema, arguments).err()
+                    })
+                    .map(|errors| {
+                        let message =
+                            format!("Invalid arguments for tool {name}: {}", describe(&errors));
+                        (mode, message)
+                    }),
+            };
+
+            let result = match invalid {
+                Some((ArgumentValidation::InvalidParams, message)) => {
+                    return Err(RouterError::InvalidParams(message))
+                }
+                Some((_, message)) => CallToolResult {
+                    content: vec![Content::text(message)],
                     is_error: Some(true),
                 },
+                None => match self.call_tool(name, arguments).await {
+                    Ok(result) => CallToolResult {
+                        content: result,
+                        is_error: None,
+                    },
+                    Err(err) => CallToolResult {
+                        content: vec![Content::text(err.to_string())],
+                        is_error: Some(true),
+                    },
+                },
             };
 
             let result = serde_json::to_value(result)
@@ -231,7 +300,10 @@ pub trait Router: 'static {
         async move {
             let prompts = self.list_prompts();
 
-            let result = ListPromptsResult { prompts };
+            let result = ListPromptsResult {
+                prompts,
+                next_cursor: None,
+            };
 
             let result = serde_json::to_value(result)
                 .map_err(|e| RouterError::Internal(format!("JSON serialization error: {}", e)))?;
@@ -363,8 +435,16 @@ pub trait Router: 'static {
     }
 }
 
+/// Serves a `Router` as a `Service`, e.g. to `Server`, which keeps track of the session with the
+/// client and refuses the requests its state doesn't allow.
 pub struct RouterService<T>(pub T);
 
+impl<T> RouterService<T> {
+    pub fn new(router: T) -> Self {
+        Self(router)
+    }
+}
+
 impl<T> Service<SendableMessage> for RouterService<T>
 where
     T: Router + Clone + 'static,
@@ -382,7 +462,9 @@ where
 
         Box::pin(async move {
             if let SendableMessage::Request(req) = req {
+                let id = req.id.clone();
                 let result = match req.method.as_str() {
+                    "ping" => Ok(JsonRpcResponse::success(req.id, serde_json::json!({}))),
                     "initialize" => this.handle_initialize(req).await,
                     "tools/list" => this.handle_tools_list(req).await,
                     "tools/call" => this.handle_tools_call(req).await,
@@ -399,9 +481,20 @@ where
                     }
                 };
 
-                result.map(Some).map_err(BoxError::from)
+                // Answer with the error's own code, e.g. invalid params, rather than failing the
+                // request as a whole
+                let response = result.unwrap_or_else(|err| JsonRpcResponse::error(id, err.into()));
+                Ok(Some(response))
             } else {
-                return Err(RouterError::Unsupported("Unsupported message type.".into()).into());
+                // The server only passes the initialized notification on when it completes the
+                // handshake
+                if let SendableMessage::Notification(notification) = req {
+                    if notification.method == "notifications/initialized" {
+                        this.on_initialized();
+                    }
+                }
+                // Notifications never get a response
+                Ok(None)
             }
         })
     }
@@ -1,5 +1,6 @@
 use crate::context::Inject;
-use crate::{context::Context, router::CapabilitiesBuilder, Router};
+use crate::BuildError;
+use crate::{context::Context, router::CapabilitiesBuilder, ArgumentValidation, Peer, Router};
 use async_trait::async_trait;
 use mcp_core::{
     handler::{PromptError, ResourceError},
@@ -8,7 +9,9 @@ use mcp_core::{
 };
 use serde_json::Value;
 use std::rc::Rc;
+use std::time::Duration;
 use std::{collections::HashMap, future::Future, pin::Pin};
+use tokio::sync::Semaphore;
 
 #[async_trait(?Send)]
 pub trait CtxToolHandler: 'static {
@@ -21,11 +24,68 @@ pub trait CtxToolHandler: 'static {
     /// JSON schema describing the tool's parameters
     fn schema(&self) -> Value;
 
+    /// Limits on running the tool, e.g. from `#[tool(timeout = "30s", max_concurrency = 2)]`.
+    /// Limits set on the `MCPServerBuilder` take precedence.
+    fn limits(&self) -> ToolLimits {
+        ToolLimits::default()
+    }
+
     /// Execute the tool with the given parameters
     async fn call(&self, context: &Context, params: Value) -> ToolResult<Value>;
 }
 
-type Tools = HashMap<String, Rc<dyn CtxToolHandler>>;
+/// Limits on running a tool. Calls that exceed them fail with an error result instead of holding
+/// up the client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolLimits {
+    timeout: Option<Duration>,
+    max_concurrency: Option<usize>,
+}
+
+impl ToolLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Zero would refuse every call, so `MCPServerBuilder::build` fails if a tool ends up with it.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    /// How long a call may run before it is abandoned
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// How many calls may run at once. Calls beyond that are refused rather than queued.
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency
+    }
+
+    /// These limits, with those not set taken from `fallback`.
+    fn or(self, fallback: ToolLimits) -> ToolLimits {
+        ToolLimits {
+            timeout: self.timeout.or(fallback.timeout),
+            max_concurrency: self.max_concurrency.or(fallback.max_concurrency),
+        }
+    }
+}
+
+/// A tool, with what's needed to enforce its limits.
+struct RegisteredTool {
+    handler: Rc<dyn CtxToolHandler>,
+    timeout: Option<Duration>,
+    /// One permit per call allowed to run at once
+    permits: Option<(Rc<Semaphore>, usize)>,
+}
+
+type Tools = HashMap<String, Rc<RegisteredTool>>;
 
 /// A higher-level server that handles MCP requests.
 #[derive(Clone)]
@@ -34,6 +94,8 @@ pub struct MCPServer {
     description: String,
     tools: Rc<Tools>,
     ctx: Rc<Context>,
+    peer: Peer,
+    argument_validation: ArgumentValidation,
 }
 
 /// Build an MCPServer. Tools and structs are defined when the MCPServer is built. They cannot be
@@ -43,6 +105,9 @@ pub struct MCPServerBuilder {
     description: String,
     tools: HashMap<String, Rc<dyn CtxToolHandler>>,
     ctx: Context,
+    default_limits: ToolLimits,
+    tool_limits: HashMap<String, ToolLimits>,
+    argument_validation: ArgumentValidation,
 }
 
 impl MCPServerBuilder {
@@ -52,6 +117,9 @@ impl MCPServerBuilder {
             description,
             tools: HashMap::new(),
             ctx: Context::default(),
+            default_limits: ToolLimits::default(),
+            tool_limits: HashMap::new(),
+            argument_validation: ArgumentValidation::default(),
         }
     }
 
@@ -65,13 +133,72 @@ impl MCPServerBuilder {
         self
     }
 
-    pub fn build(self) -> MCPServer {
-        MCPServer {
+    /// Limits for tools that don't set their own.
+    pub fn with_default_limits(mut self, limits: ToolLimits) -> Self {
+        self.default_limits = limits;
+        self
+    }
+
+    /// Limits for the tool called `name`, overriding those set in its `#[tool]` attribute.
+    pub fn with_tool_limits(mut self, name: impl Into<String>, limits: ToolLimits) -> Self {
+        self.tool_limits.insert(name.into(), limits);
+        self
+    }
+
+    /// Check tool arguments against each tool's schema before calling it, and answer calls with
+    /// invalid arguments as `validation` says.
+    pub fn with_argument_validation(mut self, validation: ArgumentValidation) -> Self {
+        self.argument_validation = validation;
+        self
+    }
+
+    /// Build the server. Fails if a tool's limits, after applying the defaults, are invalid.
+    pub fn build(mut self) -> Result<MCPServer, BuildError> {
+        // Tool handlers can reach the client through an injected `Peer`
+        let peer = Peer::new();
+        self.ctx.insert(Inject::new(peer.clone()));
+
+        let tools = self
+            .tools
+            .into_iter()
+            .map(|(name, handler)| {
+                let limits = self
+                    .tool_limits
+                    .get(&name)
+                    .copied()
+                    .unwrap_or_default()
+                    .or(handler.limits())
+                    .or(self.default_limits);
+                if limits.max_concurrency == Some(0) {
+                    return Err(BuildError::ZeroMaxConcurrency(name));
+                }
+                let tool = RegisteredTool {
+                    handler,
+                    timeout: limits.timeout,
+                    permits: limits
+                        .max_concurrency
+                        .map(|max| (Rc::new(Semaphore::new(max)), max)),
+                };
+                Ok((name, Rc::new(tool)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(MCPServer {
             name: self.name,
             description: self.description,
-            tools: Rc::new(self.tools),
+            tools: Rc::new(tools),
             ctx: Rc::new(self.ctx),
-        }
+            peer,
+            argument_validation: self.argument_validation,
+        })
+    }
+}
+
+impl MCPServer {
+    /// The handle tool handlers use to send requests to the client. Pass it to
+    /// `Server::with_peer` so it's connected while the server runs.
+    pub fn peer(&self) -> Peer {
+        self.peer.clone()
     }
 }
 
@@ -79,7 +206,13 @@ impl Router for MCPServer {
     fn list_tools(&self) -> Vec<Tool> {
         self.tools
             .iter()
-            .map(|(name, tool)| Tool::new(name.clone(), tool.description(), tool.schema()))
+            .map(|(name, tool)| {
+                Tool::new(
+                    name.clone(),
+                    tool.handler.description(),
+                    tool.handler.schema(),
+                )
+            })
             .collect()
     }
 
@@ -91,11 +224,18 @@ impl Router for MCPServer {
         self.description.clone()
     }
 
+    fn argument_validation(&self) -> ArgumentValidation {
+        self.argument_validation
+    }
+
+    fn tool_input_schema(&self, name: &str) -> Option<Value> {
+        self.tools.get(name).map(|tool| tool.handler.schema())
+    }
+
     fn capabilities(&self) -> mcp_core::protocol::ServerCapabilities {
+        // Resources and prompts aren't supported yet, so they aren't advertised
         CapabilitiesBuilder::new()
-            .with_tools(self.tools.len() > 0)
-            .with_resources(false, false)
-            .with_prompts(false)
+            .with_tools(!self.tools.is_empty())
             .build()
     }
 
@@ -104,9 +244,27 @@ impl Router for MCPServer {
         tool_name: &str,
         arguments: serde_json::Value,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<Content>, ToolError>> + '_>> {
-        let tool = self.tools.get(tool_name).unwrap().clone();
+        let tool = self.tools.get(tool_name).cloned();
+        let tool_name = tool_name.to_string();
         Box::pin(async move {
-            let res = tool.call(&self.ctx, arguments).await?;
+            let tool = tool.ok_or_else(|| ToolError::NotFound(tool_name.clone()))?;
+            let _permit = match &tool.permits {
+                Some((permits, max)) => Some(permits.try_acquire().map_err(|_| {
+                    ToolError::ExecutionError(format!(
+                        "Tool {tool_name} is busy: {max} calls are already running"
+                    ))
+                })?),
+                None => None,
+            };
+            let call = tool.handler.call(&self.ctx, arguments);
+            let res = match tool.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, call).await.map_err(|_| {
+                    ToolError::ExecutionError(format!(
+                        "Tool {tool_name} timed out after {timeout:?}"
+                    ))
+                })??,
+                None => call.await?,
+            };
             let contents = match res {
                 serde_json::Value::Number(n) => vec![Content::text(n.to_string())],
                 serde_json::Value::String(s) => vec![Content::text(s)],
@@ -123,24 +281,212 @@ impl Router for MCPServer {
     }
 
     fn list_resources(&self) -> Vec<mcp_core::resource::Resource> {
-        todo!()
+        Vec::new()
     }
 
     fn read_resource(
         &self,
-        _uri: &str,
+        uri: &str,
     ) -> Pin<Box<dyn Future<Output = Result<String, ResourceError>> + 'static>> {
-        todo!()
+        let uri = uri.to_string();
+        Box::pin(async move { Err(ResourceError::NotFound(uri)) })
     }
 
     fn list_prompts(&self) -> Vec<Prompt> {
-        todo!()
+        Vec::new()
     }
 
     fn get_prompt(
         &self,
-        _prompt_name: &str,
+        prompt_name: &str,
     ) -> Pin<Box<dyn Future<Output = Result<String, PromptError>> + 'static>> {
-        todo!()
+        let prompt_name = prompt_name.to_string();
+        Box::pin(async move { Err(PromptError::NotFound(prompt_name)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::RouterService;
+    use mcp_core::protocol::{JsonRpcRequest, JsonRpcResponse, MessageId};
+    use mcp_core::transport::SendableMessage;
+    use mcp_macros::tool;
+    use tower_service::Service;
+
+    #[tool(description = "Sleep", timeout = "50ms", max_concurrency = 1)]
+    async fn sleep(millis: u64) -> Result<(), ToolError> {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        Ok(())
+    }
+
+    fn server() -> MCPServerBuilder {
+        MCPServerBuilder::new("test".to_string(), "".to_string()).with_tool(Sleep)
+    }
+
+    fn request(id: u64, method: &str, params: serde_json::Value) -> SendableMessage {
+        SendableMessage::Request(JsonRpcRequest::new(
+            MessageId::Num(id),
+            method.to_string(),
+            Some(params),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_tool_timeout() {
+        let server = server().build().unwrap();
+        assert!(server
+            .call_tool("sleep", serde_json::json!({ "millis": 10 }))
+            .await
+            .is_ok());
+
+        let result = server
+            .call_tool("sleep", serde_json::json!({ "millis": 1000 }))
+            .await;
+        let Err(ToolError::ExecutionError(message)) = result else {
+            panic!("Expected a timeout, got {result:?}");
+        };
+        assert!(message.contains("timed out after 50ms"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn test_tool_concurrency_limit() {
+        let server = server().build().unwrap();
+        let (first, second) = tokio::join!(
+            server.call_tool("sleep", serde_json::json!({ "millis": 20 })),
+            server.call_tool("sleep", serde_json::json!({ "millis": 20 })),
+        );
+        assert!(first.is_ok());
+        let Err(ToolError::ExecutionError(message)) = second else {
+            panic!("Expected the second call to be refused, got {second:?}");
+        };
+        assert!(message.contains("busy"), "{message}");
+
+        // The permit is released once the first call is done
+        assert!(server
+            .call_tool("sleep", serde_json::json!({ "millis": 1 }))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_zero_max_concurrency_is_refused() {
+        let result = server()
+            .with_tool_limits("sleep", ToolLimits::new().with_max_concurrency(0))
+            .build();
+        assert!(matches!(result, Err(BuildError::ZeroMaxConcurrency(name)) if name == "sleep"));
+    }
+
+    #[tokio::test]
+    async fn test_builder_limits_override_attribute() {
+        let server = server()
+            .with_tool_limits("sleep", ToolLimits::new().with_max_concurrency(2))
+            .with_default_limits(ToolLimits::new().with_timeout(Duration::from_millis(10)))
+            .build()
+            .unwrap();
+        let tool = &server.tools["sleep"];
+        // The builder raises the concurrency, and the attribute's timeout wins over the default
+        assert_eq!(tool.timeout, Some(Duration::from_millis(50)));
+        assert_eq!(tool.permits.as_ref().map(|(_, max)| *max), Some(2));
+
+        let (first, second) = tokio::join!(
+            server.call_tool("sleep", serde_json::json!({ "millis": 20 })),
+            server.call_tool("sleep", serde_json::json!({ "millis": 20 })),
+        );
+        assert!(first.is_ok() && second.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tool() {
+        let result = server()
+            .build()
+            .unwrap()
+            .call_tool("missing", serde_json::json!({}))
+            .await;
+        assert!(matches!(result, Err(ToolError::NotFound(name)) if name == "missing"));
+    }
+
+    #[tokio::test]
+    async fn test_argument_validation() {
+        use crate::RouterError;
+
+        let call = |arguments| {
+            JsonRpcRequest::new(
+                MessageId::Num(1),
+                "tools/call".to_string(),
+                Some(serde_json::json!({ "name": "sleep", "arguments": arguments })),
+            )
+        };
+
+        let strict = server()
+            .with_argument_validation(ArgumentValidation::InvalidParams)
+            .build()
+            .unwrap();
+        let result = strict
+            .handle_tools_call(call(serde_json::json!({ "millis": "soon" })))
+            .await;
+        let Err(RouterError::InvalidParams(message)) = result else {
+            panic!("Expected invalid params, got {result:?}");
+        };
+        assert_eq!(
+            message,
+            "Invalid arguments for tool sleep: /millis: expected integer, got string"
+        );
+        assert!(strict
+            .handle_tools_call(call(serde_json::json!({ "millis": 1 })))
+            .await
+            .is_ok());
+
+        // On the wire, the rejection is an INVALID_PARAMS error rather than an internal one
+        let response = RouterService(strict)
+            .call(SendableMessage::Request(call(
+                serde_json::json!({ "millis": "soon" }),
+            )))
+            .await
+            .unwrap();
+        let Some(JsonRpcResponse::Error { error, .. }) = response else {
+            panic!("Expected an error, got {response:?}");
+        };
+        assert_eq!(error.code, mcp_core::protocol::INVALID_PARAMS);
+
+        let lenient = server()
+            .with_argument_validation(ArgumentValidation::ToolError)
+            .build()
+            .unwrap();
+        let response = lenient
+            .handle_tools_call(call(serde_json::Value::Null))
+            .await
+            .unwrap();
+        let JsonRpcResponse::Success { result, .. } = response else {
+            panic!("Expected a result, got {response:?}");
+        };
+        assert_eq!(result["isError"], true);
+        assert_eq!(
+            result["content"][0]["text"],
+            "Invalid arguments for tool sleep: (root): missing required property `millis`"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_protocol_version_negotiation() {
+        let protocol_version = |requested: &str| {
+            let mut service = RouterService::new(server().build().unwrap());
+            let initialize = request(
+                0,
+                "initialize",
+                serde_json::json!({ "protocolVersion": requested }),
+            );
+            async move {
+                let Some(JsonRpcResponse::Success { result, .. }) =
+                    service.call(initialize).await.unwrap()
+                else {
+                    panic!("Expected a successful response");
+                };
+                result["protocolVersion"].clone()
+            }
+        };
+        assert_eq!(protocol_version("2025-06-18").await, "2025-06-18");
+        assert_eq!(protocol_version("2024-11-05").await, "2024-11-05");
+        assert_eq!(protocol_version("1.0.0").await, "2025-06-18");
     }
 }
@@ -0,0 +1,205 @@
+use mcp_core::protocol::JsonRpcResponse;
+
+use crate::SessionError;
+
+/// The lifecycle of a connection with a client.
+///
+/// A session starts `Uninitialized`. The client's `initialize` request moves it to `Initializing`
+/// as soon as it arrives, and the `notifications/initialized` notification confirming the
+/// handshake moves it to `Ready`. If `initialize` fails, the session goes back to `Uninitialized`.
+/// Once the server stops accepting requests it is `ShuttingDown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    Uninitialized,
+    Initializing,
+    Ready,
+    ShuttingDown,
+}
+
+/// Tracks the state of a single connection and decides which messages it may process.
+///
+/// Before initialization only `initialize` and `ping` are accepted. Once `initialize` has arrived a
+/// second one is refused, and so is anything but `ping` until the server has answered it. From
+/// then on requests are accepted, as the spec allows clients to send them as soon as the server
+/// has responded. If `initialize` fails, the client may try again.
+#[derive(Debug)]
+pub struct Session {
+    state: SessionState,
+    /// Whether `initialize` has been answered successfully
+    initialized: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            state: SessionState::Uninitialized,
+            initialized: false,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Check whether a request for `method` may be processed.
+    pub fn on_request(&mut self, method: &str) -> Result<(), SessionError> {
+        match (self.state, method) {
+            (SessionState::ShuttingDown, _) => Err(SessionError::ShuttingDown),
+            (_, "ping") => Ok(()),
+            (SessionState::Uninitialized, "initialize") => {
+                self.state = SessionState::Initializing;
+                Ok(())
+            }
+            (SessionState::Uninitialized, _) => Err(SessionError::NotInitialized),
+            (_, "initialize") => Err(SessionError::AlreadyInitialized),
+            _ if !self.initialized => Err(SessionError::NotInitialized),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the answer to `initialize`: requests are accepted once it succeeded, and the client
+    /// may try again if it failed.
+    pub fn on_initialize_response(&mut self, response: &JsonRpcResponse) {
+        if self.state != SessionState::Initializing || self.initialized {
+            return;
+        }
+        match response {
+            JsonRpcResponse::Success { .. } => self.initialized = true,
+            JsonRpcResponse::Error { .. } => self.state = SessionState::Uninitialized,
+        }
+    }
+
+    /// Advance the state on a notification from the client. Returns true if the notification
+    /// completed initialization.
+    pub fn on_notification(&mut self, method: &str) -> bool {
+        if method != "notifications/initialized" {
+            return false;
+        }
+        match self.state {
+            SessionState::Initializing if self.initialized => {
+                self.state = SessionState::Ready;
+                true
+            }
+            state => {
+                tracing::warn!(?state, "Unexpected initialized notification");
+                false
+            }
+        }
+    }
+
+    /// Stop accepting requests.
+    pub fn shut_down(&mut self) {
+        self.state = SessionState::ShuttingDown;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::protocol::{ErrorData, MessageId, INTERNAL_ERROR};
+
+    /// Accept `initialize`, and answer it successfully.
+    fn initialize(session: &mut Session) {
+        session.on_request("initialize").unwrap();
+        session.on_initialize_response(&JsonRpcResponse::success(
+            MessageId::Num(0),
+            serde_json::json!({}),
+        ));
+    }
+
+    #[test]
+    fn test_requests_rejected_before_initialize() {
+        let mut session = Session::new();
+        assert!(matches!(
+            session.on_request("tools/call"),
+            Err(SessionError::NotInitialized)
+        ));
+        assert!(session.on_request("ping").is_ok());
+        assert_eq!(session.state(), SessionState::Uninitialized);
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut session = Session::new();
+        assert!(session.on_request("initialize").is_ok());
+        assert_eq!(session.state(), SessionState::Initializing);
+
+        // Until initialize is answered, only pings get through
+        assert!(matches!(
+            session.on_request("initialize"),
+            Err(SessionError::AlreadyInitialized)
+        ));
+        assert!(matches!(
+            session.on_request("tools/list"),
+            Err(SessionError::NotInitialized)
+        ));
+        assert!(session.on_request("ping").is_ok());
+        assert!(!session.on_notification("notifications/initialized"));
+
+        session.on_initialize_response(&JsonRpcResponse::success(
+            MessageId::Num(0),
+            serde_json::json!({}),
+        ));
+        assert_eq!(session.state(), SessionState::Initializing);
+        assert!(session.on_request("tools/list").is_ok());
+
+        assert!(session.on_notification("notifications/initialized"));
+        assert_eq!(session.state(), SessionState::Ready);
+
+        // The handshake only completes once
+        assert!(!session.on_notification("notifications/initialized"));
+    }
+
+    #[test]
+    fn test_failed_initialize_can_be_retried() {
+        let mut session = Session::new();
+        session.on_request("initialize").unwrap();
+        session.on_initialize_response(&JsonRpcResponse::error(
+            MessageId::Num(0),
+            ErrorData {
+                code: INTERNAL_ERROR,
+                message: "Not yet".to_string(),
+                data: None,
+            },
+        ));
+        assert_eq!(session.state(), SessionState::Uninitialized);
+        assert!(matches!(
+            session.on_request("tools/list"),
+            Err(SessionError::NotInitialized)
+        ));
+        assert!(session.on_request("initialize").is_ok());
+    }
+
+    #[test]
+    fn test_second_initialize_refused() {
+        let mut session = Session::new();
+        initialize(&mut session);
+        assert!(matches!(
+            session.on_request("initialize"),
+            Err(SessionError::AlreadyInitialized)
+        ));
+
+        session.on_notification("notifications/initialized");
+        assert!(matches!(
+            session.on_request("initialize"),
+            Err(SessionError::AlreadyInitialized)
+        ));
+    }
+
+    #[test]
+    fn test_shutting_down_rejects_everything() {
+        let mut session = Session::new();
+        initialize(&mut session);
+        session.shut_down();
+        assert!(matches!(
+            session.on_request("ping"),
+            Err(SessionError::ShuttingDown)
+        ));
+    }
+}
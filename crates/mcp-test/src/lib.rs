@@ -0,0 +1,369 @@
+use std::{path::Path, time::Duration};
+
+use mcp_client::{
+    client::{ClientCapabilities, ClientInfo},
+    transport::memory::MemoryTransportHandle,
+    Error, McpClient, McpClientTrait, McpService, MemoryTransport, Transport,
+};
+use mcp_core::{
+    protocol::{CallToolResult, InitializeResult, JsonRpcResponse},
+    transport::{duplex, SendableMessage},
+    Tool,
+};
+use mcp_server::{router::RouterService, BoxError, Router, Server, ServerError};
+use serde_json::Value;
+use tokio::sync::oneshot;
+use tower::{timeout::Timeout, Service};
+
+/// How long a request may take before the client gives up on it, so a stuck server fails the
+/// test instead of hanging it.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Set to rewrite snapshots instead of comparing against them.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "MCP_UPDATE_SNAPSHOTS";
+
+/// The client connected to the server under test.
+pub type TestClient = McpClient<Timeout<McpService<MemoryTransportHandle>>>;
+
+/// A server running in-process, with an initialized client connected to it.
+///
+/// The server gets a thread and runtime of its own, as `Server` isn't `Send`. It stops once the
+/// client goes away, when the `McpTest` is dropped or shut down.
+pub struct McpTest {
+    client: TestClient,
+    transport: MemoryTransport,
+    server_info: InitializeResult,
+    stopped: oneshot::Receiver<Result<(), ServerError>>,
+}
+
+impl McpTest {
+    /// Serve the router made by `router`, e.g. an `MCPServer` from `MCPServerBuilder::build`.
+    /// It is made on the server's thread, so it needn't be `Send`.
+    ///
+    /// Panics if the client can't initialize.
+    pub async fn start<R, F>(router: F) -> Self
+    where
+        R: Router + Clone,
+        F: FnOnce() -> R + Send + 'static,
+    {
+        Self::start_server(move || Server::new(RouterService::new(router()))).await
+    }
+
+    /// Run the server made by `server`, for tests that need to configure it, e.g. to connect an
+    /// `MCPServer`'s peer with `Server::with_peer`.
+    ///
+    /// Panics if the client can't initialize.
+    pub async fn start_server<S, F>(server: F) -> Self
+    where
+        S: Service<SendableMessage, Response = Option<JsonRpcResponse>>,
+        S::Error: Into<BoxError>,
+        F: FnOnce() -> Server<S> + Send + 'static,
+    {
+        let (client_end, server_end) = duplex();
+        let (stopped_tx, stopped) = oneshot::channel();
+        std::thread::Builder::new()
+            .name("mcp-test-server".to_string())
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to build the server's runtime");
+                let result = runtime.block_on(async move {
+                    server()
+                        .run(mcp_server::MemoryTransport::new(server_end))
+                        .await
+                });
+                let _ = stopped_tx.send(result);
+            })
+            .expect("Failed to spawn the server's thread");
+
+        let transport = MemoryTransport::new(client_end);
+        let handle = transport
+            .start()
+            .await
+            .expect("Failed to start the transport");
+        let mut client = McpClient::new(McpService::with_timeout(handle, REQUEST_TIMEOUT))
+            // Leave argument checks to the server, which is what's under test
+            .with_argument_validation(false);
+        let server_info = client
+            .initialize(
+                ClientInfo {
+                    name: "mcp-test".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                ClientCapabilities::default(),
+            )
+            .await
+            .expect("Failed to initialize");
+
+        Self {
+            client,
+            transport,
+            server_info,
+            stopped,
+        }
+    }
+
+    /// The client, for anything the helpers don't cover.
+    pub fn client(&self) -> &TestClient {
+        &self.client
+    }
+
+    /// What the server answered to `initialize`.
+    pub fn server_info(&self) -> &InitializeResult {
+        &self.server_info
+    }
+
+    /// Every page of `tools/list`. Panics if listing fails.
+    pub async fn tools(&self) -> Vec<Tool> {
+        let mut tools = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self
+                .client
+                .list_tools(cursor)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to list tools: {e}"));
+            tools.extend(page.tools);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return tools;
+            }
+        }
+    }
+
+    /// Panics unless the server lists a tool called `name`, which is returned.
+    pub async fn assert_tool_exists(&self, name: &str) -> Tool {
+        let tools = self.tools().await;
+        let names: Vec<_> = tools.iter().map(|tool| tool.name.clone()).collect();
+        tools
+            .into_iter()
+            .find(|tool| tool.name == name)
+            .unwrap_or_else(|| panic!("Expected a tool called {name}, found {names:?}"))
+    }
+
+    /// Panics if the server lists a tool called `name`.
+    pub async fn assert_tool_missing(&self, name: &str) {
+        if self.tools().await.iter().any(|tool| tool.name == name) {
+            panic!("Expected no tool called {name}");
+        }
+    }
+
+    /// Call the tool `name`, for the assertions on `CallOutcome`.
+    pub async fn call(&self, name: &str, arguments: Value) -> CallOutcome {
+        CallOutcome {
+            tool: name.to_string(),
+            result: self.client.call_tool(name, arguments).await,
+        }
+    }
+
+    /// The `tools/list` output as pretty-printed JSON, sorted by name so it doesn't depend on the
+    /// order the server lists tools in.
+    pub async fn tools_snapshot(&self) -> String {
+        let mut tools = self.tools().await;
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut snapshot = serde_json::to_string_pretty(&tools).expect("Tools should serialize");
+        snapshot.push('\n');
+        snapshot
+    }
+
+    /// Compare `tools_snapshot` with the file at `path`. A missing file fails the test, unless
+    /// `MCP_UPDATE_SNAPSHOTS` is set, which writes the file instead of comparing against it.
+    pub async fn assert_tools_snapshot(&self, path: impl AsRef<Path>) {
+        let update = std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some();
+        check_snapshot(path.as_ref(), &self.tools_snapshot().await, update);
+    }
+
+    /// Disconnect, and wait for the server to stop. Returns how the server's run ended.
+    pub async fn shutdown(self) -> Result<(), ServerError> {
+        let _ = self.transport.close().await;
+        drop(self.client);
+        self.stopped.await.expect("The server's thread panicked")
+    }
+}
+
+/// The outcome of a tool call, with assertions that panic with the call's details.
+#[derive(Debug)]
+pub struct CallOutcome {
+    tool: String,
+    result: Result<CallToolResult, Error>,
+}
+
+impl CallOutcome {
+    pub fn into_result(self) -> Result<CallToolResult, Error> {
+        self.result
+    }
+
+    /// Panics unless the call succeeded and the tool didn't report an error.
+    #[track_caller]
+    pub fn expect_ok(self) -> CallToolResult {
+        match self.result {
+            Ok(result) if result.is_error != Some(true) => result,
+            Ok(result) => panic!(
+                "Expected {} to succeed, it failed with: {}",
+                self.tool,
+                text(&result)
+            ),
+            Err(e) => panic!("Expected {} to succeed, the call failed: {e}", self.tool),
+        }
+    }
+
+    /// Panics unless the call succeeded with `expected` as its text, the text items of its
+    /// content joined by newlines.
+    #[track_caller]
+    pub fn expect_text(self, expected: &str) -> CallToolResult {
+        let tool = self.tool.clone();
+        let result = self.expect_ok();
+        let actual = text(&result);
+        assert_eq!(actual, expected, "Unexpected text from {tool}");
+        result
+    }
+
+    /// Panics unless the tool reported an error. Returns the error's text.
+    #[track_caller]
+    pub fn expect_tool_error(self) -> String {
+        match self.result {
+            Ok(result) if result.is_error == Some(true) => text(&result),
+            Ok(result) => panic!(
+                "Expected {} to report an error, it returned: {}",
+                self.tool,
+                text(&result)
+            ),
+            Err(e) => panic!(
+                "Expected {} to report an error, the call failed: {e}",
+                self.tool
+            ),
+        }
+    }
+
+    /// Panics unless the server answered the call with a JSON-RPC error with `code`. Returns the
+    /// error's message.
+    #[track_caller]
+    pub fn expect_error_code(self, code: i32) -> String {
+        match self.result {
+            Err(Error::RpcError {
+                code: actual,
+                message,
+                ..
+            }) if actual == code => message,
+            Err(e) => panic!(
+                "Expected {} to fail with code {code}, it failed with: {e}",
+                self.tool
+            ),
+            Ok(result) => panic!(
+                "Expected {} to fail with code {code}, it returned: {}",
+                self.tool,
+                text(&result)
+            ),
+        }
+    }
+}
+
+fn text(result: &CallToolResult) -> String {
+    result
+        .content
+        .iter()
+        .filter_map(|content| content.as_text())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Compare `snapshot` with the file at `path`, or write it there when `update` is set.
+fn check_snapshot(path: &Path, snapshot: &str, update: bool) {
+    if update {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create the snapshot directory");
+        }
+        std::fs::write(path, snapshot)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {e}", path.display()));
+        return;
+    }
+
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => panic!(
+            "There is no snapshot at {}. Set {UPDATE_SNAPSHOTS_VAR} to write it.",
+            path.display()
+        ),
+        Err(e) => panic!("Failed to read {}: {e}", path.display()),
+    };
+    if snapshot != expected {
+        panic!(
+            "tools/list doesn't match {}. Set {UPDATE_SNAPSHOTS_VAR} to update it.\n\
+             --- expected\n{expected}--- actual\n{snapshot}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_core::{handler::ToolError, protocol::INVALID_PARAMS};
+    use mcp_macros::tool;
+    use mcp_server::{context::Inject, server::MCPServerBuilder, ArgumentValidation, MCPServer};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tool(description = "Add to the counter, and return its value")]
+    async fn increment(counter: Inject<AtomicU32>, quantity: u32) -> Result<u32, ToolError> {
+        Ok(counter.fetch_add(quantity, Ordering::SeqCst) + quantity)
+    }
+
+    fn counter() -> MCPServer {
+        MCPServerBuilder::new("counter".to_string(), "A counter".to_string())
+            .with_tool(Increment)
+            .with_state(Inject::new(AtomicU32::new(0)))
+            .with_argument_validation(ArgumentValidation::InvalidParams)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_call_assertions() {
+        let test = McpTest::start(counter).await;
+        assert_eq!(test.server_info().server_info.name, "counter");
+        test.assert_tool_exists("increment").await;
+        test.assert_tool_missing("decrement").await;
+
+        test.call("increment", json!({ "quantity": 2 }))
+            .await
+            .expect_text("2");
+        test.call("increment", json!({ "quantity": 3 }))
+            .await
+            .expect_text("5");
+        let message = test
+            .call("increment", json!({ "quantity": "two" }))
+            .await
+            .expect_error_code(INVALID_PARAMS);
+        assert!(message.contains("/quantity"), "{message}");
+        let message = test.call("decrement", json!({})).await.expect_tool_error();
+        assert!(message.contains("decrement"), "{message}");
+
+        test.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tools_snapshot() {
+        let path = std::env::temp_dir().join(format!("mcp-test-{}/tools.json", std::process::id()));
+        let test = McpTest::start(counter).await;
+
+        // Only written when asked to, then compared against
+        let missing = std::panic::catch_unwind(|| check_snapshot(&path, "[]\n", false));
+        assert!(missing.is_err());
+        assert!(!path.exists());
+        check_snapshot(&path, &test.tools_snapshot().await, true);
+        test.assert_tools_snapshot(&path).await;
+        let snapshot: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(snapshot[0]["name"], "increment");
+
+        std::fs::write(&path, "[]\n").unwrap();
+        let mismatch = tokio::spawn({
+            let path = path.clone();
+            async move { test.assert_tools_snapshot(&path).await }
+        });
+        assert!(mismatch.await.unwrap_err().is_panic());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
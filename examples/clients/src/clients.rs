@@ -1,7 +1,7 @@
 use mcp_client::{
     client::{ClientCapabilities, ClientInfo, McpClient, McpClientTrait},
     transport::{SseTransport, StdioTransport, Transport},
-    McpService,
+    McpClientPool, McpService,
 };
 use rand::Rng;
 use rand::SeedableRng;
@@ -21,111 +21,91 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let transport1 = StdioTransport::new("uvx", vec!["mcp-server-git".to_string()], HashMap::new());
     let handle1 = transport1.start().await?;
     let service1 = McpService::with_timeout(handle1, Duration::from_secs(30));
-    let client1 = McpClient::new(service1);
 
     let transport2 = StdioTransport::new("uvx", vec!["mcp-server-git".to_string()], HashMap::new());
     let handle2 = transport2.start().await?;
     let service2 = McpService::with_timeout(handle2, Duration::from_secs(30));
-    let client2 = McpClient::new(service2);
 
-    let transport3 = SseTransport::new("http://localhost:8000/sse", HashMap::new());
+    let transport3 = SseTransport::new("http://localhost:8000/sse");
     let handle3 = transport3.start().await?;
     let service3 = McpService::with_timeout(handle3, Duration::from_secs(10));
-    let client3 = McpClient::new(service3);
 
-    // Initialize both clients
-    let mut clients: Vec<Box<dyn McpClientTrait>> =
-        vec![Box::new(client1), Box::new(client2), Box::new(client3)];
+    let mut client1 = McpClient::new(service1);
+    let mut client2 = McpClient::new(service2);
+    let mut client3 = McpClient::new(service3);
+    initialize("git1", &mut client1).await?;
+    initialize("git2", &mut client2).await?;
+    initialize("echo", &mut client3).await?;
 
-    // Initialize all clients
-    for (i, client) in clients.iter_mut().enumerate() {
-        let info = ClientInfo {
-            name: format!("example-client-{}", i + 1),
-            version: "1.0.0".to_string(),
-        };
-        let capabilities = ClientCapabilities::default();
+    // Pool the clients under one catalog
+    let pool = McpClientPool::new()
+        .with_client("git1", client1)?
+        .with_client("git2", client2)?
+        .with_client("echo", client3)?;
 
-        println!("\nInitializing client {}", i + 1);
-        let init_result = client.initialize(info, capabilities).await?;
-        println!("Client {} initialized: {:?}", i + 1, init_result);
+    // Both git servers have the same tools, so they are prefixed with the server name
+    let catalog = pool.catalog().await;
+    for tool in &catalog.tools {
+        println!("\nTool: {}", tool.name);
     }
-
-    // List tools for all clients
-    for (i, client) in clients.iter_mut().enumerate() {
-        let tools = client.list_tools(None).await?;
-        println!("\nClient {} tools: {:?}", i + 1, tools);
+    for (server, error) in &catalog.errors {
+        println!("\nCould not list {server}: {error}");
     }
 
     println!("\n\n----------------------------------\n\n");
 
-    // Wrap clients in Arc before spawning tasks
-    let clients = Arc::new(clients);
+    // Wrap the pool in Arc before spawning tasks
+    let pool = Arc::new(pool);
     let mut handles = vec![];
 
     for i in 0..20 {
-        let clients = Arc::clone(&clients);
+        let pool = Arc::clone(&pool);
         let handle = tokio::spawn(async move {
-            // let mut rng = rand::thread_rng();
             let mut rng = rand::rngs::StdRng::from_entropy();
             tokio::time::sleep(Duration::from_millis(rng.gen_range(5..50))).await;
 
-            // Randomly select an operation
-            match rng.gen_range(0..4) {
-                0 => {
-                    println!("\n{i}: Listing tools for client 1 (stdio)");
-                    match clients[0].list_tools(None).await {
-                        Ok(tools) => {
-                            println!("  {i}: -> Got tools, first one: {:?}", tools.tools.first())
-                        }
-                        Err(e) => println!("  {i}: -> Error: {}", e),
-                    }
-                }
-                1 => {
-                    println!("\n{i}: Calling tool for client 2 (stdio)");
-                    match clients[1]
-                        .call_tool("git_status", serde_json::json!({ "repo_path": "." }))
-                        .await
-                    {
-                        Ok(result) => println!(
-                            "  {i}: -> Tool execution result, is_error: {:?}",
-                            result.is_error
-                        ),
-                        Err(e) => println!("  {i}: -> Error: {}", e),
-                    }
-                }
-                2 => {
-                    println!("\n{i}: Listing tools for client 3 (sse)");
-                    match clients[2].list_tools(None).await {
-                        Ok(tools) => {
-                            println!("  {i}: -> Got tools, first one: {:?}", tools.tools.first())
-                        }
-                        Err(e) => println!("  {i}: -> Error: {}", e),
-                    }
-                }
-                3 => {
-                    println!("\n{i}: Calling tool for client 3 (sse)");
-                    match clients[2]
-                            .call_tool(
-                                "echo_tool",
-                                serde_json::json!({ "message": "Client with SSE transport - calling a tool" }),
-                            )
-                            .await
-                        {
-                        Ok(result) => println!("  {i}: -> Tool execution result, is_error: {:?}", result.is_error),
-                        Err(e) => println!("  {i}: -> Error: {}", e),
-                    }
-                }
+            // Randomly select a tool to call
+            let (name, arguments) = match rng.gen_range(0..3) {
+                0 => ("git1__git_status", serde_json::json!({ "repo_path": "." })),
+                1 => ("git2__git_status", serde_json::json!({ "repo_path": "." })),
+                2 => (
+                    "echo_tool",
+                    serde_json::json!({ "message": "Client with SSE transport - calling a tool" }),
+                ),
                 _ => unreachable!(),
+            };
+            println!("\n{i}: Calling {name}");
+            match pool.call_tool(name, arguments).await {
+                Ok(result) => println!(
+                    "  {i}: -> Tool execution result, is_error: {:?}",
+                    result.is_error
+                ),
+                Err(e) => println!("  {i}: -> Error: {}", e),
             }
-            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
         });
         handles.push(handle);
     }
 
     // Wait for all tasks to complete
     for handle in handles {
-        handle.await.unwrap().unwrap();
+        handle.await?;
     }
 
     Ok(())
 }
+
+async fn initialize(
+    name: &str,
+    client: &mut impl McpClientTrait,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let info = ClientInfo {
+        name: format!("example-client-{name}"),
+        version: "1.0.0".to_string(),
+    };
+    let capabilities = ClientCapabilities::default();
+
+    println!("\nInitializing client {name}");
+    let init_result = client.initialize(info, capabilities).await?;
+    println!("Client {name} initialized: {:?}", init_result);
+    Ok(())
+}
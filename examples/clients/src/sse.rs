@@ -2,7 +2,6 @@ use anyhow::Result;
 use mcp_client::client::{ClientCapabilities, ClientInfo, McpClient, McpClientTrait};
 use mcp_client::transport::{SseTransport, Transport};
 use mcp_client::McpService;
-use std::collections::HashMap;
 use std::time::Duration;
 use tracing_subscriber::EnvFilter;
 
@@ -18,7 +17,7 @@ async fn main() -> Result<()> {
         .init();
 
     // Create the base transport
-    let transport = SseTransport::new("http://localhost:8000/sse", HashMap::new());
+    let transport = SseTransport::new("http://localhost:8000/sse");
 
     // Start transport
     let handle = transport.start().await?;
@@ -56,10 +56,10 @@ async fn main() -> Result<()> {
     let mcp_server = MCPServerBuilder::new(
         "Calculator".to_string(),
         "This server provides a calculator tool that can perform basic arithmetic operations. Use the 'calculator' tool to perform calculations.".to_string()
-    ).with_tool(Calculator).build();
+    ).with_tool(Calculator).build()?;
 
     // Create and run the server
-    let router = RouterService(mcp_server);
+    let router = RouterService::new(mcp_server);
     let server = Server::new(router);
     let transport = ByteTransport::new(stdin(), stdout());
 
@@ -77,12 +77,23 @@ async fn main() -> Result<()> {
     .with_tool(GetValue)
     .with_state(Inject::new(counter))
     // TODO: Compile-time safety: can we ensure all contexts required by handlers are provided in the server?
-    .build();
+    .build()?;
 
-    let router = RouterService(mcp_server);
+    let router = RouterService::new(mcp_server);
     let server = Server::new(router);
     let transport = ByteTransport::new(stdin(), stdout());
 
+    // Finish in-flight tool calls when asked to stop, e.g. by a supervisor during deploys
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    let shutdown = async move {
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    };
+
     tracing::info!("Server initialized and ready to handle requests");
-    Ok(server.run(transport).await?)
+    let summary = server.run_until(transport, shutdown).await?;
+    tracing::info!(?summary, "Server stopped");
+    Ok(())
 }